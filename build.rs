@@ -0,0 +1,24 @@
+//! Embeds the names of the migrations into the binary, so `/readyz` can tell
+//! which of them the database lacks without the source tree next to it.
+
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    let mut migrations = Vec::new();
+    for entry in std::fs::read_dir("migrations").expect("migrations directory") {
+        let entry = entry.expect("migrations directory entry");
+        if entry.path().join("up.sql").is_file() {
+            migrations.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    migrations.sort();
+
+    let out = Path::new(&std::env::var("OUT_DIR").expect("OUT_DIR")).join("migrations.rs");
+    std::fs::write(
+        out,
+        format!("const MIGRATIONS: &[&str] = &{:?};\n", migrations),
+    )
+    .expect("migrations.rs");
+}
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS Game_status_history CASCADE;
+
+ALTER TABLE Games DROP COLUMN IF EXISTS Status;
@@ -0,0 +1,13 @@
+ALTER TABLE Games ADD COLUMN IF NOT EXISTS Status VARCHAR(20) NOT NULL DEFAULT 'concept'
+    CHECK(Status IN ('concept', 'pre-production', 'production', 'alpha', 'beta', 'released', 'sunset'));
+
+UPDATE Games SET Status = 'released' WHERE Release_date <= CURRENT_DATE;
+
+CREATE TABLE IF NOT EXISTS Game_status_history (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    From_status VARCHAR(20) NOT NULL,
+    To_status VARCHAR(20) NOT NULL,
+    Changed_at TIMESTAMP NOT NULL,
+    Changed_by VARCHAR(100) NOT NULL
+);
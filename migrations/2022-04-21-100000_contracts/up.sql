@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS Contracts (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    Publisher_id INT REFERENCES Publishers(ID) ON DELETE CASCADE NOT NULL,
+    Advance_payment MONEY NOT NULL,
+    Revenue_share SMALLINT NOT NULL CHECK(Revenue_share >= 0 AND Revenue_share <= 100),
+    Territory VARCHAR(100) NOT NULL,
+    Start_date DATE NOT NULL,
+    End_date DATE CHECK(End_date IS NULL OR End_date >= Start_date)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS Sales (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    Platform VARCHAR(100) NOT NULL,
+    Sale_date DATE NOT NULL,
+    Units INT NOT NULL CHECK(Units >= 0),
+    Gross MONEY NOT NULL,
+    Refunds MONEY NOT NULL DEFAULT 0,
+    CHECK(Refunds <= Gross)
+);
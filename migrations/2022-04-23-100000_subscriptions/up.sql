@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS Subscriptions (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    User_id INT REFERENCES Users(ID) ON DELETE CASCADE NOT NULL,
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    Plan VARCHAR(20) NOT NULL CHECK(Plan IN ('monthly', 'quarterly', 'yearly')),
+    Price MONEY NOT NULL,
+    Start_date DATE NOT NULL,
+    End_date DATE NOT NULL CHECK(End_date >= Start_date),
+    Periods_paid INT NOT NULL DEFAULT 1 CHECK(Periods_paid >= 1),
+    Cancelled_at TIMESTAMP
+);
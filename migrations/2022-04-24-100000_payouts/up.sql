@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS Payouts (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Investment_id INT REFERENCES Investments(ID) ON DELETE CASCADE NOT NULL,
+    Period_start DATE NOT NULL,
+    Period_end DATE NOT NULL CHECK(Period_end >= Period_start),
+    Net_income MONEY NOT NULL,
+    Amount MONEY NOT NULL,
+    Paid_at TIMESTAMP NOT NULL
+);
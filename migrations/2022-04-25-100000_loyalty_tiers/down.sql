@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS User_tier_history CASCADE;
+DROP TABLE IF EXISTS Loyalty_tiers CASCADE;
@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS Loyalty_tiers (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Name VARCHAR(50) NOT NULL,
+    Min_amount MONEY NOT NULL UNIQUE,
+    Badge VARCHAR(20) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS User_tier_history (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    User_id INT REFERENCES Users(ID) ON DELETE CASCADE NOT NULL,
+    Tier_id INT REFERENCES Loyalty_tiers(ID) ON DELETE SET NULL,
+    Tier_name VARCHAR(50) NOT NULL,
+    Total_donated MONEY NOT NULL,
+    Reached_at TIMESTAMP NOT NULL
+);
+
+INSERT INTO Loyalty_tiers (name, min_amount, badge)
+VALUES
+('Bronze', 100, '🥉'),
+('Silver', 500, '🥈'),
+('Gold', 1000, '🥇'),
+('Platinum', 5000, '💎');
+
+INSERT INTO User_tier_history (user_id, tier_id, tier_name, total_donated, reached_at)
+SELECT totals.user_id, tier.id, tier.name, totals.total, NOW()
+FROM (SELECT user_id, SUM(amount) AS total FROM Donations GROUP BY user_id) AS totals
+JOIN LATERAL (
+    SELECT id, name FROM Loyalty_tiers
+    WHERE min_amount <= totals.total
+    ORDER BY min_amount DESC
+    LIMIT 1
+) AS tier ON true;
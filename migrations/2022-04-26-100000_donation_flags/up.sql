@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS Donation_flags (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Donation_id INT REFERENCES Donations(ID) ON DELETE SET NULL,
+    User_id INT REFERENCES Users(ID) ON DELETE CASCADE NOT NULL,
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    Amount MONEY NOT NULL,
+    Donation_time TIMESTAMP NOT NULL,
+    Reasons VARCHAR(255) NOT NULL,
+    Flagged_at TIMESTAMP NOT NULL,
+    Resolution VARCHAR(20),
+    Reviewed_at TIMESTAMP
+);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS Donation_refunds CASCADE;
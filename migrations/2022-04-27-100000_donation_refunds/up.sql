@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS Donation_refunds (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Donation_id INT REFERENCES Donations(ID) ON DELETE CASCADE NOT NULL,
+    Amount MONEY NOT NULL CHECK (Amount > 0::money),
+    Reason VARCHAR(255) NOT NULL,
+    Refunded_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,12 @@
+ALTER TABLE Staff
+    DROP CONSTRAINT IF EXISTS Staff_employment_period,
+    DROP CONSTRAINT IF EXISTS Staff_manager_not_self,
+    DROP COLUMN IF EXISTS Phone,
+    DROP COLUMN IF EXISTS Email,
+    DROP COLUMN IF EXISTS Employment_end,
+    DROP COLUMN IF EXISTS Employment_start,
+    DROP COLUMN IF EXISTS Manager_id,
+    DROP COLUMN IF EXISTS Job_title,
+    DROP COLUMN IF EXISTS Department_id;
+
+DROP TABLE IF EXISTS Departments CASCADE;
@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS Departments (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Name VARCHAR(100) NOT NULL UNIQUE
+);
+
+ALTER TABLE Staff
+    ADD COLUMN Department_id INT REFERENCES Departments(ID) ON DELETE SET NULL,
+    ADD COLUMN Job_title VARCHAR(100) NOT NULL DEFAULT '',
+    ADD COLUMN Manager_id INT REFERENCES Staff(ID) ON DELETE SET NULL,
+    ADD COLUMN Employment_start DATE,
+    ADD COLUMN Employment_end DATE,
+    ADD COLUMN Email VARCHAR(100) NOT NULL DEFAULT '',
+    ADD COLUMN Phone VARCHAR(20) NOT NULL DEFAULT '',
+    ADD CONSTRAINT Staff_manager_not_self CHECK (Manager_id <> ID),
+    ADD CONSTRAINT Staff_employment_period CHECK (Employment_end >= Employment_start);
+
+-- Everyone who has worked on a game is considered hired from their first job.
+UPDATE Staff
+SET Employment_start = first_jobs.first_work_day
+FROM (SELECT staff_id, MIN(first_work_day) AS first_work_day FROM Jobs GROUP BY staff_id) AS first_jobs
+WHERE Staff.ID = first_jobs.staff_id;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS Salary_changes (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Job_id INT REFERENCES Jobs(ID) ON DELETE CASCADE NOT NULL,
+    Salary MONEY NOT NULL,
+    Effective_date DATE NOT NULL,
+    UNIQUE (Job_id, Effective_date)
+);
+
+-- The salary stored in a job so far is the one it started with.
+INSERT INTO Salary_changes (job_id, salary, effective_date)
+SELECT id, salary, first_work_day FROM Jobs;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS Game_roles (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    Position VARCHAR NOT NULL,
+    Headcount INT NOT NULL CHECK (Headcount > 0),
+    Start_date DATE NOT NULL,
+    End_date DATE CHECK (End_date >= Start_date),
+    Salary MONEY NOT NULL
+);
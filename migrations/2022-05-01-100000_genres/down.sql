@@ -0,0 +1,15 @@
+ALTER TABLE Games ADD COLUMN Genre VARCHAR NOT NULL DEFAULT '';
+
+UPDATE Games SET Genre = Tagged.Names
+FROM (
+    SELECT Game_genres.Game_id, string_agg(Genres.Name, ', ' ORDER BY Genres.Name) AS Names
+    FROM Game_genres
+    JOIN Genres ON Genres.ID = Game_genres.Genre_id
+    GROUP BY Game_genres.Game_id
+) AS Tagged
+WHERE Games.ID = Tagged.Game_id;
+
+ALTER TABLE Games ALTER COLUMN Genre DROP DEFAULT;
+
+DROP TABLE IF EXISTS Game_genres CASCADE;
+DROP TABLE IF EXISTS Genres CASCADE;
@@ -0,0 +1,39 @@
+CREATE TABLE IF NOT EXISTS Genres (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Name VARCHAR NOT NULL UNIQUE,
+    Parent_id INT REFERENCES Genres(ID) ON DELETE SET NULL,
+    CHECK (Parent_id <> ID)
+);
+
+CREATE TABLE IF NOT EXISTS Game_genres (
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    Genre_id INT REFERENCES Genres(ID) ON DELETE CASCADE NOT NULL,
+    PRIMARY KEY (Game_id, Genre_id)
+);
+
+-- Free-text genres differ in case, separators and naming ("RPG", "Rpg",
+-- "role-playing"), so they are normalized before becoming genres.
+CREATE TEMPORARY TABLE Normalized_genres AS
+SELECT ID AS Game_id,
+    CASE lower(regexp_replace(trim(Genre), '[\s_-]+', ' ', 'g'))
+        WHEN 'rpg' THEN 'RPG'
+        WHEN 'role playing' THEN 'RPG'
+        WHEN 'role playing game' THEN 'RPG'
+        WHEN 'fps' THEN 'FPS'
+        WHEN 'first person shooter' THEN 'FPS'
+        ELSE initcap(regexp_replace(trim(Genre), '[\s_-]+', ' ', 'g'))
+    END AS Name
+FROM Games
+WHERE trim(Genre) <> '';
+
+INSERT INTO Genres (Name)
+SELECT DISTINCT Name FROM Normalized_genres ORDER BY Name;
+
+INSERT INTO Game_genres (Game_id, Genre_id)
+SELECT Normalized_genres.Game_id, Genres.ID
+FROM Normalized_genres
+JOIN Genres ON Genres.Name = Normalized_genres.Name;
+
+DROP TABLE Normalized_genres;
+
+ALTER TABLE Games DROP COLUMN Genre;
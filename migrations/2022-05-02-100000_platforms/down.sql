@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS Game_releases CASCADE;
+DROP TABLE IF EXISTS Platforms CASCADE;
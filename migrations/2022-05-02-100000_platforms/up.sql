@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS Platforms (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS Game_releases (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    Platform_id INT REFERENCES Platforms(ID) ON DELETE CASCADE NOT NULL,
+    Region VARCHAR NOT NULL,
+    Release_date DATE NOT NULL,
+    Price MONEY NOT NULL CHECK (Price >= 0::money),
+    UNIQUE (Game_id, Platform_id, Region)
+);
+
+INSERT INTO Platforms (Name)
+VALUES ('PC'), ('PlayStation 5'), ('Xbox Series X|S'), ('Nintendo Switch'), ('Mobile');
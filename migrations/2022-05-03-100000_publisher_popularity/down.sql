@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS Publisher_popularity CASCADE;
+ALTER TABLE Publishers DROP CONSTRAINT IF EXISTS Publishers_popularity_check1;
+UPDATE Publishers SET Popularity = 0 WHERE Popularity IS NULL;
+ALTER TABLE Publishers ALTER COLUMN Popularity SET NOT NULL;
@@ -0,0 +1,12 @@
+ALTER TABLE Publishers ALTER COLUMN Popularity DROP NOT NULL;
+ALTER TABLE Publishers ADD CHECK (Popularity >= 0);
+
+CREATE TABLE IF NOT EXISTS Publisher_popularity (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Publisher_id INT REFERENCES Publishers(ID) ON DELETE CASCADE NOT NULL,
+    Score SMALLINT NOT NULL CHECK (Score BETWEEN 0 AND 10),
+    Computed_score SMALLINT NOT NULL CHECK (Computed_score BETWEEN 0 AND 10),
+    Income MONEY NOT NULL,
+    Taken_on DATE NOT NULL,
+    UNIQUE (Publisher_id, Taken_on)
+);
@@ -0,0 +1,15 @@
+ALTER TABLE Contracts DROP COLUMN IF EXISTS Version;
+ALTER TABLE Departments DROP COLUMN IF EXISTS Version;
+ALTER TABLE Donations DROP COLUMN IF EXISTS Version;
+ALTER TABLE Games DROP COLUMN IF EXISTS Version;
+ALTER TABLE Genres DROP COLUMN IF EXISTS Version;
+ALTER TABLE Investments DROP COLUMN IF EXISTS Version;
+ALTER TABLE Investors DROP COLUMN IF EXISTS Version;
+ALTER TABLE Jobs DROP COLUMN IF EXISTS Version;
+ALTER TABLE Loyalty_tiers DROP COLUMN IF EXISTS Version;
+ALTER TABLE Platforms DROP COLUMN IF EXISTS Version;
+ALTER TABLE Publishers DROP COLUMN IF EXISTS Version;
+ALTER TABLE Sales DROP COLUMN IF EXISTS Version;
+ALTER TABLE Staff DROP COLUMN IF EXISTS Version;
+ALTER TABLE Subscriptions DROP COLUMN IF EXISTS Version;
+ALTER TABLE Users DROP COLUMN IF EXISTS Version;
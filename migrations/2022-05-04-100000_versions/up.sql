@@ -0,0 +1,15 @@
+ALTER TABLE Contracts ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Departments ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Donations ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Games ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Genres ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Investments ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Investors ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Jobs ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Loyalty_tiers ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Platforms ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Publishers ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Sales ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Staff ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Subscriptions ADD COLUMN Version INT NOT NULL DEFAULT 1;
+ALTER TABLE Users ADD COLUMN Version INT NOT NULL DEFAULT 1;
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS Scheduled_runs CASCADE;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS Scheduled_runs (
+    ID SERIAL NOT NULL PRIMARY KEY,
+    Task VARCHAR(64) NOT NULL,
+    Started_at TIMESTAMP NOT NULL,
+    Duration_ms BIGINT NOT NULL CHECK (Duration_ms >= 0),
+    Message TEXT NOT NULL,
+    Error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS Scheduled_runs_task ON Scheduled_runs (Task, Started_at);
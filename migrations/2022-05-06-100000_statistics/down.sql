@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS Investment_stats CASCADE;
+DROP TABLE IF EXISTS Donation_user_stats CASCADE;
+DROP TABLE IF EXISTS Donation_daily_stats CASCADE;
@@ -0,0 +1,40 @@
+CREATE TABLE IF NOT EXISTS Donation_daily_stats (
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    Day DATE NOT NULL,
+    Donations_count INT NOT NULL CHECK (Donations_count > 0),
+    Amount MONEY NOT NULL,
+    PRIMARY KEY (Game_id, Day)
+);
+
+CREATE TABLE IF NOT EXISTS Donation_user_stats (
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL,
+    User_id INT REFERENCES Users(ID) ON DELETE CASCADE NOT NULL,
+    Donations_count INT NOT NULL CHECK (Donations_count > 0),
+    Amount MONEY NOT NULL,
+    PRIMARY KEY (Game_id, User_id)
+);
+
+CREATE TABLE IF NOT EXISTS Investment_stats (
+    Game_id INT REFERENCES Games(ID) ON DELETE CASCADE NOT NULL PRIMARY KEY,
+    Investments_count INT NOT NULL CHECK (Investments_count > 0),
+    Invested MONEY NOT NULL
+);
+
+-- Amounts are net of refunds.
+INSERT INTO Donation_daily_stats (Game_id, Day, Donations_count, Amount)
+    SELECT Game_id, Donation_time::date, count(*), sum(Amount - coalesce((
+        SELECT sum(Donation_refunds.Amount) FROM Donation_refunds
+        WHERE Donation_refunds.Donation_id = Donations.ID), 0::money))
+    FROM Donations GROUP BY Game_id, Donation_time::date
+    ON CONFLICT DO NOTHING;
+
+INSERT INTO Donation_user_stats (Game_id, User_id, Donations_count, Amount)
+    SELECT Game_id, User_id, count(*), sum(Amount - coalesce((
+        SELECT sum(Donation_refunds.Amount) FROM Donation_refunds
+        WHERE Donation_refunds.Donation_id = Donations.ID), 0::money))
+    FROM Donations GROUP BY Game_id, User_id
+    ON CONFLICT DO NOTHING;
+
+INSERT INTO Investment_stats (Game_id, Investments_count, Invested)
+    SELECT Game_id, count(*), sum(Invested) FROM Investments GROUP BY Game_id
+    ON CONFLICT DO NOTHING;
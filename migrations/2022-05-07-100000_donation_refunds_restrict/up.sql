@@ -0,0 +1,4 @@
+-- Refunds are financial history: a donation with refunds can not be deleted.
+ALTER TABLE Donation_refunds DROP CONSTRAINT donation_refunds_donation_id_fkey;
+ALTER TABLE Donation_refunds ADD CONSTRAINT donation_refunds_donation_id_fkey
+    FOREIGN KEY (Donation_id) REFERENCES Donations(ID) ON DELETE RESTRICT;
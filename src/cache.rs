@@ -0,0 +1,268 @@
+use anyhow::Result;
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::{Build, Rocket};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// A table the cached value was read from, and the row when the value only
+/// depends on one. Rows of child tables are named by the id of their game,
+/// e.g. `("game_genres", Some(game_id))`.
+pub type Dependency = (&'static str, Option<i32>);
+
+/// The `cache` table of `Rocket.toml`.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+struct CacheConfig {
+    enabled: bool,
+    ttl_seconds: u64,
+    max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: true,
+            ttl_seconds: 60,
+            max_entries: 1000,
+        }
+    }
+}
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    depends_on: Vec<Dependency>,
+    created: Instant,
+    expires: Instant,
+    hits: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<String, Entry>,
+    // Bumped by every invalidation, so a value loaded while a write was
+    // committed is not stored.
+    generation: u64,
+}
+
+#[derive(Default)]
+struct Cache {
+    config: RwLock<CacheConfig>,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidated: AtomicU64,
+    evicted: AtomicU64,
+}
+
+static CACHE: LazyLock<Cache> = LazyLock::new(Cache::default);
+
+fn affects(dependency: &Dependency, changed: &Dependency) -> bool {
+    dependency.0 == changed.0
+        && (dependency.1.is_none() || changed.1.is_none() || dependency.1 == changed.1)
+}
+
+/// Returns the cached value under `key`, or runs `load` and caches what it
+/// returns until it expires or one of the returned dependencies changes.
+/// Errors are never cached.
+pub async fn cached<T, F>(key: String, load: F) -> Result<T>
+where
+    T: Clone + Send + Sync + 'static,
+    F: Future<Output = Result<(T, Vec<Dependency>)>>,
+{
+    let config = *CACHE.config.read().unwrap();
+    if !config.enabled {
+        return load.await.map(|(value, _)| value);
+    }
+
+    let generation = {
+        let mut entries = CACHE.entries.lock().unwrap();
+        let now = Instant::now();
+        match entries.entries.get_mut(&key) {
+            Some(entry) if entry.expires > now => {
+                if let Some(value) = entry.value.downcast_ref::<T>() {
+                    entry.hits += 1;
+                    CACHE.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(value.clone());
+                }
+            }
+            Some(_) => {
+                entries.entries.remove(&key);
+                CACHE.evicted.fetch_add(1, Ordering::Relaxed);
+            }
+            None => {}
+        }
+        entries.generation
+    };
+    CACHE.misses.fetch_add(1, Ordering::Relaxed);
+
+    let (value, depends_on) = load.await?;
+
+    let mut entries = CACHE.entries.lock().unwrap();
+    if entries.generation == generation {
+        let now = Instant::now();
+        if entries.entries.len() >= config.max_entries {
+            let before = entries.entries.len();
+            entries.entries.retain(|_, entry| entry.expires > now);
+            CACHE
+                .evicted
+                .fetch_add((before - entries.entries.len()) as u64, Ordering::Relaxed);
+        }
+        if entries.entries.len() >= config.max_entries {
+            let oldest = entries
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.entries.remove(&oldest);
+                CACHE.evicted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        entries.entries.insert(
+            key,
+            Entry {
+                value: Arc::new(value.clone()),
+                depends_on,
+                created: now,
+                expires: now + Duration::from_secs(config.ttl_seconds),
+                hits: 0,
+            },
+        );
+    }
+    Ok(value)
+}
+
+/// Drops every cached value read from the changed rows. Called by the
+/// controllers after a write is committed.
+pub fn invalidate(changed: &[Dependency]) {
+    let mut entries = CACHE.entries.lock().unwrap();
+    entries.generation += 1;
+
+    let before = entries.entries.len();
+    entries.entries.retain(|_, entry| {
+        !entry
+            .depends_on
+            .iter()
+            .any(|dependency| changed.iter().any(|changed| affects(dependency, changed)))
+    });
+    CACHE
+        .invalidated
+        .fetch_add((before - entries.entries.len()) as u64, Ordering::Relaxed);
+}
+
+pub fn clear() {
+    let mut entries = CACHE.entries.lock().unwrap();
+    entries.generation += 1;
+    let dropped = entries.entries.len() as u64;
+    entries.entries.clear();
+    CACHE.invalidated.fetch_add(dropped, Ordering::Relaxed);
+}
+
+#[derive(Serialize, Debug)]
+pub struct CacheStats {
+    pub enabled: bool,
+    pub ttl_seconds: u64,
+    pub max_entries: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    pub invalidated: u64,
+    pub evicted: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CacheEntryView {
+    pub key: String,
+    pub hits: u64,
+    pub age_seconds: u64,
+    pub expires_in_seconds: u64,
+    pub depends_on: String,
+}
+
+/// Counters since the server started.
+pub fn stats() -> CacheStats {
+    let config = *CACHE.config.read().unwrap();
+    let entries = CACHE.entries.lock().unwrap().entries.len();
+    let hits = CACHE.hits.load(Ordering::Relaxed);
+    let misses = CACHE.misses.load(Ordering::Relaxed);
+
+    CacheStats {
+        enabled: config.enabled,
+        ttl_seconds: config.ttl_seconds,
+        max_entries: config.max_entries,
+        entries,
+        hits,
+        misses,
+        hit_rate: if hits + misses == 0 {
+            0f64
+        } else {
+            hits as f64 * 100f64 / (hits + misses) as f64
+        },
+        invalidated: CACHE.invalidated.load(Ordering::Relaxed),
+        evicted: CACHE.evicted.load(Ordering::Relaxed),
+    }
+}
+
+pub fn entries() -> Vec<CacheEntryView> {
+    let entries = CACHE.entries.lock().unwrap();
+    let now = Instant::now();
+
+    let mut views: Vec<CacheEntryView> = entries
+        .entries
+        .iter()
+        .map(|(key, entry)| CacheEntryView {
+            key: key.clone(),
+            hits: entry.hits,
+            age_seconds: now.duration_since(entry.created).as_secs(),
+            expires_in_seconds: entry.expires.saturating_duration_since(now).as_secs(),
+            depends_on: entry
+                .depends_on
+                .iter()
+                .map(|(table, id)| match id {
+                    Some(id) => format!("{}#{}", table, id),
+                    None => table.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        })
+        .collect();
+    views.sort_by(|first, second| first.key.cmp(&second.key));
+    views
+}
+
+/// Reads the `cache` table of `Rocket.toml`.
+pub fn fairing() -> impl Fairing {
+    CacheFairing
+}
+
+struct CacheFairing;
+
+#[rocket::async_trait]
+impl Fairing for CacheFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Cache",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.figment().extract_inner::<CacheConfig>("cache") {
+            Ok(config) => config,
+            Err(err) if err.missing() => CacheConfig::default(),
+            Err(err) => {
+                error!("Invalid cache config: {}", err);
+                return Err(rocket);
+            }
+        };
+
+        *CACHE.config.write().unwrap() = config;
+        Ok(rocket)
+    }
+}
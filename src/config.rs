@@ -0,0 +1,248 @@
+use crate::db;
+use crate::errors::ServerError;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{NaiveDate, NaiveDateTime};
+use rocket::figment::{Figment, Profile};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Minimal length of the token that protects `/metrics`.
+const MIN_TOKEN_LENGTH: usize = 16;
+
+/// The connection to the database. `url` may also come from `DATABASE_URL`,
+/// set in the environment or in `.env` like for the diesel CLI.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DatabaseConfig {
+    pub url: String,
+    #[serde(default = "DatabaseConfig::default_pool_size")]
+    pub pool_size: u32,
+    /// Seconds to wait for a free connection.
+    #[serde(default = "DatabaseConfig::default_timeout")]
+    pub timeout: u8,
+}
+
+impl DatabaseConfig {
+    fn default_pool_size() -> u32 {
+        10
+    }
+
+    fn default_timeout() -> u8 {
+        5
+    }
+}
+
+/// How dates and sums are shown on the pages and in the reports. Dates are
+/// chrono format strings.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct FormatsConfig {
+    pub date: String,
+    pub date_time: String,
+    pub currency: String,
+}
+
+impl Default for FormatsConfig {
+    fn default() -> Self {
+        FormatsConfig {
+            date: "%d-%m-%Y".to_string(),
+            date_time: "%d-%m-%Y, %H:%M".to_string(),
+            currency: "₴".to_string(),
+        }
+    }
+}
+
+/// Parts of the server that can be turned off.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct FeaturesConfig {
+    /// Runs the scheduled tasks. They can still be started by hand.
+    pub scheduler: bool,
+    /// Serves the printable reports.
+    pub reports: bool,
+    /// Serves `/metrics`.
+    pub metrics: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        FeaturesConfig {
+            scheduler: true,
+            reports: true,
+            metrics: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// When set, `/metrics` needs an `Authorization: Bearer <token>` header.
+    /// Required in the `prod` profile while metrics are on.
+    pub metrics_token: Option<String>,
+}
+
+/// Settings of the application, read from the selected profile of
+/// `Rocket.toml` and `ROCKET_*` variables. Managed by Rocket.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AppConfig {
+    #[serde(skip)]
+    pub profile: String,
+    pub database: DatabaseConfig,
+    #[serde(default)]
+    pub formats: FormatsConfig,
+    #[serde(default)]
+    pub features: FeaturesConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+}
+
+static FORMATS: OnceLock<FormatsConfig> = OnceLock::new();
+
+/// The formats of the loaded configuration, or the default ones before it
+/// is loaded.
+pub fn formats() -> &'static FormatsConfig {
+    FORMATS.get_or_init(FormatsConfig::default)
+}
+
+/// The edit forms read the shown dates back, so a format has to keep
+/// everything it is given: the date, and the time up to minutes.
+fn check_date_format(errors: &mut Vec<String>, key: &str, format: &str, with_time: bool) {
+    // Showing a date in a broken format panics.
+    if format.is_empty() || StrftimeItems::new(format).any(|item| item == Item::Error) {
+        errors.push(format!("{}: неправильний формат дати `{}`", key, format));
+        return;
+    }
+
+    let sample = NaiveDate::from_ymd(2021, 12, 31).and_hms(23, 59, 0);
+    let shown = sample.format(format).to_string();
+    let read_back = if with_time {
+        NaiveDateTime::parse_from_str(&shown, format).ok()
+    } else {
+        NaiveDate::parse_from_str(&shown, format)
+            .ok()
+            .map(|date| date.and_hms(23, 59, 0))
+    };
+    if read_back != Some(sample) {
+        errors.push(format!(
+            "{}: дату у форматі `{}` неможливо прочитати назад",
+            key, format
+        ));
+    }
+}
+
+impl AppConfig {
+    /// All problems of the configuration, so they can be fixed at once.
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if !self.database.url.starts_with("postgres://")
+            && !self.database.url.starts_with("postgresql://")
+        {
+            errors.push(
+                "database.url: потрібна адреса виду postgres://користувач@сервер/база".to_string(),
+            );
+        }
+        if self.database.pool_size == 0 {
+            errors.push("database.pool_size: має бути більше 0".to_string());
+        }
+        if self.database.timeout == 0 {
+            errors.push("database.timeout: має бути більше 0".to_string());
+        }
+
+        check_date_format(&mut errors, "formats.date", &self.formats.date, false);
+        check_date_format(
+            &mut errors,
+            "formats.date_time",
+            &self.formats.date_time,
+            true,
+        );
+        if self.formats.currency.trim().is_empty() {
+            errors.push("formats.currency: не може бути порожнім".to_string());
+        }
+
+        match &self.auth.metrics_token {
+            Some(token) if token.len() < MIN_TOKEN_LENGTH => errors.push(format!(
+                "auth.metrics_token: потрібно щонайменше {} символів",
+                MIN_TOKEN_LENGTH
+            )),
+            None if self.profile == "prod" && self.features.metrics => errors.push(
+                "auth.metrics_token: обов'язковий у профілі prod, поки features.metrics увімкнено"
+                    .to_string(),
+            ),
+            _ => {}
+        }
+
+        errors
+    }
+}
+
+/// The profile used when `ROCKET_PROFILE` is not set.
+fn default_profile() -> &'static str {
+    if cfg!(debug_assertions) {
+        "dev"
+    } else {
+        "prod"
+    }
+}
+
+/// Reads and checks the configuration of the selected profile: `dev`,
+/// `test` or `prod`. Returns the figment to build Rocket from, with the
+/// database pool set from the `database` table.
+pub fn load() -> Result<(Figment, AppConfig), ServerError> {
+    let profile = Profile::from_env_or("ROCKET_PROFILE", default_profile());
+    let mut figment = rocket::Config::figment().select(profile.clone());
+    if let Ok(url) = std::env::var("DATABASE_URL") {
+        figment = figment.merge(("database.url", url));
+    }
+
+    let invalid = |details: String| ServerError::InvalidConfig(profile.to_string(), details);
+    if figment.find_value("database.url").is_err() {
+        return Err(invalid(
+            "database.url: не задано, вкажіть його у Rocket.toml або у DATABASE_URL".to_string(),
+        ));
+    }
+    let mut config: AppConfig = figment.extract().map_err(|err| invalid(err.to_string()))?;
+    config.profile = profile.to_string();
+
+    let errors = config.validate();
+    if !errors.is_empty() {
+        return Err(invalid(errors.join("; ")));
+    }
+
+    FORMATS.get_or_init(|| config.formats.clone());
+    let pool = format!("databases.{}", db::DATABASE);
+    let figment = figment
+        .merge((format!("{}.url", pool), &config.database.url))
+        .merge((format!("{}.pool_size", pool), config.database.pool_size))
+        .merge((format!("{}.timeout", pool), config.database.timeout));
+    Ok((figment, config))
+}
+
+/// Allows a request to `/metrics`: forwarded to a 404 when metrics are off,
+/// refused when the configured token is not given.
+pub struct MetricsAccess;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsAccess {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let config = match request.rocket().state::<AppConfig>() {
+            Some(config) if config.features.metrics => config,
+            _ => return Outcome::Forward(()),
+        };
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "));
+
+        match &config.auth.metrics_token {
+            Some(expected) if token != Some(expected.as_str()) => {
+                Outcome::Failure((Status::Unauthorized, ()))
+            }
+            _ => Outcome::Success(MetricsAccess),
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use crate::config;
+use crate::controllers::GamesControl;
+use crate::controllers::PublishersControl;
+use crate::controllers::Resource;
+use crate::controllers::NET_DONATION_AMOUNT;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddContract;
+use crate::schema::contracts;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Local, NaiveDate};
+use diesel::dsl::sql;
+use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{Money, Nullable};
+use serde::Serialize;
+
+sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "contracts"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewContract {
+    pub game_id: i32,
+    pub publisher_id: i32,
+    pub advance_payment: PgMoney,
+    pub revenue_share: i16,
+    pub territory: String,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+}
+
+impl NewContract {
+    pub fn from(contract: AddContract) -> Result<Self, ServerError> {
+        let start_date = NaiveDate::parse_from_str(&contract.start_date, "%Y-%m-%d");
+        if start_date.is_err() {
+            return Err(ServerError::InvalidDate);
+        }
+        let start_date = start_date.unwrap();
+
+        let end_date = NaiveDate::parse_from_str(&contract.end_date, "%Y-%m-%d");
+        if end_date.is_err() && !contract.end_date.is_empty() {
+            return Err(ServerError::InvalidDate);
+        }
+        let end_date = end_date.ok();
+        if matches!(end_date, Some(end_date) if end_date < start_date) {
+            return Err(ServerError::InvalidDate);
+        }
+
+        if !(0..=100).contains(&contract.revenue_share) {
+            return Err(ServerError::InvalidValue(vec!["revenue share".to_string()]));
+        }
+
+        let territory = contract.territory.trim().to_string();
+        if territory.is_empty() {
+            return Err(ServerError::NullValues(vec!["territory".to_string()]));
+        }
+
+        Ok(NewContract {
+            game_id: contract.game_id,
+            publisher_id: contract.publisher_id,
+            advance_payment: PgMoney((contract.advance_payment * 100f64) as i64),
+            revenue_share: contract.revenue_share,
+            territory,
+            start_date,
+            end_date,
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ContractsControl {
+    pub id: i32,
+    pub version: i32,
+    pub game: String,
+    pub game_id: i32,
+    pub publisher: String,
+    pub publisher_id: i32,
+    pub advance_payment: f64,
+    pub revenue_share: i16,
+    pub territory: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub projected_payout: f64,
+}
+
+impl ContractsControl {
+    pub async fn make_contracts_control(conn: &DBConnection, contracts_struct: Contract) -> Self {
+        let game = GamesControl::get_game_by_id(conn, contracts_struct.game_id)
+            .await
+            .unwrap()
+            .name;
+        let publisher = PublishersControl::get_publisher_by_id(conn, contracts_struct.publisher_id)
+            .await
+            .unwrap()
+            .name;
+        let projected_payout = ContractsControl::get_projected_payout(
+            conn,
+            contracts_struct.game_id,
+            contracts_struct.revenue_share,
+            contracts_struct.start_date,
+            contracts_struct.end_date,
+        )
+        .await;
+
+        let start_date = contracts_struct
+            .start_date
+            .format(&config::formats().date)
+            .to_string();
+        let end_date = match contracts_struct.end_date {
+            Some(end_date) => end_date.format(&config::formats().date).to_string(),
+            None => "".to_string(),
+        };
+        ContractsControl {
+            id: contracts_struct.id,
+            version: contracts_struct.version,
+            game,
+            game_id: contracts_struct.game_id,
+            publisher,
+            publisher_id: contracts_struct.publisher_id,
+            advance_payment: contracts_struct.advance_payment.0 as f64 / 100f64,
+            revenue_share: contracts_struct.revenue_share,
+            territory: contracts_struct.territory,
+            start_date,
+            end_date,
+            projected_payout,
+        }
+    }
+
+    pub fn change_date_format(&mut self, from: &str, to: &str) -> Result<()> {
+        let start_date = NaiveDate::parse_from_str(&self.start_date, from)?;
+        self.start_date = start_date.format(to).to_string();
+
+        if !self.end_date.is_empty() {
+            let end_date = NaiveDate::parse_from_str(&self.end_date, from)?;
+            self.end_date = end_date.format(to).to_string();
+        }
+        Ok(())
+    }
+
+    /// Publisher's share of the game income (donations and sales, both net
+    /// of refunds) received during the contract term.
+    pub async fn get_projected_payout(
+        conn: &DBConnection,
+        game_id_for_lookup: i32,
+        revenue_share: i16,
+        start_date: NaiveDate,
+        end_date: Option<NaiveDate>,
+    ) -> f64 {
+        use crate::schema::{donations, sales};
+
+        let today = Local::today().naive_local();
+        let end_date = end_date.unwrap_or(today);
+        let from = start_date.and_hms(0, 0, 0);
+        let to = end_date.and_hms(23, 59, 59);
+
+        let (donated, sold, refunded) = conn
+            .run(
+                move |sql_conn| -> (Option<PgMoney>, Option<PgMoney>, Option<PgMoney>) {
+                    let donated = donations::table
+                        .select(sql::<Nullable<Money>>(&format!(
+                            "sum{}",
+                            NET_DONATION_AMOUNT
+                        )))
+                        .filter(donations::game_id.eq(game_id_for_lookup))
+                        .filter(donations::donation_time.between(from, to))
+                        .first(sql_conn)
+                        .unwrap();
+                    let (sold, refunded) = sales::table
+                        .select((
+                            sql::<Nullable<Money>>("sum(gross)"),
+                            sql::<Nullable<Money>>("sum(refunds)"),
+                        ))
+                        .filter(sales::game_id.eq(game_id_for_lookup))
+                        .filter(sales::sale_date.between(start_date, end_date))
+                        .first(sql_conn)
+                        .unwrap();
+                    (donated, sold, refunded)
+                },
+            )
+            .await;
+
+        let income = [donated, sold]
+            .iter()
+            .flatten()
+            .map(|money| money.0)
+            .sum::<i64>()
+            - refunded.map_or(0, |money| money.0);
+        income as f64 / 100f64 * revenue_share as f64 / 100f64
+    }
+
+    /// Territories are compared ignoring case, so "EU" and "eu" are the same
+    /// one. The game stays locked until the write of the contract is
+    /// committed, so two contracts for it are checked one after the other.
+    /// The lock is `FOR NO KEY UPDATE` for the same reason as in
+    /// `StatisticsControl::refresh_game`.
+    fn check_overlap(
+        sql_conn: &PgConnection,
+        contract: &NewContract,
+        id_to_skip: Option<i32>,
+    ) -> Result<()> {
+        use crate::schema::contracts::dsl::*;
+        use crate::schema::games;
+
+        games::table
+            .filter(games::id.eq(contract.game_id))
+            .select(games::id)
+            .for_no_key_update()
+            .load::<i32>(sql_conn)?;
+
+        let mut query = contracts
+            .filter(game_id.eq(contract.game_id))
+            .filter(lower(territory).eq(contract.territory.to_lowercase()))
+            .into_boxed();
+        if let Some(id_to_skip) = id_to_skip {
+            query = query.filter(id.ne(id_to_skip));
+        }
+        let existing: Vec<Contract> = query.load(sql_conn)?;
+
+        let overlaps = existing.iter().any(|other| {
+            let starts_before_other_ends = other
+                .end_date
+                .is_none_or(|other_end| contract.start_date <= other_end);
+            let ends_after_other_starts = contract
+                .end_date
+                .is_none_or(|contract_end| other.start_date <= contract_end);
+            starts_before_other_ends && ends_after_other_starts
+        });
+
+        if overlaps {
+            return Err(ServerError::ContractOverlap(contract.territory.clone()).into());
+        }
+        Ok(())
+    }
+
+    pub async fn get_contracts(conn: &DBConnection) -> Result<Vec<ContractsControl>> {
+        use crate::schema::contracts::dsl::*;
+
+        let results = conn
+            .run(move |sql_conn| -> Result<Vec<Contract>> {
+                Ok(contracts.order(id.asc()).load::<Contract>(sql_conn)?)
+            })
+            .await?;
+
+        let mut contracts_result: Vec<ContractsControl> = vec![];
+        for contract in results {
+            contracts_result.push(ContractsControl::make_contracts_control(conn, contract).await);
+        }
+
+        Ok(contracts_result)
+    }
+
+    pub async fn get_contract_by_id(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+    ) -> Result<ContractsControl> {
+        use crate::schema::contracts::dsl::*;
+
+        let contract = conn
+            .run(move |sql_conn| -> Result<Contract> {
+                let result: Contract = contracts
+                    .filter(id.eq(id_for_lookup))
+                    .first(sql_conn)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                Ok(result)
+            })
+            .await?;
+
+        Ok(ContractsControl::make_contracts_control(conn, contract).await)
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for ContractsControl {
+    type Form = AddContract;
+    type New = NewContract;
+    type Table = contracts::table;
+    type Version = contracts::version;
+
+    const NAME: &'static str = "contracts";
+    const TABLE: &'static str = "Контракти";
+
+    fn parse(form: AddContract) -> Result<Self::New, ServerError> {
+        NewContract::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_contract_by_id(conn, id).await
+    }
+
+    fn check(sql_conn: &PgConnection, new: &NewContract, id: Option<i32>) -> Result<()> {
+        Self::check_overlap(sql_conn, new, id)
+    }
+
+    /// Game and publisher choices.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let games = GamesControl::get_games(conn).await.unwrap();
+        let games_id = games.iter().map(|game| game.id.to_string()).collect();
+
+        let games_name = games.into_iter().map(|game| game.name).collect();
+
+        let publishers = PublishersControl::get_publishers(conn).await.unwrap();
+        let publishers_id = publishers
+            .iter()
+            .map(|publisher| publisher.id.to_string())
+            .collect();
+
+        let publishers_name = publishers
+            .into_iter()
+            .map(|publisher| publisher.name)
+            .collect();
+
+        vec![games_id, games_name, publishers_id, publishers_name]
+    }
+
+    fn for_edit(&mut self) -> Result<()> {
+        self.change_date_format(&config::formats().date, "%Y-%m-%d")
+    }
+}
@@ -0,0 +1,118 @@
+use crate::controllers::Resource;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddDepartment;
+use crate::schema::departments;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::Local;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "departments"]
+pub struct NewDepartment {
+    pub name: String,
+}
+
+impl NewDepartment {
+    pub fn from(department: AddDepartment) -> Result<Self, ServerError> {
+        let name = department.name.trim().to_string();
+        if name.is_empty() {
+            return Err(ServerError::NullValues(vec!["name".to_string()]));
+        }
+
+        Ok(NewDepartment { name })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DepartmentsControl {
+    pub id: i32,
+    pub version: i32,
+    pub name: String,
+    pub headcount: i64,
+}
+
+impl DepartmentsControl {
+    pub async fn get_departments(conn: &DBConnection) -> Result<Vec<DepartmentsControl>> {
+        use crate::schema::staff;
+
+        let today = Local::today().naive_local();
+        let (all_departments, members) = conn
+            .run(
+                move |sql_conn| -> Result<(Vec<Department>, Vec<Option<i32>>)> {
+                    let all_departments = departments::table
+                        .order(departments::name.asc())
+                        .load(sql_conn)?;
+                    let members = staff::table
+                        .select(staff::department_id)
+                        .filter(staff::employment_start.is_not_null())
+                        .filter(
+                            staff::employment_end
+                                .is_null()
+                                .or(staff::employment_end.ge(today)),
+                        )
+                        .load(sql_conn)?;
+                    Ok((all_departments, members))
+                },
+            )
+            .await?;
+
+        let mut headcount: HashMap<i32, i64> = HashMap::new();
+        for department_id in members.into_iter().flatten() {
+            *headcount.entry(department_id).or_default() += 1;
+        }
+
+        Ok(all_departments
+            .into_iter()
+            .map(|department| DepartmentsControl {
+                id: department.id,
+                version: department.version,
+                headcount: headcount.get(&department.id).copied().unwrap_or_default(),
+                name: department.name,
+            })
+            .collect())
+    }
+
+    pub async fn get_department_by_id(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+    ) -> Result<DepartmentsControl> {
+        use crate::schema::departments::dsl::*;
+
+        conn.run(move |sql_conn| -> Result<DepartmentsControl> {
+            let result: Department = departments
+                .filter(id.eq(id_for_lookup))
+                .first(sql_conn)
+                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+            Ok(DepartmentsControl {
+                id: result.id,
+                version: result.version,
+                name: result.name,
+                headcount: 0,
+            })
+        })
+        .await
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for DepartmentsControl {
+    type Form = AddDepartment;
+    type New = NewDepartment;
+    type Table = departments::table;
+    type Version = departments::version;
+
+    const NAME: &'static str = "departments";
+    const TABLE: &'static str = "Відділи";
+
+    fn parse(form: AddDepartment) -> Result<Self::New, ServerError> {
+        NewDepartment::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_department_by_id(conn, id).await
+    }
+}
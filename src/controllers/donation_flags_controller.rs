@@ -0,0 +1,398 @@
+use crate::cache;
+use crate::config;
+use crate::controllers::{
+    DonationRefundsControl, GameStatus, GamesControl, NewRefund, UsersControl,
+};
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::schema::donation_flags;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Duration, Local, NaiveDateTime};
+use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::Serialize;
+
+/// A donation is large when it exceeds the user's average by this factor.
+const LARGE_DONATION_FACTOR: i64 = 5;
+/// Fewer previous donations than this are not enough to judge the average.
+const MIN_HISTORY: usize = 3;
+/// This many donations from one user within the window form a burst.
+const BURST_SIZE: i64 = 5;
+const BURST_WINDOW_MINUTES: i64 = 10;
+
+const APPROVED: &str = "approved";
+const REVERSED: &str = "reversed";
+const REVERSAL_REASON: &str = "Скасовано після перевірки";
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "donation_flags"]
+pub struct NewDonationFlag {
+    pub donation_id: Option<i32>,
+    pub user_id: i32,
+    pub game_id: i32,
+    pub amount: PgMoney,
+    pub donation_time: NaiveDateTime,
+    pub reasons: String,
+    pub flagged_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DonationFlagsControl {
+    pub id: i32,
+    pub donation_id: Option<i32>,
+    pub user: String,
+    pub user_id: i32,
+    pub game: String,
+    pub game_id: i32,
+    pub amount: f64,
+    pub donation_time: String,
+    pub reasons: String,
+    pub flagged_at: String,
+    pub resolution: String,
+    pub reviewed_at: String,
+}
+
+impl DonationFlagsControl {
+    pub async fn make_donation_flags_control(conn: &DBConnection, flag: DonationFlag) -> Self {
+        let game = GamesControl::get_game_by_id(conn, flag.game_id)
+            .await
+            .unwrap()
+            .name;
+        let user = UsersControl::get_user_by_id(conn, flag.user_id)
+            .await
+            .unwrap()
+            .nickname;
+
+        DonationFlagsControl {
+            id: flag.id,
+            donation_id: flag.donation_id,
+            user,
+            user_id: flag.user_id,
+            game,
+            game_id: flag.game_id,
+            amount: flag.amount.0 as f64 / 100f64,
+            donation_time: flag
+                .donation_time
+                .format(&config::formats().date_time)
+                .to_string(),
+            reasons: flag.reasons,
+            flagged_at: flag
+                .flagged_at
+                .format(&config::formats().date_time)
+                .to_string(),
+            resolution: flag.resolution.unwrap_or_default(),
+            reviewed_at: flag
+                .reviewed_at
+                .map(|reviewed_at| reviewed_at.format(&config::formats().date_time).to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Puts the donation into the review queue when it looks suspicious:
+    /// much larger than the user's usual donation, part of a burst of
+    /// donations or made to a game that is not released yet. Also called
+    /// after an edit: a donation still waiting for review keeps one flag,
+    /// updated to its new values, or leaves the queue when it no longer
+    /// looks suspicious.
+    pub fn detect(sql_conn: &PgConnection, donation: &Donation) -> Result<()> {
+        use crate::schema::{donations, games};
+
+        let mut reasons = Vec::new();
+
+        let history: Vec<PgMoney> = donations::table
+            .select(donations::amount)
+            .filter(donations::user_id.eq(donation.user_id))
+            .filter(donations::id.ne(donation.id))
+            .load(sql_conn)?;
+        if history.len() >= MIN_HISTORY {
+            let average = history.iter().map(|amount| amount.0).sum::<i64>() / history.len() as i64;
+            if donation.amount.0 > average * LARGE_DONATION_FACTOR {
+                reasons.push(format!(
+                    "сума у {}+ разів більша за середню ({} ₴)",
+                    LARGE_DONATION_FACTOR,
+                    average as f64 / 100f64
+                ));
+            }
+        }
+
+        let window = Duration::minutes(BURST_WINDOW_MINUTES);
+        let burst: i64 = donations::table
+            .filter(donations::user_id.eq(donation.user_id))
+            .filter(donations::donation_time.between(
+                donation.donation_time - window,
+                donation.donation_time + window,
+            ))
+            .count()
+            .get_result(sql_conn)?;
+        if burst >= BURST_SIZE {
+            reasons.push(format!(
+                "{} донатів протягом {} хвилин",
+                burst, BURST_WINDOW_MINUTES
+            ));
+        }
+
+        let status: String = games::table
+            .select(games::status)
+            .filter(games::id.eq(donation.game_id))
+            .first(sql_conn)?;
+        if !GameStatus::parse(&status)?.is_released() {
+            reasons.push(format!("гра ще не вийшла (статус {})", status));
+        }
+
+        let pending = donation_flags::table
+            .filter(donation_flags::donation_id.eq(donation.id))
+            .filter(donation_flags::resolution.is_null());
+        if reasons.is_empty() {
+            diesel::delete(pending).execute(sql_conn)?;
+            return Ok(());
+        }
+        let flag = NewDonationFlag {
+            donation_id: Some(donation.id),
+            user_id: donation.user_id,
+            game_id: donation.game_id,
+            amount: donation.amount,
+            donation_time: donation.donation_time,
+            reasons: reasons.join("; "),
+            flagged_at: Local::now().naive_local(),
+        };
+
+        let pending: Option<i32> = pending
+            .select(donation_flags::id)
+            .first(sql_conn)
+            .optional()?;
+        match pending {
+            Some(pending) => diesel::update(donation_flags::table.find(pending))
+                .set(&flag)
+                .execute(sql_conn)?,
+            None => diesel::insert_into(donation_flags::table)
+                .values(&flag)
+                .execute(sql_conn)?,
+        };
+        Ok(())
+    }
+
+    /// Flagged donations waiting for review and the already reviewed ones.
+    pub async fn get_flags(
+        conn: &DBConnection,
+    ) -> Result<(Vec<DonationFlagsControl>, Vec<DonationFlagsControl>)> {
+        use crate::schema::donation_flags::dsl::*;
+
+        let results = conn
+            .run(move |sql_conn| -> Result<Vec<DonationFlag>> {
+                Ok(donation_flags
+                    .order(id.asc())
+                    .load::<DonationFlag>(sql_conn)?)
+            })
+            .await?;
+
+        let mut pending = vec![];
+        let mut reviewed = vec![];
+        for flag in results {
+            let is_pending = flag.resolution.is_none();
+            let flag = DonationFlagsControl::make_donation_flags_control(conn, flag).await;
+            if is_pending {
+                pending.push(flag);
+            } else {
+                reviewed.push(flag);
+            }
+        }
+
+        Ok((pending, reviewed))
+    }
+
+    fn review(sql_conn: &PgConnection, id_for_review: i32, verdict: &str) -> Result<DonationFlag> {
+        use crate::schema::donation_flags::dsl::*;
+
+        let flag: DonationFlag = donation_flags
+            .filter(id.eq(id_for_review))
+            .for_update()
+            .first(sql_conn)
+            .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+        if flag.resolution.is_some() {
+            return Err(ServerError::FlagAlreadyReviewed.into());
+        }
+
+        diesel::update(donation_flags.filter(id.eq(id_for_review)))
+            .set((
+                resolution.eq(verdict),
+                reviewed_at.eq(Local::now().naive_local()),
+            ))
+            .execute(sql_conn)?;
+        Ok(flag)
+    }
+
+    pub async fn approve(conn: &DBConnection, id_for_review: i32) -> Result<()> {
+        conn.run(move |sql_connection| -> Result<()> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                DonationFlagsControl::review(sql_connection, id_for_review, APPROVED)?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Refunds whatever is left of the flagged donation, so the reversal
+    /// stays in the donation's history. The donation is read as it is now,
+    /// not as it was when flagged.
+    fn reverse_flag(sql_conn: &PgConnection, id_for_review: i32) -> Result<()> {
+        use crate::schema::donations;
+
+        sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            let flag = DonationFlagsControl::review(sql_conn, id_for_review, REVERSED)?;
+            let donation_id = match flag.donation_id {
+                Some(donation_id) => donation_id,
+                None => return Ok(()),
+            };
+
+            let amount: Option<PgMoney> = donations::table
+                .select(donations::amount)
+                .filter(donations::id.eq(donation_id))
+                .for_update()
+                .first(sql_conn)
+                .optional()?;
+            let amount = match amount {
+                Some(amount) => amount,
+                None => return Ok(()),
+            };
+            let remaining = amount.0 - DonationRefundsControl::refunded(sql_conn, donation_id)?.0;
+            if remaining <= 0 {
+                return Ok(());
+            }
+            DonationRefundsControl::refund(
+                sql_conn,
+                &NewRefund {
+                    donation_id,
+                    amount: PgMoney(remaining),
+                    reason: REVERSAL_REASON.to_string(),
+                    refunded_at: Local::now().naive_local(),
+                },
+            )
+        })
+    }
+
+    pub async fn reverse(conn: &DBConnection, id_for_review: i32) -> Result<()> {
+        conn.run(move |sql_connection| {
+            DonationFlagsControl::reverse_flag(sql_connection, id_for_review)
+        })
+        .await?;
+        cache::invalidate(&[("donation_user_stats", None)]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controllers::NewUser;
+    use crate::schema::{donation_refunds, donations, games, users};
+    use chrono::NaiveDate;
+    use diesel::pg::data_types::PgDate;
+
+    /// The database of the profile the tests run with; every test rolls its
+    /// changes back.
+    fn connection() -> PgConnection {
+        let (_, config) = config::load().unwrap();
+        PgConnection::establish(&config.database.url).unwrap()
+    }
+
+    /// A new user with three donations of 1.00 to a released game and a
+    /// fourth one of `amount`.
+    fn donate(sql_conn: &PgConnection, amount: i64) -> Result<Donation> {
+        let game_id: i32 = games::table
+            .select(games::id)
+            .filter(games::status.eq("released"))
+            .first(sql_conn)?;
+        let user_id: i32 = diesel::insert_into(users::table)
+            .values(&NewUser {
+                nickname: "flags test".to_string(),
+                registration_date: PgDate(0),
+            })
+            .returning(users::id)
+            .get_result(sql_conn)?;
+
+        let mut donation = None;
+        for (day, amount) in [(1, 100), (2, 100), (3, 100), (4, amount)] {
+            donation = Some(
+                diesel::insert_into(donations::table)
+                    .values((
+                        donations::user_id.eq(user_id),
+                        donations::game_id.eq(game_id),
+                        donations::amount.eq(PgMoney(amount)),
+                        donations::donation_time
+                            .eq(NaiveDate::from_ymd(2022, 1, day).and_hms(12, 0, 0)),
+                    ))
+                    .get_result::<Donation>(sql_conn)?,
+            );
+        }
+        Ok(donation.unwrap())
+    }
+
+    fn edit(sql_conn: &PgConnection, donation: &Donation, amount: i64) -> Result<Donation> {
+        let edited = diesel::update(donations::table.find(donation.id))
+            .set(donations::amount.eq(PgMoney(amount)))
+            .get_result::<Donation>(sql_conn)?;
+        DonationFlagsControl::detect(sql_conn, &edited)?;
+        Ok(edited)
+    }
+
+    fn pending_flag(sql_conn: &PgConnection, donation: &Donation) -> Result<Option<DonationFlag>> {
+        Ok(donation_flags::table
+            .filter(donation_flags::donation_id.eq(donation.id))
+            .filter(donation_flags::resolution.is_null())
+            .first(sql_conn)
+            .optional()?)
+    }
+
+    fn refunded(sql_conn: &PgConnection, donation: &Donation) -> Result<Vec<PgMoney>> {
+        Ok(donation_refunds::table
+            .select(donation_refunds::amount)
+            .filter(donation_refunds::donation_id.eq(donation.id))
+            .load(sql_conn)?)
+    }
+
+    #[test]
+    fn edit_without_reasons_clears_the_flag_before_reverse() {
+        let sql_conn = connection();
+        sql_conn.test_transaction::<_, anyhow::Error, _>(|| {
+            let donation = donate(&sql_conn, 100_000)?;
+            DonationFlagsControl::detect(&sql_conn, &donation)?;
+            let flag = pending_flag(&sql_conn, &donation)?.unwrap();
+
+            let donation = edit(&sql_conn, &donation, 200)?;
+            assert!(pending_flag(&sql_conn, &donation)?.is_none());
+            // The queue showed the flag before the edit: reversing it now
+            // finds nothing to review and refunds nothing.
+            assert!(DonationFlagsControl::reverse_flag(&sql_conn, flag.id).is_err());
+            assert!(refunded(&sql_conn, &donation)?.is_empty());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn reverse_refunds_the_current_amount() {
+        let sql_conn = connection();
+        sql_conn.test_transaction::<_, anyhow::Error, _>(|| {
+            let donation = donate(&sql_conn, 100_000)?;
+            DonationFlagsControl::detect(&sql_conn, &donation)?;
+            let flag = pending_flag(&sql_conn, &donation)?.unwrap();
+
+            // Edited down behind the review queue's back: the flag still
+            // holds the old amount.
+            diesel::update(donations::table.find(donation.id))
+                .set(donations::amount.eq(PgMoney(50_000)))
+                .execute(&sql_conn)?;
+            DonationFlagsControl::reverse_flag(&sql_conn, flag.id)?;
+
+            assert_eq!(
+                refunded(&sql_conn, &donation)?
+                    .iter()
+                    .map(|amount| amount.0)
+                    .collect::<Vec<_>>(),
+                vec![50_000]
+            );
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,142 @@
+use crate::cache;
+use crate::config;
+use crate::controllers::LoyaltyTiersControl;
+use crate::controllers::StatisticsControl;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddRefund;
+use crate::schema::donation_refunds;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Local, NaiveDateTime};
+use diesel::dsl::sql;
+use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{Money, Nullable};
+use serde::Serialize;
+
+/// Donation amount minus everything refunded from it, for use inside
+/// aggregates over the `donations` table.
+pub const NET_DONATION_AMOUNT: &str = "(donations.amount - coalesce((\
+    SELECT sum(donation_refunds.amount) FROM donation_refunds \
+    WHERE donation_refunds.donation_id = donations.id), 0::money))";
+
+#[derive(Insertable)]
+#[table_name = "donation_refunds"]
+pub struct NewRefund {
+    pub donation_id: i32,
+    pub amount: PgMoney,
+    pub reason: String,
+    pub refunded_at: NaiveDateTime,
+}
+
+impl NewRefund {
+    pub fn from(donation_id: i32, refund: AddRefund) -> Result<Self, ServerError> {
+        if refund.amount <= 0f64 {
+            return Err(ServerError::InvalidValue(vec!["amount".to_string()]));
+        }
+        let reason = refund.reason.trim().to_string();
+        if reason.is_empty() {
+            return Err(ServerError::NullValues(vec!["reason".to_string()]));
+        }
+
+        Ok(NewRefund {
+            donation_id,
+            amount: PgMoney((refund.amount * 100f64) as i64),
+            reason,
+            refunded_at: Local::now().naive_local(),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DonationRefundsControl {
+    pub id: i32,
+    pub donation_id: i32,
+    pub amount: f64,
+    pub reason: String,
+    pub refunded_at: String,
+}
+
+impl std::convert::From<DonationRefund> for DonationRefundsControl {
+    fn from(refund: DonationRefund) -> Self {
+        DonationRefundsControl {
+            id: refund.id,
+            donation_id: refund.donation_id,
+            amount: refund.amount.0 as f64 / 100f64,
+            reason: refund.reason,
+            refunded_at: refund
+                .refunded_at
+                .format(&config::formats().date_time)
+                .to_string(),
+        }
+    }
+}
+
+impl DonationRefundsControl {
+    pub fn refunded(sql_conn: &PgConnection, donation_id_for_lookup: i32) -> Result<PgMoney> {
+        use crate::schema::donation_refunds::dsl::*;
+
+        let refunded: Option<PgMoney> = donation_refunds
+            .select(sql::<Nullable<Money>>("sum(amount)"))
+            .filter(donation_id.eq(donation_id_for_lookup))
+            .first(sql_conn)?;
+        Ok(refunded.unwrap_or(PgMoney(0)))
+    }
+
+    pub async fn get_refunds(
+        conn: &DBConnection,
+        donation_id_for_lookup: i32,
+    ) -> Result<Vec<DonationRefundsControl>> {
+        use crate::schema::donation_refunds::dsl::*;
+
+        let results = conn
+            .run(move |sql_conn| -> Result<Vec<DonationRefund>> {
+                Ok(donation_refunds
+                    .filter(donation_id.eq(donation_id_for_lookup))
+                    .order(id.asc())
+                    .load::<DonationRefund>(sql_conn)?)
+            })
+            .await?;
+
+        Ok(results
+            .into_iter()
+            .map(DonationRefundsControl::from)
+            .collect())
+    }
+
+    /// Records a refund. A donation can never be refunded for more than
+    /// its original amount in total.
+    pub fn refund(sql_conn: &PgConnection, refund: &NewRefund) -> Result<()> {
+        use crate::schema::donations;
+
+        let donation: Donation = donations::table
+            .filter(donations::id.eq(refund.donation_id))
+            .for_update()
+            .first(sql_conn)
+            .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+        let remaining =
+            donation.amount.0 - DonationRefundsControl::refunded(sql_conn, donation.id)?.0;
+        if refund.amount.0 > remaining {
+            return Err(ServerError::RefundExceedsDonation(remaining as f64 / 100f64).into());
+        }
+
+        diesel::insert_into(donation_refunds::table)
+            .values(refund)
+            .execute(sql_conn)?;
+        StatisticsControl::refresh_game(sql_conn, donation.game_id)?;
+        LoyaltyTiersControl::refresh_user_tier(sql_conn, donation.user_id)
+    }
+
+    pub async fn add_refund(conn: &DBConnection, refund: NewRefund) -> Result<()> {
+        conn.run(move |sql_connection| -> Result<()> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                DonationRefundsControl::refund(sql_connection, &refund)
+            })
+        })
+        .await?;
+        cache::invalidate(&[("donation_user_stats", None)]);
+        Ok(())
+    }
+}
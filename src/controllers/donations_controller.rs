@@ -1,4 +1,12 @@
+use crate::cache;
+use crate::config;
+use crate::controllers::write_error;
+use crate::controllers::DonationFlagsControl;
+use crate::controllers::DonationRefundsControl;
 use crate::controllers::GamesControl;
+use crate::controllers::LoyaltyTiersControl;
+use crate::controllers::Resource;
+use crate::controllers::StatisticsControl;
 use crate::controllers::UsersControl;
 use crate::errors::ServerError;
 use crate::models::*;
@@ -6,13 +14,13 @@ use crate::requests_handler::AddDonation;
 use crate::schema::donations;
 use crate::DBConnection;
 use anyhow::Result;
-use chrono::NaiveDateTime;
-use diesel::pg::data_types::PgMoney;
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime};
+use diesel::pg::data_types::{PgDate, PgMoney};
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::result::Error as DieselError;
 use serde::Serialize;
 
-#[derive(Insertable)]
+#[derive(Insertable, AsChangeset)]
 #[table_name = "donations"]
 pub struct NewDonation {
     pub user_id: i32,
@@ -28,29 +36,43 @@ impl NewDonation {
         if donation_time.is_err() {
             return Err(ServerError::InvalidDate);
         }
+        let donation_time = donation_time.unwrap();
+        if donation_time > Local::now().naive_local() {
+            return Err(ServerError::DonationInFuture);
+        }
+        if donation.amount <= 0f64 {
+            return Err(ServerError::InvalidValue(vec!["amount".to_string()]));
+        }
 
         Ok(NewDonation {
             game_id: donation.game_id,
             user_id: donation.user_id,
             amount: PgMoney((donation.amount * 100f64) as i64),
-            donation_time: donation_time.unwrap(),
+            donation_time,
         })
     }
 }
 #[derive(Serialize, Debug)]
 pub struct DonationsControl {
     pub id: i32,
+    pub version: i32,
     pub user: String,
     pub user_id: i32,
     pub game: String,
     pub game_id: i32,
     pub amount: f64,
+    pub refunded: f64,
     pub donation_time: String,
 }
 
 impl DonationsControl {
     pub async fn make_donations_control(conn: &DBConnection, donations_struct: Donation) -> Self {
         let donation_time = donations_struct.donation_time;
+        let donation_id = donations_struct.id;
+        let refunded = conn
+            .run(move |sql_conn| DonationRefundsControl::refunded(sql_conn, donation_id))
+            .await
+            .unwrap();
 
         let game = GamesControl::get_game_by_id(conn, donations_struct.game_id)
             .await
@@ -61,15 +83,19 @@ impl DonationsControl {
             .unwrap()
             .nickname;
 
-        let donation_time = donation_time.format("%d-%m-%Y, %H:%M").to_string();
+        let donation_time = donation_time
+            .format(&config::formats().date_time)
+            .to_string();
         DonationsControl {
             id: donations_struct.id,
+            version: donations_struct.version,
             game: game,
             game_id: donations_struct.game_id,
             user: user,
             user_id: donations_struct.user_id,
             donation_time: donation_time,
             amount: donations_struct.amount.0 as f64 / 100f64,
+            refunded: refunded.0 as f64 / 100f64,
         }
     }
 
@@ -79,6 +105,33 @@ impl DonationsControl {
         Ok(())
     }
 
+    fn stored_date(date: PgDate) -> NaiveDate {
+        let date = NaiveDate::from_num_days_from_ce(date.0);
+        NaiveDate::from_ymd(date.year() + 1999, date.month(), date.day())
+    }
+
+    /// Rejects donations made before the user registered or the game was released.
+    fn check_dates(sql_conn: &PgConnection, donation: &NewDonation) -> Result<()> {
+        use crate::schema::{games, users};
+
+        let user: User = users::table
+            .filter(users::id.eq(donation.user_id))
+            .first(sql_conn)
+            .map_err(|_| ServerError::InvalidForeignKey("user id".to_string()))?;
+        if donation.donation_time.date() < DonationsControl::stored_date(user.registration_date) {
+            return Err(ServerError::DonationBeforeRegistration(user.nickname).into());
+        }
+
+        let game: Game = games::table
+            .filter(games::id.eq(donation.game_id))
+            .first(sql_conn)
+            .map_err(|_| ServerError::InvalidForeignKey("game id".to_string()))?;
+        if donation.donation_time.date() < DonationsControl::stored_date(game.release_date) {
+            return Err(ServerError::DonationBeforeRelease(game.name).into());
+        }
+        Ok(())
+    }
+
     pub async fn get_donations(conn: &DBConnection) -> Result<Vec<DonationsControl>> {
         use crate::schema::donations::dsl::*;
 
@@ -119,57 +172,153 @@ impl DonationsControl {
         use crate::schema::donations::dsl::*;
 
         conn.run(move |sql_connection| -> Result<()> {
-            diesel::insert_into(donations)
-                .values(&donation)
-                .get_result::<Donation>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                DonationsControl::check_dates(sql_connection, &donation)?;
+                let inserted = diesel::insert_into(donations)
+                    .values(&donation)
+                    .get_result::<Donation>(sql_connection)
+                    .map_err(write_error)?;
+                DonationFlagsControl::detect(sql_connection, &inserted)?;
+                StatisticsControl::refresh_game(sql_connection, donation.game_id)?;
+                LoyaltyTiersControl::refresh_user_tier(sql_connection, donation.user_id)
+            })
         })
-        .await
+        .await?;
+        cache::invalidate(&[("donation_user_stats", None)]);
+        Ok(())
     }
 
     pub async fn update_donation(
         conn: &DBConnection,
         id_for_update: i32,
+        version_for_update: i32,
         donation: NewDonation,
     ) -> Result<()> {
         use crate::schema::donations::dsl::*;
 
         conn.run(move |sql_connection| -> Result<()> {
-            diesel::update(donations.filter(&id.eq(id_for_update)))
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                DonationsControl::check_dates(sql_connection, &donation)?;
+                let previous: Donation = donations
+                    .filter(id.eq(id_for_update))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                // Refunds lock the donation too, so none can be added meanwhile.
+                let refunded = DonationRefundsControl::refunded(sql_connection, id_for_update)?;
+                if donation.amount.0 < refunded.0 {
+                    return Err(
+                        ServerError::DonationBelowRefunded(refunded.0 as f64 / 100f64).into(),
+                    );
+                }
+                let updated = diesel::update(
+                    donations
+                        .filter(&id.eq(id_for_update))
+                        .filter(version.eq(version_for_update)),
+                )
                 .set((
+                    version.eq(version + 1),
                     game_id.eq(donation.game_id),
                     user_id.eq(donation.user_id),
                     amount.eq(donation.amount),
                     donation_time.eq(donation.donation_time),
                 ))
                 .get_result::<Donation>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+                .map_err(write_error)?;
+                DonationFlagsControl::detect(sql_connection, &updated)?;
+
+                StatisticsControl::refresh_game(sql_connection, donation.game_id)?;
+                if previous.game_id != donation.game_id {
+                    StatisticsControl::refresh_game(sql_connection, previous.game_id)?;
+                }
+                LoyaltyTiersControl::refresh_user_tier(sql_connection, donation.user_id)?;
+                if previous.user_id != donation.user_id {
+                    LoyaltyTiersControl::refresh_user_tier(sql_connection, previous.user_id)?;
+                }
+                Ok(())
+            })
         })
-        .await
+        .await?;
+        cache::invalidate(&[("donation_user_stats", None)]);
+        Ok(())
     }
 
-    pub async fn delete_donation(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
+    /// Deletes a donation made by mistake. Donations with refunds keep their
+    /// history and have to be refunded in full instead.
+    fn remove_donation(sql_conn: &PgConnection, id_for_delete: i32) -> Result<()> {
         use crate::schema::donations::dsl::*;
 
-        conn.run(move |sql_conn| -> Result<()> {
-            diesel::delete(donations)
+        sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            donations
+                .select(id)
+                .filter(id.eq(id_for_delete))
+                .for_update()
+                .first::<i32>(sql_conn)
+                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+            if DonationRefundsControl::refunded(sql_conn, id_for_delete)?.0 > 0 {
+                return Err(ServerError::DonationHasRefunds.into());
+            }
+
+            let deleted = diesel::delete(donations)
                 .filter(&id.eq(id_for_delete))
                 .get_result::<Donation>(sql_conn)
                 .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(())
+            StatisticsControl::refresh_game(sql_conn, deleted.game_id)?;
+            LoyaltyTiersControl::refresh_user_tier(sql_conn, deleted.user_id)
         })
-        .await
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for DonationsControl {
+    type Form = AddDonation;
+    type New = NewDonation;
+    type Table = donations::table;
+    type Version = donations::version;
+
+    const NAME: &'static str = "donations";
+    const TABLE: &'static str = "Донати";
+
+    fn parse(form: AddDonation) -> Result<Self::New, ServerError> {
+        NewDonation::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_donation_by_id(conn, id).await
+    }
+
+    async fn add(conn: &DBConnection, new: Self::New) -> Result<()> {
+        Self::add_donation(conn, new).await
+    }
+
+    async fn update(conn: &DBConnection, id: i32, version: i32, new: Self::New) -> Result<()> {
+        Self::update_donation(conn, id, version, new).await
+    }
+
+    fn remove(sql_conn: &PgConnection, id: i32) -> Result<()> {
+        Self::remove_donation(sql_conn, id)
+    }
+
+    fn cached(_id: Option<i32>) -> Vec<cache::Dependency> {
+        vec![("donation_user_stats", None)]
+    }
+
+    /// User and game choices.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let users = UsersControl::get_users(conn).await.unwrap();
+        let users_id = users.iter().map(|user| user.id.to_string()).collect();
+
+        let users_name = users.into_iter().map(|user| user.nickname).collect();
+
+        let games = GamesControl::get_games(conn).await.unwrap();
+        let games_id = games.iter().map(|game| game.id.to_string()).collect();
+
+        let games_name = games.into_iter().map(|game| game.name).collect();
+
+        vec![users_id, users_name, games_id, games_name]
+    }
+
+    fn for_edit(&mut self) -> Result<()> {
+        self.change_date_format(&config::formats().date_time, "%Y-%m-%dT%H:%M")
     }
 }
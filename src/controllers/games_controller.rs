@@ -1,28 +1,154 @@
+use crate::cache;
+use crate::config;
 use crate::controllers::*;
 use crate::errors::ServerError;
 use crate::models::*;
-use crate::requests_handler::AddGame;
-use crate::schema::games;
+use crate::requests_handler::{AddGame, GamesBulkUpdate};
+use crate::schema::{game_status_history, games};
 use crate::DBConnection;
 use anyhow::Result;
-use chrono::{Datelike, NaiveDate};
-use diesel::dsl::sql;
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime};
 use diesel::pg::data_types::{PgDate, PgMoney};
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::result::Error as DieselError;
 use serde::Serialize;
 
-#[derive(Insertable)]
+#[derive(Insertable, AsChangeset)]
 #[table_name = "games"]
 pub struct NewGame {
     pub name: String,
-    pub genre: String,
     pub release_date: PgDate,
     pub prime_cost: PgMoney,
     pub publisher_id: i32,
     pub cost: PgMoney,
     pub is_subscribable: bool,
 }
+
+/// A game with the genres picked on its form.
+pub struct GameWithGenres {
+    pub game: NewGame,
+    pub genre_ids: Vec<i32>,
+}
+
+impl Row<games::table, games::version> for GameWithGenres {
+    fn insert(self, sql_conn: &PgConnection) -> Result<()> {
+        let inserted: i32 = diesel::insert_into(games::table)
+            .values(&self.game)
+            .returning(games::id)
+            .get_result(sql_conn)
+            .map_err(write_error)?;
+        GenresControl::set_game_genres(sql_conn, inserted, &self.genre_ids)
+    }
+
+    fn update(self, sql_conn: &PgConnection, id: i32, version: i32) -> Result<()> {
+        Row::<games::table, games::version>::update(self.game, sql_conn, id, version)?;
+        GenresControl::set_game_genres(sql_conn, id, &self.genre_ids)
+    }
+
+    fn delete(sql_conn: &PgConnection, id: i32) -> Result<()> {
+        <NewGame as Row<games::table, games::version>>::delete(sql_conn, id)
+    }
+}
+#[derive(Insertable)]
+#[table_name = "game_status_history"]
+pub struct NewGameStatusChange {
+    pub game_id: i32,
+    pub from_status: String,
+    pub to_status: String,
+    pub changed_at: NaiveDateTime,
+    pub changed_by: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameStatus {
+    Concept,
+    PreProduction,
+    Production,
+    Alpha,
+    Beta,
+    Released,
+    Sunset,
+}
+
+impl GameStatus {
+    pub const ALL: [GameStatus; 7] = [
+        GameStatus::Concept,
+        GameStatus::PreProduction,
+        GameStatus::Production,
+        GameStatus::Alpha,
+        GameStatus::Beta,
+        GameStatus::Released,
+        GameStatus::Sunset,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameStatus::Concept => "concept",
+            GameStatus::PreProduction => "pre-production",
+            GameStatus::Production => "production",
+            GameStatus::Alpha => "alpha",
+            GameStatus::Beta => "beta",
+            GameStatus::Released => "released",
+            GameStatus::Sunset => "sunset",
+        }
+    }
+
+    pub fn parse(status: &str) -> Result<Self, ServerError> {
+        GameStatus::ALL
+            .iter()
+            .find(|known| known.as_str() == status)
+            .copied()
+            .ok_or_else(|| ServerError::InvalidStatus(status.to_string()))
+    }
+
+    /// Statuses a game may be moved to from this one. Development stages can
+    /// step one stage forward or back, a released game can only be sunset.
+    pub fn allowed_transitions(&self) -> Vec<GameStatus> {
+        match self {
+            GameStatus::Concept => vec![GameStatus::PreProduction],
+            GameStatus::PreProduction => vec![GameStatus::Production, GameStatus::Concept],
+            GameStatus::Production => vec![GameStatus::Alpha, GameStatus::PreProduction],
+            GameStatus::Alpha => vec![GameStatus::Beta, GameStatus::Production],
+            GameStatus::Beta => vec![GameStatus::Released, GameStatus::Alpha],
+            GameStatus::Released => vec![GameStatus::Sunset],
+            GameStatus::Sunset => vec![],
+        }
+    }
+
+    pub fn can_move_to(&self, to: GameStatus) -> bool {
+        self.allowed_transitions().contains(&to)
+    }
+
+    pub fn is_released(&self) -> bool {
+        matches!(self, GameStatus::Released | GameStatus::Sunset)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct GameStatusChangeControl {
+    pub id: i32,
+    pub from_status: String,
+    pub to_status: String,
+    pub changed_at: String,
+    pub changed_by: String,
+}
+
+impl std::convert::From<GameStatusChange> for GameStatusChangeControl {
+    fn from(change: GameStatusChange) -> Self {
+        GameStatusChangeControl {
+            id: change.id,
+            from_status: change.from_status,
+            to_status: change.to_status,
+            changed_at: change
+                .changed_at
+                .format(&config::formats().date_time)
+                .to_string(),
+            changed_by: change.changed_by,
+        }
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct TotalDonations {
     user: String,
@@ -31,6 +157,9 @@ pub struct TotalDonations {
 
 impl NewGame {
     pub fn from(game: AddGame) -> Result<Self, ServerError> {
+        if game.genre_ids.is_empty() {
+            return Err(ServerError::NullValues(vec!["genre".to_string()]));
+        }
         let release_date = NaiveDate::parse_from_str(&game.release_date, "%Y-%m-%d");
         if release_date.is_err() {
             return Err(ServerError::InvalidDate);
@@ -43,7 +172,6 @@ impl NewGame {
 
         Ok(NewGame {
             name: game.name,
-            genre: game.genre,
             release_date: PgDate(release_date.num_days_from_ce()),
             prime_cost: PgMoney((game.prime_cost * 100f64) as i64),
             publisher_id: game.publisher_id,
@@ -52,17 +180,64 @@ impl NewGame {
         })
     }
 }
-#[derive(Serialize, Debug)]
+
+/// Fields set on every selected game. `None` leaves the field as it is.
+#[derive(AsChangeset)]
+#[table_name = "games"]
+pub struct GamesBulkChange {
+    pub is_subscribable: Option<bool>,
+    pub publisher_id: Option<i32>,
+}
+
+impl GamesBulkChange {
+    pub fn from(change: &GamesBulkUpdate) -> Result<Self, ServerError> {
+        let is_subscribable = match change.is_subscribable.as_str() {
+            "" => None,
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => {
+                return Err(ServerError::InvalidValue(vec![
+                    "is subscribable".to_string()
+                ]))
+            }
+        };
+        if is_subscribable.is_none() && change.publisher_id.is_none() {
+            return Err(ServerError::NullValues(vec![
+                "is subscribable".to_string(),
+                "publisher id".to_string(),
+            ]));
+        }
+
+        Ok(GamesBulkChange {
+            is_subscribable,
+            publisher_id: change.publisher_id,
+        })
+    }
+}
+/// Cached tables with rows of any game, all dropped when games are deleted.
+pub const GAME_ROWS: [cache::Dependency; 5] = [
+    ("games", None),
+    ("game_genres", None),
+    ("game_releases", None),
+    ("sales", None),
+    ("donation_user_stats", None),
+];
+
+#[derive(Serialize, Debug, Clone)]
 pub struct GamesControl {
     pub id: i32,
+    pub version: i32,
     pub name: String,
     pub genre: String,
+    pub genre_ids: Vec<i32>,
     pub release_date: String,
+    pub first_release: String,
     pub prime_cost: f64,
     pub publisher: String,
     pub publisher_id: i32,
     pub cost: f64,
     pub is_subscribable: bool,
+    pub status: String,
 }
 
 impl GamesControl {
@@ -76,53 +251,168 @@ impl GamesControl {
         Vec<InvestmentsControl>,
         Vec<TotalDonations>,
         f64,
+        Vec<GameStatusChangeControl>,
+        Vec<String>,
+        usize,
+        f64,
+        ReleaseMatrix,
+        Vec<PlatformsControl>,
+        Vec<DailyDonationsControl>,
+        (i32, f64),
     ) {
         let (total_donations, sum) = GamesControl::get_total_donations(conn, id_for_lookup).await;
+        let allowed_statuses = match GamesControl::get_game_by_id(conn, id_for_lookup).await {
+            Ok(game) => GameStatus::parse(&game.status)
+                .map(|status| {
+                    status
+                        .allowed_transitions()
+                        .iter()
+                        .map(|next| next.as_str().to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Err(_) => vec![],
+        };
+        let (active_subscribers, monthly_recurring_revenue) =
+            GamesControl::get_subscribers(conn, id_for_lookup).await;
         (
             id_for_lookup,
-            GamesControl::get_game_staff(conn, id_for_lookup).await,
-            GamesControl::get_donations(conn, id_for_lookup).await,
+            GamesControl::get_game_staff(conn, id_for_lookup, None).await,
+            GamesControl::get_donations(conn, id_for_lookup, None).await,
             GamesControl::get_investments(conn, id_for_lookup).await,
             total_donations,
             sum,
+            GamesControl::get_status_history(conn, id_for_lookup).await,
+            allowed_statuses,
+            active_subscribers,
+            monthly_recurring_revenue,
+            ReleasesControl::get_release_matrix(conn, id_for_lookup)
+                .await
+                .unwrap(),
+            PlatformsControl::get_platforms(conn).await.unwrap(),
+            StatisticsControl::get_daily_donations(conn, id_for_lookup)
+                .await
+                .unwrap(),
+            StatisticsControl::get_invested(conn, id_for_lookup)
+                .await
+                .unwrap(),
         )
     }
 
-    pub async fn get_total_donations(
+    /// Number of users with an active subscription and the monthly recurring
+    /// revenue of subscriptions that are going to be renewed.
+    pub async fn get_subscribers(conn: &DBConnection, id_for_lookup: i32) -> (usize, f64) {
+        use crate::schema::subscriptions::dsl::*;
+
+        let today = Local::today().naive_local();
+        let active = conn
+            .run(move |sql_conn| -> Vec<Subscription> {
+                subscriptions
+                    .filter(game_id.eq(id_for_lookup))
+                    .filter(start_date.le(today))
+                    .filter(end_date.ge(today))
+                    .load(sql_conn)
+                    .unwrap()
+            })
+            .await;
+
+        let mut subscribers: Vec<i32> = active.iter().map(|active| active.user_id).collect();
+        subscribers.sort_unstable();
+        subscribers.dedup();
+
+        let monthly_recurring_revenue = active
+            .iter()
+            .filter(|active| active.cancelled_at.is_none())
+            .fold(0f64, |sum, active| {
+                sum + SubscriptionsControl::monthly_revenue(active)
+            });
+
+        (subscribers.len(), monthly_recurring_revenue)
+    }
+
+    pub async fn get_status_history(
         conn: &DBConnection,
         id_for_lookup: i32,
-    ) -> (Vec<TotalDonations>, f64) {
-        use crate::schema::donations::dsl::*;
-        use crate::schema::users;
-
-        #[derive(Queryable)]
-        struct Tmp {
-            user: String,
-            amount: PgMoney,
-        }
+    ) -> Vec<GameStatusChangeControl> {
+        use crate::schema::game_status_history::dsl::*;
 
         let table = conn
-            .run(move |sql_conn| -> Vec<Tmp> {
-                donations
-                    .select((
-                        sql::<diesel::types::VarChar>("nickname as nickname"),
-                        sql::<diesel::sql_types::Money>("sum(amount) AS sum"),
-                    ))
-                    .group_by(users::nickname)
-                    .inner_join(games::table)
-                    .inner_join(users::table)
-                    .filter(games::id.eq(id_for_lookup))
+            .run(move |sql_conn| -> Vec<GameStatusChange> {
+                game_status_history
+                    .select((id, from_status, to_status, changed_at, changed_by))
+                    .filter(game_id.eq(id_for_lookup))
+                    .order(changed_at.asc())
                     .load(sql_conn)
                     .unwrap()
             })
             .await;
 
-        let result: Vec<TotalDonations> = table
-            .iter()
-            .map(|query_result| TotalDonations {
-                user: query_result.user.clone(),
-                amount: query_result.amount.0 as f64 / 100f64,
+        table
+            .into_iter()
+            .map(GameStatusChangeControl::from)
+            .collect()
+    }
+
+    pub async fn change_status(
+        conn: &DBConnection,
+        id_for_update: i32,
+        new_status: &str,
+        changed_by: String,
+    ) -> Result<()> {
+        let new_status = GameStatus::parse(new_status)?;
+
+        conn.run(move |sql_connection| -> Result<()> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let game: Game = games::table
+                    .filter(games::id.eq(id_for_update))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                let old_status = GameStatus::parse(&game.status)?;
+
+                if !old_status.can_move_to(new_status) {
+                    return Err(ServerError::InvalidStatusTransition(
+                        old_status.as_str().to_string(),
+                        new_status.as_str().to_string(),
+                    )
+                    .into());
+                }
+
+                diesel::update(games::table.filter(games::id.eq(id_for_update)))
+                    .set((
+                        games::status.eq(new_status.as_str()),
+                        games::version.eq(games::version + 1),
+                    ))
+                    .execute(sql_connection)?;
+
+                diesel::insert_into(game_status_history::table)
+                    .values(&NewGameStatusChange {
+                        game_id: id_for_update,
+                        from_status: old_status.as_str().to_string(),
+                        to_status: new_status.as_str().to_string(),
+                        changed_at: Local::now().naive_local(),
+                        changed_by,
+                    })
+                    .execute(sql_connection)?;
+                Ok(())
             })
+        })
+        .await?;
+        cache::invalidate(&[("games", Some(id_for_update))]);
+        Ok(())
+    }
+
+    /// Net donations of every user to the game and their sum, read from the
+    /// materialized statistics.
+    pub async fn get_total_donations(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+    ) -> (Vec<TotalDonations>, f64) {
+        let result: Vec<TotalDonations> = StatisticsControl::get_game_donors(conn, id_for_lookup)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(user, amount)| TotalDonations { user, amount })
             .collect();
 
         let total_amount = result.iter().map(|res| res.amount).sum();
@@ -155,17 +445,28 @@ impl GamesControl {
         vec
     }
 
-    pub async fn get_donations(conn: &DBConnection, id_for_lookup: i32) -> Vec<DonationsControl> {
+    /// Donations to the game, only the ones made within `period` (first and
+    /// last day, inclusive) if it is given.
+    pub async fn get_donations(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+        period: Option<(NaiveDate, NaiveDate)>,
+    ) -> Vec<DonationsControl> {
         use crate::schema::donations;
         use crate::schema::games::dsl::*;
 
         let table = conn
             .run(move |sql_conn| -> Vec<(Game, Donation)> {
-                games
+                let mut query = games
                     .filter(id.eq(id_for_lookup))
                     .inner_join(donations::table)
-                    .load(sql_conn)
-                    .unwrap()
+                    .into_boxed();
+                if let Some((from, to)) = period {
+                    query = query
+                        .filter(donations::donation_time.ge(from.and_hms(0, 0, 0)))
+                        .filter(donations::donation_time.lt(to.succ().and_hms(0, 0, 0)));
+                }
+                query.load(sql_conn).unwrap()
             })
             .await;
 
@@ -177,17 +478,30 @@ impl GamesControl {
         vec
     }
 
-    pub async fn get_game_staff(conn: &DBConnection, id_for_lookup: i32) -> Vec<JobsControl> {
+    /// Jobs on the game, only the ones that overlap `period` (first and last
+    /// day, inclusive) if it is given.
+    pub async fn get_game_staff(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+        period: Option<(NaiveDate, NaiveDate)>,
+    ) -> Vec<JobsControl> {
         use crate::schema::games::dsl::*;
         use crate::schema::jobs;
 
         let table = conn
             .run(move |sql_conn| -> Vec<(Game, Job)> {
-                games
+                let mut query = games
                     .filter(id.eq(id_for_lookup))
                     .inner_join(jobs::table)
-                    .load(sql_conn)
-                    .unwrap()
+                    .into_boxed();
+                if let Some((from, to)) = period {
+                    query = query.filter(jobs::first_work_day.le(to)).filter(
+                        jobs::last_work_day
+                            .is_null()
+                            .or(jobs::last_work_day.ge(from)),
+                    );
+                }
+                query.load(sql_conn).unwrap()
             })
             .await;
 
@@ -210,17 +524,32 @@ impl GamesControl {
         let publishers = PublishersControl::get_publisher_by_id(&pool, games_struct.publisher_id)
             .await
             .unwrap();
-        let release_date = release_date.format("%d-%m-%Y").to_string();
+        let game_genres = GenresControl::get_game_genres(&pool, games_struct.id)
+            .await
+            .unwrap();
+        let first_release = ReleasesControl::get_first_release(&pool, games_struct.id)
+            .await
+            .unwrap()
+            .unwrap_or(release_date);
+        let release_date = release_date.format(&config::formats().date).to_string();
         GamesControl {
             id: games_struct.id,
+            version: games_struct.version,
             name: games_struct.name,
-            genre: games_struct.genre,
+            genre: game_genres
+                .iter()
+                .map(|(_, genre)| genre.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            genre_ids: game_genres.iter().map(|(genre_id, _)| *genre_id).collect(),
             release_date: release_date,
+            first_release: first_release.format(&config::formats().date).to_string(),
             prime_cost: games_struct.prime_cost.0 as f64 / 100f64,
             publisher: publishers.name,
             publisher_id: games_struct.publisher_id,
             cost: games_struct.cost.0 as f64 / 100f64,
             is_subscribable: games_struct.is_subscribable,
+            status: games_struct.status,
         }
     }
     pub fn change_date_format(&mut self, from: &str, to: &str) -> Result<()> {
@@ -232,9 +561,46 @@ impl GamesControl {
     pub async fn get_games(conn: &DBConnection) -> Result<Vec<GamesControl>> {
         use crate::schema::games::dsl::*;
 
+        cache::cached("games".to_string(), async {
+            let results = conn
+                .run(move |sql_conn| -> Result<Vec<Game>> {
+                    Ok(games.order(id.asc()).load::<Game>(sql_conn)?)
+                })
+                .await?;
+
+            let mut games_result: Vec<GamesControl> = vec![];
+
+            for game in results {
+                games_result.push(GamesControl::make_games_control(conn, game).await);
+            }
+
+            Ok((
+                games_result,
+                vec![
+                    ("games", None),
+                    ("publishers", None),
+                    ("game_genres", None),
+                    ("genres", None),
+                    ("game_releases", None),
+                ],
+            ))
+        })
+        .await
+    }
+
+    pub async fn get_games_by_status(
+        conn: &DBConnection,
+        status_for_lookup: &str,
+    ) -> Result<Vec<GamesControl>> {
+        use crate::schema::games::dsl::*;
+
+        let status_for_lookup = GameStatus::parse(status_for_lookup)?.as_str();
         let results = conn
             .run(move |sql_conn| -> Result<Vec<Game>> {
-                Ok(games.order(id.asc()).load::<Game>(sql_conn)?)
+                Ok(games
+                    .filter(status.eq(status_for_lookup))
+                    .order(id.asc())
+                    .load::<Game>(sql_conn)?)
             })
             .await?;
 
@@ -250,73 +616,120 @@ impl GamesControl {
     pub async fn get_game_by_id(conn: &DBConnection, id_for_lookup: i32) -> Result<GamesControl> {
         use crate::schema::games::dsl::*;
 
-        let game = conn
-            .run(move |sql_conn| -> Result<Game> {
-                let result: Game = games
-                    .filter(id.eq(id_for_lookup))
-                    .first(sql_conn)
-                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-                Ok(result)
-            })
-            .await?;
-
-        Ok(GamesControl::make_games_control(conn, game).await)
+        cache::cached(format!("games/{}", id_for_lookup), async {
+            let game = conn
+                .run(move |sql_conn| -> Result<Game> {
+                    let result: Game = games
+                        .filter(id.eq(id_for_lookup))
+                        .first(sql_conn)
+                        .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                    Ok(result)
+                })
+                .await?;
+
+            let game = GamesControl::make_games_control(conn, game).await;
+            let publisher = game.publisher_id;
+            Ok((
+                game,
+                vec![
+                    ("games", Some(id_for_lookup)),
+                    ("publishers", Some(publisher)),
+                    ("game_genres", Some(id_for_lookup)),
+                    ("genres", None),
+                    ("game_releases", Some(id_for_lookup)),
+                ],
+            ))
+        })
+        .await
     }
 
-    pub async fn add_game(conn: &DBConnection, game: NewGame) -> Result<()> {
+    /// Applies the change to the selected games and returns the rows that failed.
+    pub async fn update_selected(
+        conn: &DBConnection,
+        ids: Vec<i32>,
+        change: GamesBulkChange,
+    ) -> Result<Vec<String>> {
         use crate::schema::games::dsl::*;
 
-        conn.run(move |sql_connection| -> Result<()> {
-            diesel::insert_into(games)
-                .values(&game)
-                .get_result::<Game>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+        let failed = conn
+            .run(move |sql_conn| {
+                for_each_selected(sql_conn, &ids, |id_for_update| {
+                    diesel::update(games.filter(id.eq(id_for_update)))
+                        .set((&change, version.eq(version + 1)))
+                        .get_result::<Game>(sql_conn)
+                        .map_err(|err| match err {
+                            DieselError::DatabaseError(_, info) => {
+                                ServerError::InvalidForeignKey(info.message().to_string())
+                            }
+                            _ => ServerError::InvalidValue(vec!["Id".to_string()]),
+                        })?;
+                    Ok(())
+                })
+            })
+            .await?;
+        cache::invalidate(&[("games", None), ("game_genres", None)]);
+        Ok(failed)
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for GamesControl {
+    type Form = AddGame;
+    type New = GameWithGenres;
+    type Table = games::table;
+    type Version = games::version;
+
+    const NAME: &'static str = "games";
+    const TABLE: &'static str = "Ігри";
+
+    fn parse(form: AddGame) -> Result<Self::New, ServerError> {
+        let genre_ids = form.genre_ids.clone();
+        Ok(GameWithGenres {
+            game: NewGame::from(form)?,
+            genre_ids,
         })
-        .await
     }
 
-    pub async fn update_game(conn: &DBConnection, id_for_update: i32, game: NewGame) -> Result<()> {
-        use crate::schema::games::dsl::*;
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_game_by_id(conn, id).await
+    }
 
-        conn.run(move |sql_connection| -> Result<()> {
-            diesel::update(games.filter(&id.eq(id_for_update)))
-                .set((
-                    name.eq(game.name),
-                    genre.eq(game.genre),
-                    release_date.eq(game.release_date),
-                    prime_cost.eq(game.prime_cost),
-                    publisher_id.eq(game.publisher_id),
-                    cost.eq(game.cost),
-                    is_subscribable.eq(game.is_subscribable),
-                ))
-                .get_result::<Game>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
-        })
-        .await
+    /// Everything that shows the game, or every game row when `id` is `None`.
+    fn cached(id: Option<i32>) -> Vec<cache::Dependency> {
+        match id {
+            Some(id) => vec![
+                ("games", Some(id)),
+                ("game_genres", Some(id)),
+                ("game_releases", Some(id)),
+                ("sales", None),
+                ("donation_user_stats", None),
+            ],
+            None => GAME_ROWS.to_vec(),
+        }
     }
 
-    pub async fn delete_game(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
-        use crate::schema::games::dsl::*;
+    /// Publisher and genre choices.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let publishers = PublishersControl::get_publishers(conn).await.unwrap();
+        let publishers_id = publishers
+            .iter()
+            .map(|publisher| publisher.id.to_string())
+            .collect();
 
-        conn.run(move |sql_conn| -> Result<()> {
-            diesel::delete(games)
-                .filter(&id.eq(id_for_delete))
-                .get_result::<Game>(sql_conn)
-                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(())
-        })
-        .await
+        let publishers_name = publishers
+            .into_iter()
+            .map(|publisher| publisher.name)
+            .collect();
+
+        let genres = GenresControl::get_genres(conn).await.unwrap();
+        let genres_id = genres.iter().map(|genre| genre.id.to_string()).collect();
+
+        let genres_path = genres.into_iter().map(|genre| genre.path).collect();
+
+        vec![publishers_id, publishers_name, genres_id, genres_path]
+    }
+
+    fn for_edit(&mut self) -> Result<()> {
+        self.change_date_format(&config::formats().date, "%Y-%m-%d")
     }
 }
@@ -0,0 +1,285 @@
+use crate::cache;
+use crate::controllers::write_error;
+use crate::controllers::Resource;
+use crate::controllers::StatisticsControl;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddGenre;
+use crate::schema::{game_genres, genres};
+use crate::DBConnection;
+use anyhow::Result;
+use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Genres, `(game, genre)` tags, game costs and net donations by game, as
+/// read for `get_genres`.
+type GenreRows = (
+    Vec<Genre>,
+    Vec<(i32, i32)>,
+    Vec<(i32, PgMoney)>,
+    HashMap<i32, i64>,
+);
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "genres"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewGenre {
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+impl NewGenre {
+    pub fn from(genre: AddGenre) -> Result<Self, ServerError> {
+        let name = genre.name.trim().to_string();
+        if name.is_empty() {
+            return Err(ServerError::NullValues(vec!["name".to_string()]));
+        }
+
+        Ok(NewGenre {
+            name,
+            parent_id: genre.parent_id,
+        })
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "game_genres"]
+struct NewGameGenre {
+    game_id: i32,
+    genre_id: i32,
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct GenresControl {
+    pub id: i32,
+    pub version: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub parent: String,
+    pub path: String,
+    pub games_count: usize,
+    pub average_cost: f64,
+    pub total_donations: f64,
+}
+
+impl GenresControl {
+    /// Names from the top-level genre down to this one.
+    fn path(genre: &Genre, genres_by_id: &HashMap<i32, Genre>) -> String {
+        let mut names = vec![genre.name.clone()];
+        let mut next = genre.parent_id;
+        while let Some(parent) = next.and_then(|parent_id| genres_by_id.get(&parent_id)) {
+            if names.len() > genres_by_id.len() {
+                break;
+            }
+            names.push(parent.name.clone());
+            next = parent.parent_id;
+        }
+        names.reverse();
+        names.join(" / ")
+    }
+
+    /// Rejects a parent that is the genre or one of its subgenres.
+    fn check_parent(
+        sql_conn: &PgConnection,
+        id_for_check: i32,
+        new_parent_id: Option<i32>,
+    ) -> Result<()> {
+        use crate::schema::genres::dsl::*;
+
+        let mut next = new_parent_id;
+        while let Some(current) = next {
+            if current == id_for_check {
+                return Err(ServerError::GenreCycle.into());
+            }
+            next = genres
+                .select(parent_id)
+                .filter(id.eq(current))
+                .first::<Option<i32>>(sql_conn)
+                .optional()?
+                .flatten();
+        }
+        Ok(())
+    }
+
+    /// All genres ordered by their path. Statistics of a genre cover the
+    /// games tagged with it or with any of its subgenres.
+    pub async fn get_genres(conn: &DBConnection) -> Result<Vec<GenresControl>> {
+        use crate::schema::games;
+
+        cache::cached("genres".to_string(), async {
+            let (all_genres, tags, costs, donated) = conn
+                .run(move |sql_conn| -> Result<GenreRows> {
+                    let all_genres = genres::table.load(sql_conn)?;
+                    let tags = game_genres::table
+                        .select((game_genres::game_id, game_genres::genre_id))
+                        .load(sql_conn)?;
+                    let costs = games::table
+                        .select((games::id, games::cost))
+                        .load(sql_conn)?;
+                    let donated = StatisticsControl::donated_by_game(sql_conn)?;
+                    Ok((all_genres, tags, costs, donated))
+                })
+                .await?;
+
+            let costs: HashMap<i32, i64> = costs
+                .into_iter()
+                .map(|(game_id, cost)| (game_id, cost.0))
+                .collect();
+
+            let genres_by_id: HashMap<i32, Genre> = all_genres
+                .into_iter()
+                .map(|genre| (genre.id, genre))
+                .collect();
+            let mut games_by_genre: HashMap<i32, HashSet<i32>> = HashMap::new();
+            for (game_id, genre_id) in tags {
+                // Every ancestor of a genre also counts the game, once.
+                let mut next = Some(genre_id);
+                let mut seen = HashSet::new();
+                while let Some(current) = next.filter(|current| seen.insert(*current)) {
+                    games_by_genre.entry(current).or_default().insert(game_id);
+                    next = genres_by_id.get(&current).and_then(|genre| genre.parent_id);
+                }
+            }
+
+            let mut result: Vec<GenresControl> = genres_by_id
+                .values()
+                .map(|genre| {
+                    let tagged = games_by_genre.get(&genre.id).cloned().unwrap_or_default();
+                    let total_cost: i64 = tagged.iter().filter_map(|game| costs.get(game)).sum();
+                    let total_donations: i64 =
+                        tagged.iter().filter_map(|game| donated.get(game)).sum();
+                    GenresControl {
+                        id: genre.id,
+                        version: genre.version,
+                        name: genre.name.clone(),
+                        parent_id: genre.parent_id,
+                        parent: genre
+                            .parent_id
+                            .and_then(|parent_id| genres_by_id.get(&parent_id))
+                            .map(|parent| parent.name.clone())
+                            .unwrap_or_default(),
+                        path: GenresControl::path(genre, &genres_by_id),
+                        games_count: tagged.len(),
+                        average_cost: if tagged.is_empty() {
+                            0f64
+                        } else {
+                            total_cost as f64 / 100f64 / tagged.len() as f64
+                        },
+                        total_donations: total_donations as f64 / 100f64,
+                    }
+                })
+                .collect();
+            result.sort_by(|first, second| first.path.cmp(&second.path));
+
+            Ok((
+                result,
+                vec![
+                    ("genres", None),
+                    ("game_genres", None),
+                    ("games", None),
+                    ("donation_user_stats", None),
+                ],
+            ))
+        })
+        .await
+    }
+
+    pub async fn get_genre_by_id(conn: &DBConnection, id_for_lookup: i32) -> Result<GenresControl> {
+        use crate::schema::genres::dsl::*;
+
+        conn.run(move |sql_conn| -> Result<GenresControl> {
+            let result: Genre = genres
+                .filter(id.eq(id_for_lookup))
+                .first(sql_conn)
+                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+            Ok(GenresControl {
+                id: result.id,
+                version: result.version,
+                name: result.name,
+                parent_id: result.parent_id,
+                ..Default::default()
+            })
+        })
+        .await
+    }
+
+    /// Genres the game is tagged with as (id, name) pairs.
+    pub async fn get_game_genres(
+        conn: &DBConnection,
+        game_id_for_lookup: i32,
+    ) -> Result<Vec<(i32, String)>> {
+        conn.run(move |sql_conn| -> Result<Vec<(i32, String)>> {
+            Ok(game_genres::table
+                .inner_join(genres::table)
+                .select((genres::id, genres::name))
+                .filter(game_genres::game_id.eq(game_id_for_lookup))
+                .order(genres::name.asc())
+                .load(sql_conn)?)
+        })
+        .await
+    }
+
+    /// Replaces the genres the game is tagged with.
+    pub fn set_game_genres(sql_conn: &PgConnection, game_id: i32, genre_ids: &[i32]) -> Result<()> {
+        diesel::delete(game_genres::table.filter(game_genres::game_id.eq(game_id)))
+            .execute(sql_conn)?;
+
+        let tags: Vec<NewGameGenre> = genre_ids
+            .iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|genre_id| NewGameGenre {
+                game_id,
+                genre_id: *genre_id,
+            })
+            .collect();
+        diesel::insert_into(game_genres::table)
+            .values(&tags)
+            .execute(sql_conn)
+            .map_err(write_error)?;
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for GenresControl {
+    type Form = AddGenre;
+    type New = NewGenre;
+    type Table = genres::table;
+    type Version = genres::version;
+
+    const NAME: &'static str = "genres";
+    const TABLE: &'static str = "Жанри";
+
+    fn parse(form: AddGenre) -> Result<Self::New, ServerError> {
+        NewGenre::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_genre_by_id(conn, id).await
+    }
+
+    fn check(sql_conn: &PgConnection, new: &NewGenre, id: Option<i32>) -> Result<()> {
+        match id {
+            Some(id) => Self::check_parent(sql_conn, id, new.parent_id),
+            None => Ok(()),
+        }
+    }
+
+    fn cached(_id: Option<i32>) -> Vec<cache::Dependency> {
+        vec![("genres", None), ("game_genres", None)]
+    }
+
+    /// Parent genre choices.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let genres = Self::get_genres(conn).await.unwrap();
+        let genres_id = genres.iter().map(|genre| genre.id.to_string()).collect();
+
+        let genres_path = genres.into_iter().map(|genre| genre.path).collect();
+
+        vec![genres_id, genres_path]
+    }
+}
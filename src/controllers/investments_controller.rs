@@ -1,5 +1,8 @@
+use crate::controllers::write_error;
 use crate::controllers::GamesControl;
 use crate::controllers::InvestorsControl;
+use crate::controllers::Resource;
+use crate::controllers::StatisticsControl;
 use crate::errors::ServerError;
 use crate::models::*;
 use crate::requests_handler::AddInvestment;
@@ -7,11 +10,11 @@ use crate::schema::investments;
 use crate::DBConnection;
 use anyhow::Result;
 use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::result::Error as DieselError;
 use serde::Serialize;
 
-#[derive(Insertable)]
+#[derive(Insertable, AsChangeset)]
 #[table_name = "investments"]
 pub struct NewInvestment {
     pub game_id: i32,
@@ -33,6 +36,7 @@ impl NewInvestment {
 #[derive(Serialize, Debug)]
 pub struct InvestmentsControl {
     pub id: i32,
+    pub version: i32,
     pub game: String,
     pub game_id: i32,
     pub investor: String,
@@ -57,6 +61,7 @@ impl InvestmentsControl {
 
         InvestmentsControl {
             id: investments_struct.id,
+            version: investments_struct.version,
             game: game,
             game_id: investments_struct.game_id,
             investor: investor,
@@ -108,16 +113,13 @@ impl InvestmentsControl {
         use crate::schema::investments::dsl::*;
 
         conn.run(move |sql_connection| -> Result<()> {
-            diesel::insert_into(investments)
-                .values(&investment)
-                .get_result::<Investment>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                diesel::insert_into(investments)
+                    .values(&investment)
+                    .get_result::<Investment>(sql_connection)
+                    .map_err(write_error)?;
+                StatisticsControl::refresh_game(sql_connection, investment.game_id)
+            })
         })
         .await
     }
@@ -125,40 +127,103 @@ impl InvestmentsControl {
     pub async fn update_investment(
         conn: &DBConnection,
         id_for_update: i32,
+        version_for_update: i32,
         investment: NewInvestment,
     ) -> Result<()> {
         use crate::schema::investments::dsl::*;
 
         conn.run(move |sql_connection| -> Result<()> {
-            diesel::update(investments.filter(&id.eq(id_for_update)))
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let previous: Investment = investments
+                    .filter(id.eq(id_for_update))
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                diesel::update(
+                    investments
+                        .filter(&id.eq(id_for_update))
+                        .filter(version.eq(version_for_update)),
+                )
                 .set((
+                    version.eq(version + 1),
                     game_id.eq(investment.game_id),
                     investor_id.eq(investment.investor_id),
                     share.eq(investment.share),
                     invested.eq(investment.invested),
                 ))
                 .get_result::<Investment>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+                .map_err(write_error)?;
+
+                StatisticsControl::refresh_game(sql_connection, investment.game_id)?;
+                if previous.game_id != investment.game_id {
+                    StatisticsControl::refresh_game(sql_connection, previous.game_id)?;
+                }
+                Ok(())
+            })
         })
         .await
     }
 
-    pub async fn delete_investment(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
+    fn remove_investment(sql_conn: &PgConnection, id_for_delete: i32) -> Result<()> {
         use crate::schema::investments::dsl::*;
 
-        conn.run(move |sql_conn| -> Result<()> {
-            diesel::delete(investments)
+        sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            let deleted = diesel::delete(investments)
                 .filter(&id.eq(id_for_delete))
                 .get_result::<Investment>(sql_conn)
                 .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(())
+            StatisticsControl::refresh_game(sql_conn, deleted.game_id)
         })
-        .await
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for InvestmentsControl {
+    type Form = AddInvestment;
+    type New = NewInvestment;
+    type Table = investments::table;
+    type Version = investments::version;
+
+    const NAME: &'static str = "investments";
+    const TABLE: &'static str = "Інвестиції";
+
+    fn parse(form: AddInvestment) -> Result<Self::New, ServerError> {
+        NewInvestment::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_investment_by_id(conn, id).await
+    }
+
+    async fn add(conn: &DBConnection, new: Self::New) -> Result<()> {
+        Self::add_investment(conn, new).await
+    }
+
+    async fn update(conn: &DBConnection, id: i32, version: i32, new: Self::New) -> Result<()> {
+        Self::update_investment(conn, id, version, new).await
+    }
+
+    fn remove(sql_conn: &PgConnection, id: i32) -> Result<()> {
+        Self::remove_investment(sql_conn, id)
+    }
+
+    /// Game and investor choices.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let games = GamesControl::get_games(conn).await.unwrap();
+        let games_id = games.iter().map(|game| game.id.to_string()).collect();
+
+        let games_name = games.into_iter().map(|game| game.name).collect();
+
+        let investors = InvestorsControl::get_investors(conn).await.unwrap();
+        let investors_id = investors
+            .iter()
+            .map(|investor| investor.id.to_string())
+            .collect();
+
+        let investors_name = investors
+            .into_iter()
+            .map(|investor| investor.name)
+            .collect();
+
+        vec![games_id, games_name, investors_id, investors_name]
     }
 }
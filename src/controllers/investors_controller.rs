@@ -5,11 +5,18 @@ use crate::requests_handler::AddInvestor;
 use crate::schema::investors;
 use crate::DBConnection;
 use anyhow::Result;
+use diesel::dsl::sql;
+use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::result::Error as DieselError;
+use diesel::sql_types::{Money, Nullable};
 use serde::Serialize;
+use std::collections::HashMap;
 
-#[derive(Insertable)]
+/// A sum of money per investor.
+type MoneySums = Vec<(i32, Option<PgMoney>)>;
+
+#[derive(Insertable, AsChangeset)]
 #[table_name = "investors"]
 pub struct NewInvestor {
     pub name: String,
@@ -27,6 +34,7 @@ impl NewInvestor {
 #[derive(Serialize, Debug)]
 pub struct InvestorsControl {
     pub id: i32,
+    pub version: i32,
     pub name: String,
     pub is_company: bool,
 }
@@ -35,23 +43,160 @@ impl std::convert::From<Investor> for InvestorsControl {
     fn from(investors_struct: Investor) -> Self {
         InvestorsControl {
             id: investors_struct.id,
+            version: investors_struct.version,
             name: investors_struct.name,
             is_company: investors_struct.is_company,
         }
     }
 }
 
+#[derive(Serialize, Debug, Default)]
+pub struct InvestorReturnsControl {
+    pub investor_id: i32,
+    pub name: String,
+    pub is_company: bool,
+    pub invested: f64,
+    pub returned: f64,
+    pub roi: f64,
+    pub outstanding: f64,
+}
+
+impl InvestorReturnsControl {
+    fn new(investor_id: i32, name: String, is_company: bool, invested: f64, returned: f64) -> Self {
+        let roi = if invested > 0f64 {
+            (returned - invested) / invested * 100f64
+        } else {
+            0f64
+        };
+        InvestorReturnsControl {
+            investor_id,
+            name,
+            is_company,
+            invested,
+            returned,
+            roi,
+            outstanding: (invested - returned).max(0f64),
+        }
+    }
+
+    /// Totals for companies, individuals and all investors together.
+    pub fn breakdown(returns: &[InvestorReturnsControl]) -> Vec<InvestorReturnsControl> {
+        let total = |name: &str, filter: &dyn Fn(&InvestorReturnsControl) -> bool| {
+            let (invested, returned) = returns.iter().filter(|investor| filter(investor)).fold(
+                (0f64, 0f64),
+                |(invested, returned), investor| {
+                    (invested + investor.invested, returned + investor.returned)
+                },
+            );
+            InvestorReturnsControl::new(0, name.to_string(), false, invested, returned)
+        };
+
+        vec![
+            total("Компанії", &|investor| investor.is_company),
+            total("Приватні особи", &|investor| {
+                !investor.is_company
+            }),
+            total("Усього", &|_| true),
+        ]
+    }
+}
+
 impl InvestorsControl {
     pub async fn get_statistic(
         conn: &DBConnection,
         id_for_lookup: i32,
-    ) -> (i32, Vec<InvestmentsControl>) {
+    ) -> (
+        i32,
+        Vec<InvestmentsControl>,
+        Vec<PayoutsControl>,
+        Vec<InvestorReturnsControl>,
+    ) {
+        let returns = InvestorsControl::get_returns(conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|investor| investor.investor_id == id_for_lookup)
+            .collect();
         (
             id_for_lookup,
             InvestorsControl::get_investments(conn, id_for_lookup).await,
+            InvestorsControl::get_payouts(conn, id_for_lookup).await,
+            returns,
         )
     }
 
+    /// Invested and returned amounts of every investor.
+    pub async fn get_returns(conn: &DBConnection) -> Result<Vec<InvestorReturnsControl>> {
+        use crate::schema::{investments, investors, payouts};
+
+        let (all_investors, invested, returned) = conn
+            .run(
+                move |sql_conn| -> Result<(Vec<Investor>, MoneySums, MoneySums)> {
+                    let all_investors =
+                        investors::table.order(investors::id.asc()).load(sql_conn)?;
+                    let invested = investments::table
+                        .select((
+                            investments::investor_id,
+                            sql::<Nullable<Money>>("sum(invested)"),
+                        ))
+                        .group_by(investments::investor_id)
+                        .load(sql_conn)?;
+                    let returned = payouts::table
+                        .inner_join(investments::table)
+                        .select((
+                            investments::investor_id,
+                            sql::<Nullable<Money>>("sum(amount)"),
+                        ))
+                        .group_by(investments::investor_id)
+                        .load(sql_conn)?;
+                    Ok((all_investors, invested, returned))
+                },
+            )
+            .await?;
+
+        let to_map = |sums: MoneySums| -> HashMap<i32, f64> {
+            sums.into_iter()
+                .map(|(investor, sum)| (investor, sum.map_or(0, |sum| sum.0) as f64 / 100f64))
+                .collect()
+        };
+        let (invested, returned) = (to_map(invested), to_map(returned));
+
+        Ok(all_investors
+            .into_iter()
+            .map(|investor| {
+                InvestorReturnsControl::new(
+                    investor.id,
+                    investor.name,
+                    investor.is_company,
+                    invested.get(&investor.id).copied().unwrap_or_default(),
+                    returned.get(&investor.id).copied().unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
+
+    pub async fn get_payouts(conn: &DBConnection, id_for_lookup: i32) -> Vec<PayoutsControl> {
+        use crate::schema::{investments, payouts};
+
+        let table = conn
+            .run(move |sql_conn| -> Vec<(Payout, Investment)> {
+                payouts::table
+                    .inner_join(investments::table)
+                    .filter(investments::investor_id.eq(id_for_lookup))
+                    .order(payouts::period_start.asc())
+                    .load(sql_conn)
+                    .unwrap()
+            })
+            .await;
+
+        let mut vec = Vec::new();
+        for (payout, _) in table {
+            vec.push(PayoutsControl::make_payouts_control(conn, payout).await);
+        }
+
+        vec
+    }
+
     pub async fn get_investments(
         conn: &DBConnection,
         id_for_lookup: i32,
@@ -105,56 +250,49 @@ impl InvestorsControl {
         .await
     }
 
-    pub async fn add_investor(conn: &DBConnection, investor: NewInvestor) -> Result<()> {
+    /// Deletes the investor with their investments, and rebuilds the
+    /// statistics of the games they invested into.
+    fn remove_investor(sql_conn: &PgConnection, id_for_delete: i32) -> Result<()> {
+        use crate::schema::investments;
         use crate::schema::investors::dsl::*;
 
-        conn.run(move |sql_connection| -> Result<()> {
-            diesel::insert_into(investors)
-                .values(&investor)
-                .get_result::<Investor>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
+        sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            let invested_into: Vec<i32> = investments::table
+                .filter(investments::investor_id.eq(id_for_delete))
+                .select(investments::game_id)
+                .distinct()
+                .load(sql_conn)?;
+            diesel::delete(investors)
+                .filter(&id.eq(id_for_delete))
+                .get_result::<Investor>(sql_conn)
+                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+            for game in invested_into {
+                StatisticsControl::refresh_game(sql_conn, game)?;
+            }
             Ok(())
         })
-        .await
     }
+}
 
-    pub async fn update_investor(
-        conn: &DBConnection,
-        id_for_update: i32,
-        investor: NewInvestor,
-    ) -> Result<()> {
-        use crate::schema::investors::dsl::*;
+#[rocket::async_trait]
+impl Resource for InvestorsControl {
+    type Form = AddInvestor;
+    type New = NewInvestor;
+    type Table = investors::table;
+    type Version = investors::version;
 
-        conn.run(move |sql_connection| -> Result<()> {
-            diesel::update(investors.filter(&id.eq(id_for_update)))
-                .set((name.eq(investor.name), is_company.eq(investor.is_company)))
-                .get_result::<Investor>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
-        })
-        .await
+    const NAME: &'static str = "investors";
+    const TABLE: &'static str = "Інвестори";
+
+    fn parse(form: AddInvestor) -> Result<Self::New, ServerError> {
+        NewInvestor::from(form)
     }
 
-    pub async fn delete_investor(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
-        use crate::schema::investors::dsl::*;
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_investor_by_id(conn, id).await
+    }
 
-        conn.run(move |sql_conn| -> Result<()> {
-            diesel::delete(investors)
-                .filter(&id.eq(id_for_delete))
-                .get_result::<Investor>(sql_conn)
-                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(())
-        })
-        .await
+    fn remove(sql_conn: &PgConnection, id: i32) -> Result<()> {
+        Self::remove_investor(sql_conn, id)
     }
 }
@@ -1,19 +1,34 @@
+use crate::config;
+use crate::controllers::GameStatus;
 use crate::controllers::GamesControl;
+use crate::controllers::Resource;
 use crate::controllers::StaffControl;
+use crate::controllers::{for_each_selected, write_error};
 use crate::errors::ServerError;
 use crate::models::*;
-use crate::requests_handler::AddJob;
-use crate::schema::jobs;
+use crate::requests_handler::{AddJob, AddRaise};
+use crate::schema::{jobs, salary_changes};
 use crate::DBConnection;
 use anyhow::Result;
-use chrono::NaiveDate;
+use chrono::{Local, NaiveDate};
 use diesel::pg::data_types::PgMoney;
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::result::Error as DieselError;
 use serde::Serialize;
+use std::collections::HashMap;
 
-#[derive(Insertable)]
+const DAYS_PER_MONTH: f64 = 365.25 / 12f64;
+
+/// Salary changes of every job, oldest first, keyed by the job id.
+type SalaryChanges = HashMap<i32, Vec<SalaryChange>>;
+/// Jobs with the names of their staff member and game, and their salary
+/// changes, as read for the payroll.
+type PayrollRows = (Vec<(Job, String, String)>, SalaryChanges);
+
+#[derive(Insertable, AsChangeset)]
 #[table_name = "jobs"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct NewJob {
     pub game_id: i32,
     pub staff_id: i32,
@@ -49,9 +64,57 @@ impl NewJob {
         })
     }
 }
+
+#[derive(Insertable)]
+#[table_name = "salary_changes"]
+pub struct NewSalaryChange {
+    pub job_id: i32,
+    pub salary: PgMoney,
+    pub effective_date: NaiveDate,
+}
+
+impl NewSalaryChange {
+    pub fn from(job_id: i32, raise: AddRaise) -> Result<Self, ServerError> {
+        let effective_date = NaiveDate::parse_from_str(&raise.effective_date, "%Y-%m-%d")
+            .map_err(|_| ServerError::InvalidDate)?;
+        if raise.salary <= 0f64 {
+            return Err(ServerError::InvalidValue(vec!["salary".to_string()]));
+        }
+
+        Ok(NewSalaryChange {
+            job_id,
+            salary: PgMoney((raise.salary * 100f64) as i64),
+            effective_date,
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SalaryChangesControl {
+    pub job_id: i32,
+    pub game: String,
+    pub position: String,
+    pub salary: f64,
+    pub effective_date: String,
+    pub raise: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PayrollEntry {
+    pub job_id: i32,
+    pub staff_id: i32,
+    pub staff: String,
+    pub game: String,
+    pub position: String,
+    pub salary: f64,
+    pub days: i64,
+    pub cost: f64,
+}
+
 #[derive(Serialize, Debug)]
 pub struct JobsControl {
     pub id: i32,
+    pub version: i32,
     pub game: String,
     pub game_id: i32,
     pub staff: String,
@@ -60,6 +123,7 @@ pub struct JobsControl {
     pub first_work_day: String,
     pub last_work_day: String,
     pub salary: f64,
+    pub salary_history: Vec<SalaryChangesControl>,
 }
 
 impl JobsControl {
@@ -76,14 +140,18 @@ impl JobsControl {
             .unwrap()
             .name;
 
-        let first_work_day = first_work_day.format("%d-%m-%Y").to_string();
+        let first_work_day = first_work_day.format(&config::formats().date).to_string();
         let last_work_day = if last_work_day.is_some() {
-            last_work_day.unwrap().format("%d-%m-%Y").to_string()
+            last_work_day
+                .unwrap()
+                .format(&config::formats().date)
+                .to_string()
         } else {
             "".to_string()
         };
         JobsControl {
             id: jobs_struct.id,
+            version: jobs_struct.version,
             game: game,
             game_id: jobs_struct.game_id,
             staff: staff,
@@ -92,9 +160,103 @@ impl JobsControl {
             first_work_day,
             last_work_day,
             salary: jobs_struct.salary.0 as f64 / 100f64,
+            salary_history: vec![],
         }
     }
 
+    /// Salary changes of the given jobs, each list ordered by effective date.
+    pub fn load_salary_changes(sql_conn: &PgConnection, job_ids: &[i32]) -> Result<SalaryChanges> {
+        let changes: Vec<SalaryChange> = salary_changes::table
+            .select((
+                salary_changes::job_id,
+                salary_changes::salary,
+                salary_changes::effective_date,
+            ))
+            .filter(salary_changes::job_id.eq_any(job_ids))
+            .order(salary_changes::effective_date.asc())
+            .load(sql_conn)?;
+
+        let mut changes_by_job: SalaryChanges = HashMap::new();
+        for change in changes {
+            changes_by_job
+                .entry(change.job_id)
+                .or_default()
+                .push(change);
+        }
+        Ok(changes_by_job)
+    }
+
+    /// Pairs every job with its salary changes.
+    pub fn with_salary_changes(
+        sql_conn: &PgConnection,
+        jobs_to_pair: Vec<Job>,
+    ) -> Result<Vec<(Job, Vec<SalaryChange>)>> {
+        let job_ids: Vec<i32> = jobs_to_pair.iter().map(|job| job.id).collect();
+        let mut changes = JobsControl::load_salary_changes(sql_conn, &job_ids)?;
+        Ok(jobs_to_pair
+            .into_iter()
+            .map(|job| {
+                let job_changes = changes.remove(&job.id).unwrap_or_default();
+                (job, job_changes)
+            })
+            .collect())
+    }
+
+    /// Salary valid on `date`. The earliest change also covers the days
+    /// before it, and a job without recorded changes keeps its own salary.
+    pub fn salary_at(job: &Job, changes: &[SalaryChange], date: NaiveDate) -> PgMoney {
+        changes
+            .iter()
+            .rev()
+            .find(|change| change.effective_date <= date)
+            .or_else(|| changes.first())
+            .map_or(job.salary, |change| change.salary)
+    }
+
+    fn period_cost(salary: PgMoney, from: NaiveDate, to: NaiveDate) -> f64 {
+        let days = (to - from).num_days() + 1;
+        salary.0 as f64 / 100f64 * days as f64 / DAYS_PER_MONTH
+    }
+
+    /// Salaries are monthly, so the cost of a job is prorated by the days
+    /// worked up to `until` (or the last work day, if it is earlier), each
+    /// day at the salary that was valid on it.
+    pub fn salary_cost(job: &Job, changes: &[SalaryChange], until: NaiveDate) -> f64 {
+        let last_day = match job.last_work_day {
+            Some(last_work_day) if last_work_day < until => last_work_day,
+            _ => until,
+        };
+        if last_day < job.first_work_day {
+            return 0f64;
+        }
+
+        let mut cost = 0f64;
+        let mut from = job.first_work_day;
+        for change in changes
+            .iter()
+            .filter(|change| job.first_work_day < change.effective_date)
+            .filter(|change| change.effective_date <= last_day)
+        {
+            let salary = JobsControl::salary_at(job, changes, from);
+            cost += JobsControl::period_cost(salary, from, change.effective_date.pred());
+            from = change.effective_date;
+        }
+        cost + JobsControl::period_cost(JobsControl::salary_at(job, changes, from), from, last_day)
+    }
+
+    pub fn salary_cost_between(
+        job: &Job,
+        changes: &[SalaryChange],
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> f64 {
+        let before = match from.pred_opt() {
+            Some(day_before) => JobsControl::salary_cost(job, changes, day_before),
+            None => 0f64,
+        };
+        (JobsControl::salary_cost(job, changes, to) - before).max(0f64)
+    }
+
     pub fn change_date_format(&mut self, from: &str, to: &str) -> Result<()> {
         let first_work_day = NaiveDate::parse_from_str(&self.first_work_day, from)?;
         self.first_work_day = first_work_day.format(to).to_string();
@@ -123,75 +285,386 @@ impl JobsControl {
         Ok(jobs_result)
     }
 
+    fn salary_history(
+        game: &str,
+        position: &str,
+        changes: &[SalaryChange],
+    ) -> Vec<SalaryChangesControl> {
+        let mut previous: Option<PgMoney> = None;
+        changes
+            .iter()
+            .map(|change| {
+                let raise = previous.map_or(0, |previous| change.salary.0 - previous.0);
+                previous = Some(change.salary);
+                SalaryChangesControl {
+                    job_id: change.job_id,
+                    game: game.to_string(),
+                    position: position.to_string(),
+                    salary: change.salary.0 as f64 / 100f64,
+                    effective_date: change
+                        .effective_date
+                        .format(&config::formats().date)
+                        .to_string(),
+                    raise: raise as f64 / 100f64,
+                }
+            })
+            .collect()
+    }
+
     pub async fn get_job_by_id(conn: &DBConnection, id_for_lookup: i32) -> Result<JobsControl> {
         use crate::schema::jobs::dsl::*;
 
-        let job = conn
-            .run(move |sql_conn| -> Result<Job> {
+        let (job, changes) = conn
+            .run(move |sql_conn| -> Result<(Job, Vec<SalaryChange>)> {
                 let result: Job = jobs
                     .filter(id.eq(id_for_lookup))
                     .first(sql_conn)
                     .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-                Ok(result)
+                let changes = JobsControl::load_salary_changes(sql_conn, &[result.id])?
+                    .remove(&result.id)
+                    .unwrap_or_default();
+                Ok((result, changes))
             })
             .await?;
 
-        Ok(JobsControl::make_jobs_control(conn, job).await)
+        let mut job = JobsControl::make_jobs_control(conn, job).await;
+        job.salary_history = JobsControl::salary_history(&job.game, &job.position, &changes);
+        Ok(job)
     }
 
-    pub async fn add_job(conn: &DBConnection, job: NewJob) -> Result<()> {
-        use crate::schema::jobs::dsl::*;
+    /// Salary changes over all jobs of a staff member, oldest first.
+    pub async fn get_salary_timeline(
+        conn: &DBConnection,
+        staff_id_for_lookup: i32,
+    ) -> Result<Vec<SalaryChangesControl>> {
+        use crate::schema::games;
 
+        let (staff_jobs, changes) = conn
+            .run(
+                move |sql_conn| -> Result<(Vec<(Job, String)>, SalaryChanges)> {
+                    let staff_jobs: Vec<(Job, String)> = jobs::table
+                        .inner_join(games::table)
+                        .select((jobs::all_columns, games::name))
+                        .filter(jobs::staff_id.eq(staff_id_for_lookup))
+                        .load(sql_conn)?;
+                    let job_ids: Vec<i32> = staff_jobs.iter().map(|(job, _)| job.id).collect();
+                    let changes = JobsControl::load_salary_changes(sql_conn, &job_ids)?;
+                    Ok((staff_jobs, changes))
+                },
+            )
+            .await?;
+
+        let mut timeline: Vec<(NaiveDate, SalaryChangesControl)> = vec![];
+        for (job, game) in staff_jobs {
+            let job_changes = changes.get(&job.id).map(Vec::as_slice).unwrap_or(&[]);
+            let history = JobsControl::salary_history(&game, &job.position, job_changes);
+            timeline.extend(
+                job_changes
+                    .iter()
+                    .map(|change| change.effective_date)
+                    .zip(history),
+            );
+        }
+        timeline.sort_by_key(|(effective_date, _)| *effective_date);
+
+        Ok(timeline.into_iter().map(|(_, change)| change).collect())
+    }
+
+    /// Every job worked in the period with the salary valid at its end and
+    /// the cost of the days worked, plus the total cost.
+    pub async fn get_payroll(
+        conn: &DBConnection,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<(Vec<PayrollEntry>, f64)> {
+        use crate::schema::{games, staff};
+
+        let (period_jobs, changes) = conn
+            .run(move |sql_conn| -> Result<PayrollRows> {
+                let period_jobs: Vec<(Job, String, String)> = jobs::table
+                    .inner_join(games::table)
+                    .inner_join(staff::table)
+                    .select((jobs::all_columns, staff::name, games::name))
+                    .filter(jobs::first_work_day.le(to))
+                    .filter(
+                        jobs::last_work_day
+                            .is_null()
+                            .or(jobs::last_work_day.ge(from)),
+                    )
+                    .order((staff::name.asc(), jobs::id.asc()))
+                    .load(sql_conn)?;
+                let job_ids: Vec<i32> = period_jobs.iter().map(|(job, _, _)| job.id).collect();
+                let changes = JobsControl::load_salary_changes(sql_conn, &job_ids)?;
+                Ok((period_jobs, changes))
+            })
+            .await?;
+
+        let mut total = 0f64;
+        let payroll = period_jobs
+            .into_iter()
+            .map(|(job, staff, game)| {
+                let job_changes = changes.get(&job.id).map(Vec::as_slice).unwrap_or(&[]);
+                let first_day = job.first_work_day.max(from);
+                let last_day = job
+                    .last_work_day
+                    .map_or(to, |last_work_day| last_work_day.min(to));
+                let cost = JobsControl::salary_cost_between(&job, job_changes, from, to);
+                total += cost;
+                PayrollEntry {
+                    job_id: job.id,
+                    staff_id: job.staff_id,
+                    staff,
+                    game,
+                    position: job.position.clone(),
+                    salary: JobsControl::salary_at(&job, job_changes, last_day).0 as f64 / 100f64,
+                    days: (last_day - first_day).num_days() + 1,
+                    cost,
+                }
+            })
+            .collect();
+
+        Ok((payroll, total))
+    }
+
+    /// Keeps `jobs.salary` equal to the latest salary change of the job.
+    fn sync_salary(sql_conn: &PgConnection, job_id_for_update: i32) -> Result<()> {
+        let latest: PgMoney = salary_changes::table
+            .select(salary_changes::salary)
+            .filter(salary_changes::job_id.eq(job_id_for_update))
+            .order(salary_changes::effective_date.desc())
+            .first(sql_conn)?;
+        diesel::update(jobs::table.filter(jobs::id.eq(job_id_for_update)))
+            .set((jobs::salary.eq(latest), jobs::version.eq(jobs::version + 1)))
+            .execute(sql_conn)?;
+        Ok(())
+    }
+
+    /// Records the salary valid from the change's effective date, replacing
+    /// a change already recorded for the same day.
+    fn record_salary_change(sql_conn: &PgConnection, change: &NewSalaryChange) -> Result<()> {
+        diesel::insert_into(salary_changes::table)
+            .values(change)
+            .on_conflict((salary_changes::job_id, salary_changes::effective_date))
+            .do_update()
+            .set(salary_changes::salary.eq(excluded(salary_changes::salary)))
+            .execute(sql_conn)?;
+        JobsControl::sync_salary(sql_conn, change.job_id)
+    }
+
+    /// Inserts the job together with its starting salary.
+    pub fn insert_job(sql_conn: &PgConnection, job: &NewJob) -> Result<Job> {
+        let inserted = diesel::insert_into(jobs::table)
+            .values(job)
+            .get_result::<Job>(sql_conn)
+            .map_err(write_error)?;
+        JobsControl::record_salary_change(
+            sql_conn,
+            &NewSalaryChange {
+                job_id: inserted.id,
+                salary: inserted.salary,
+                effective_date: inserted.first_work_day,
+            },
+        )?;
+        Ok(inserted)
+    }
+
+    pub async fn add_job(conn: &DBConnection, job: NewJob) -> Result<()> {
         conn.run(move |sql_connection| -> Result<()> {
-            diesel::insert_into(jobs)
-                .values(&job)
-                .get_result::<Job>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                JobsControl::insert_job(sql_connection, &job)?;
+                Ok(())
+            })
         })
         .await
     }
 
-    pub async fn update_job(conn: &DBConnection, id_for_update: i32, job: NewJob) -> Result<()> {
+    /// A changed salary is not rewritten in place but recorded as a change
+    /// effective today, kept within the job's work days.
+    pub async fn update_job(
+        conn: &DBConnection,
+        id_for_update: i32,
+        version_for_update: i32,
+        job: NewJob,
+    ) -> Result<()> {
         use crate::schema::jobs::dsl::*;
 
         conn.run(move |sql_connection| -> Result<()> {
-            diesel::update(jobs.filter(&id.eq(id_for_update)))
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let previous: Job = jobs
+                    .filter(id.eq(id_for_update))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+
+                diesel::update(
+                    jobs.filter(&id.eq(id_for_update))
+                        .filter(version.eq(version_for_update)),
+                )
                 .set((
+                    version.eq(version + 1),
                     game_id.eq(job.game_id),
                     staff_id.eq(job.staff_id),
                     position.eq(job.position),
                     first_work_day.eq(job.first_work_day),
                     last_work_day.eq(job.last_work_day),
-                    salary.eq(job.salary),
                 ))
                 .get_result::<Job>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+                .map_err(write_error)?;
+
+                if job.salary == previous.salary {
+                    return Ok(());
+                }
+                let mut effective_date = Local::today().naive_local();
+                if let Some(last_day) = job.last_work_day {
+                    effective_date = effective_date.min(last_day);
+                }
+                JobsControl::record_salary_change(
+                    sql_connection,
+                    &NewSalaryChange {
+                        job_id: id_for_update,
+                        salary: job.salary,
+                        effective_date: effective_date.max(job.first_work_day),
+                    },
+                )
+            })
+        })
+        .await
+    }
+
+    /// Adds a raise (or a cut) effective from a date within the job's work days.
+    pub async fn raise_salary(conn: &DBConnection, change: NewSalaryChange) -> Result<()> {
+        use crate::schema::jobs::dsl::*;
+
+        conn.run(move |sql_connection| -> Result<()> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let job: Job = jobs
+                    .filter(id.eq(change.job_id))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                let after_last_day = job
+                    .last_work_day
+                    .is_some_and(|last_day| change.effective_date > last_day);
+                if change.effective_date < job.first_work_day || after_last_day {
+                    return Err(ServerError::InvalidDate.into());
+                }
+
+                JobsControl::record_salary_change(sql_connection, &change)
+            })
         })
         .await
     }
 
-    pub async fn delete_job(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
+    /// Closes the open jobs that can no longer go on: jobs of staff whose
+    /// employment ended before `today` end on the last day of employment, jobs
+    /// on sunset games end on `today`. Returns the number of closed jobs.
+    pub fn close_past_due(sql_conn: &PgConnection, today: NaiveDate) -> Result<usize> {
         use crate::schema::jobs::dsl::*;
+        use crate::schema::{games, staff};
 
-        conn.run(move |sql_conn| -> Result<()> {
-            diesel::delete(jobs)
-                .filter(&id.eq(id_for_delete))
-                .get_result::<Job>(sql_conn)
-                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(())
+        sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            let open: Vec<(i32, NaiveDate, Option<NaiveDate>, String)> = jobs
+                .inner_join(staff::table)
+                .inner_join(games::table)
+                .filter(last_work_day.is_null())
+                .select((id, first_work_day, staff::employment_end, games::status))
+                .load(sql_conn)?;
+
+            let mut closed = 0;
+            for (job_id, first_day, employment_end, status) in open {
+                let last_day = match employment_end {
+                    Some(end) if end < today => end,
+                    _ if status == GameStatus::Sunset.as_str() => today,
+                    _ => continue,
+                };
+                if last_day < first_day {
+                    continue;
+                }
+
+                closed +=
+                    diesel::update(jobs.filter(id.eq(job_id)).filter(last_work_day.is_null()))
+                        .set((last_work_day.eq(Some(last_day)), version.eq(version + 1)))
+                        .execute(sql_conn)?;
+            }
+            Ok(closed)
+        })
+    }
+
+    /// Ends the selected jobs on `last_day` and returns the rows that failed.
+    /// Jobs that already end by that day are left as they are.
+    pub async fn close_selected(
+        conn: &DBConnection,
+        ids: Vec<i32>,
+        last_day: NaiveDate,
+    ) -> Result<Vec<String>> {
+        use crate::schema::jobs::dsl::*;
+
+        conn.run(move |sql_conn| {
+            for_each_selected(sql_conn, &ids, |id_for_update| {
+                let job: Job = jobs
+                    .filter(id.eq(id_for_update))
+                    .for_update()
+                    .first(sql_conn)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                if last_day < job.first_work_day {
+                    return Err(ServerError::InvalidDate.into());
+                }
+                if job.last_work_day.is_some_and(|current| current <= last_day) {
+                    return Ok(());
+                }
+
+                diesel::update(jobs.filter(id.eq(id_for_update)))
+                    .set((last_work_day.eq(Some(last_day)), version.eq(version + 1)))
+                    .execute(sql_conn)?;
+                Ok(())
+            })
         })
         .await
     }
 }
+
+#[rocket::async_trait]
+impl Resource for JobsControl {
+    type Form = AddJob;
+    type New = NewJob;
+    type Table = jobs::table;
+    type Version = jobs::version;
+
+    const NAME: &'static str = "jobs";
+    const TABLE: &'static str = "Позиції працівників";
+
+    fn parse(form: AddJob) -> Result<Self::New, ServerError> {
+        NewJob::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_job_by_id(conn, id).await
+    }
+
+    async fn add(conn: &DBConnection, new: Self::New) -> Result<()> {
+        Self::add_job(conn, new).await
+    }
+
+    async fn update(conn: &DBConnection, id: i32, version: i32, new: Self::New) -> Result<()> {
+        Self::update_job(conn, id, version, new).await
+    }
+
+    /// Game and employee choices.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let games = GamesControl::get_games(conn).await.unwrap();
+        let games_id = games.iter().map(|game| game.id.to_string()).collect();
+
+        let games_name = games.into_iter().map(|game| game.name).collect();
+
+        let staff = StaffControl::get_staff(conn).await.unwrap();
+        let staff_id = staff.iter().map(|member| member.id.to_string()).collect();
+
+        let staff_name = staff.into_iter().map(|member| member.name).collect();
+
+        vec![games_id, games_name, staff_id, staff_name]
+    }
+
+    fn for_edit(&mut self) -> Result<()> {
+        self.change_date_format(&config::formats().date, "%Y-%m-%d")
+    }
+}
@@ -0,0 +1,102 @@
+use crate::controllers::{LoyaltyTiersControl, NET_DONATION_AMOUNT};
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Duration, Local, NaiveDateTime};
+use diesel::dsl::sql;
+use diesel::pg::data_types::PgMoney;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Money};
+use serde::Serialize;
+
+const LEADERBOARD_SIZE: i64 = 10;
+const ROLLING_DAYS: i64 = 30;
+
+#[derive(Serialize, Debug)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub user_id: i32,
+    pub user: String,
+    pub amount: f64,
+    pub donations_count: i64,
+    pub tier: String,
+    pub badge: String,
+}
+
+pub struct LeaderboardsControl;
+
+impl LeaderboardsControl {
+    /// Top donators by the summed donation amount net of refunds, optionally
+    /// limited to one game and to donations made after `since`.
+    pub async fn get_leaderboard(
+        conn: &DBConnection,
+        game_id_for_lookup: Option<i32>,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        use crate::schema::{donations, users};
+
+        let rows = conn
+            .run(
+                move |sql_conn| -> Result<Vec<(i32, String, PgMoney, i64)>> {
+                    let mut query = donations::table
+                        .inner_join(users::table)
+                        .select((
+                            users::id,
+                            users::nickname,
+                            sql::<Money>(&format!("sum{}", NET_DONATION_AMOUNT)),
+                            sql::<BigInt>("count(donations.id)"),
+                        ))
+                        .group_by(users::id)
+                        .order(sql::<Money>(&format!("sum{} DESC", NET_DONATION_AMOUNT)))
+                        .limit(LEADERBOARD_SIZE)
+                        .into_boxed();
+                    if let Some(game_id_for_lookup) = game_id_for_lookup {
+                        query = query.filter(donations::game_id.eq(game_id_for_lookup));
+                    }
+                    if let Some(since) = since {
+                        query = query.filter(donations::donation_time.ge(since));
+                    }
+                    Ok(query.load(sql_conn)?)
+                },
+            )
+            .await?;
+
+        let user_tiers = LoyaltyTiersControl::get_user_tiers(conn).await?;
+        Ok(rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, (user_id, user, amount, donations_count))| {
+                let (tier, badge) = user_tiers
+                    .get(&user_id)
+                    .map(|tier| (tier.name.clone(), tier.badge.clone()))
+                    .unwrap_or_default();
+                LeaderboardEntry {
+                    rank: index + 1,
+                    user_id,
+                    user,
+                    amount: amount.0 as f64 / 100f64,
+                    donations_count,
+                    tier,
+                    badge,
+                }
+            })
+            .collect())
+    }
+
+    /// All-time, rolling 30 days and, when a game is given, per game boards.
+    pub async fn get_leaderboards(
+        conn: &DBConnection,
+        game_id_for_lookup: Option<i32>,
+    ) -> Result<Vec<Vec<LeaderboardEntry>>> {
+        let since = Local::now().naive_local() - Duration::days(ROLLING_DAYS);
+
+        let mut leaderboards = vec![
+            LeaderboardsControl::get_leaderboard(conn, None, None).await?,
+            LeaderboardsControl::get_leaderboard(conn, None, Some(since)).await?,
+        ];
+        if game_id_for_lookup.is_some() {
+            leaderboards
+                .push(LeaderboardsControl::get_leaderboard(conn, game_id_for_lookup, None).await?);
+        }
+        Ok(leaderboards)
+    }
+}
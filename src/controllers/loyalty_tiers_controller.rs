@@ -0,0 +1,292 @@
+use crate::config;
+use crate::controllers::Resource;
+use crate::controllers::NET_DONATION_AMOUNT;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddTier;
+use crate::schema::{loyalty_tiers, user_tier_history};
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Local, NaiveDateTime};
+use diesel::dsl::sql;
+use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{Money, Nullable};
+use serde::Serialize;
+use std::collections::HashMap;
+
+const NO_TIER: &str = "Без рівня";
+
+/// The columns of `user_tier_history` loaded as a `UserTierChange`.
+const TIER_CHANGE_COLUMNS: (
+    user_tier_history::user_id,
+    user_tier_history::tier_id,
+    user_tier_history::tier_name,
+    user_tier_history::total_donated,
+    user_tier_history::reached_at,
+) = (
+    user_tier_history::user_id,
+    user_tier_history::tier_id,
+    user_tier_history::tier_name,
+    user_tier_history::total_donated,
+    user_tier_history::reached_at,
+);
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "loyalty_tiers"]
+pub struct NewTier {
+    pub name: String,
+    pub min_amount: PgMoney,
+    pub badge: String,
+}
+
+impl NewTier {
+    pub fn from(tier: AddTier) -> Result<Self, ServerError> {
+        let name = tier.name.trim().to_string();
+        if name.is_empty() {
+            return Err(ServerError::NullValues(vec!["name".to_string()]));
+        }
+        if tier.min_amount < 0f64 {
+            return Err(ServerError::InvalidValue(vec!["min amount".to_string()]));
+        }
+
+        Ok(NewTier {
+            name,
+            min_amount: PgMoney((tier.min_amount * 100f64) as i64),
+            badge: tier.badge.trim().to_string(),
+        })
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "user_tier_history"]
+pub struct NewUserTierChange {
+    pub user_id: i32,
+    pub tier_id: Option<i32>,
+    pub tier_name: String,
+    pub total_donated: PgMoney,
+    pub reached_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LoyaltyTiersControl {
+    pub id: i32,
+    pub version: i32,
+    pub name: String,
+    pub min_amount: f64,
+    pub badge: String,
+}
+
+impl std::convert::From<LoyaltyTier> for LoyaltyTiersControl {
+    fn from(tiers_struct: LoyaltyTier) -> Self {
+        LoyaltyTiersControl {
+            id: tiers_struct.id,
+            version: tiers_struct.version,
+            name: tiers_struct.name,
+            min_amount: tiers_struct.min_amount.0 as f64 / 100f64,
+            badge: tiers_struct.badge,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserTierChangeControl {
+    pub tier: String,
+    pub total_donated: f64,
+    pub reached_at: String,
+}
+
+impl std::convert::From<UserTierChange> for UserTierChangeControl {
+    fn from(change: UserTierChange) -> Self {
+        UserTierChangeControl {
+            tier: change.tier_name,
+            total_donated: change.total_donated.0 as f64 / 100f64,
+            reached_at: change
+                .reached_at
+                .format(&config::formats().date_time)
+                .to_string(),
+        }
+    }
+}
+
+impl LoyaltyTiersControl {
+    /// Highest tier whose threshold is covered by `total`.
+    fn tier_for(sql_conn: &PgConnection, total: PgMoney) -> Result<Option<LoyaltyTier>> {
+        use crate::schema::loyalty_tiers::dsl::*;
+
+        Ok(loyalty_tiers
+            .filter(min_amount.le(total))
+            .order(min_amount.desc())
+            .first(sql_conn)
+            .optional()?)
+    }
+
+    /// Recomputes the user's tier from the cumulative donation amount net of
+    /// refunds and records a history entry when it differs from the last one.
+    pub fn refresh_user_tier(sql_conn: &PgConnection, user_id_for_lookup: i32) -> Result<()> {
+        use crate::schema::donations;
+
+        let total: Option<PgMoney> = donations::table
+            .select(sql::<Nullable<Money>>(&format!(
+                "sum{}",
+                NET_DONATION_AMOUNT
+            )))
+            .filter(donations::user_id.eq(user_id_for_lookup))
+            .first(sql_conn)?;
+        let total = total.unwrap_or(PgMoney(0));
+
+        let tier = LoyaltyTiersControl::tier_for(sql_conn, total)?;
+        let last: Option<UserTierChange> = user_tier_history::table
+            .select(TIER_CHANGE_COLUMNS)
+            .filter(user_tier_history::user_id.eq(user_id_for_lookup))
+            .order(user_tier_history::id.desc())
+            .first(sql_conn)
+            .optional()?;
+
+        let (new_tier_id, new_tier_name) = match tier {
+            Some(tier) => (Some(tier.id), tier.name),
+            None => (None, NO_TIER.to_string()),
+        };
+        let unchanged = match last {
+            Some(last) => last.tier_id == new_tier_id && last.tier_name == new_tier_name,
+            None => new_tier_id.is_none(),
+        };
+        if unchanged {
+            return Ok(());
+        }
+
+        diesel::insert_into(user_tier_history::table)
+            .values(&NewUserTierChange {
+                user_id: user_id_for_lookup,
+                tier_id: new_tier_id,
+                tier_name: new_tier_name,
+                total_donated: total,
+                reached_at: Local::now().naive_local(),
+            })
+            .execute(sql_conn)?;
+        Ok(())
+    }
+
+    /// Re-evaluates every user, used after the tier thresholds change.
+    pub fn refresh_all(sql_conn: &PgConnection) -> Result<()> {
+        use crate::schema::users;
+
+        let user_ids: Vec<i32> = users::table.select(users::id).load(sql_conn)?;
+        for user_id in user_ids {
+            LoyaltyTiersControl::refresh_user_tier(sql_conn, user_id)?;
+        }
+        Ok(())
+    }
+
+    /// Current tier of every user that has one, keyed by user id.
+    pub async fn get_user_tiers(conn: &DBConnection) -> Result<HashMap<i32, LoyaltyTiersControl>> {
+        let (all_tiers, latest) = conn
+            .run(
+                move |sql_conn| -> Result<(Vec<LoyaltyTier>, Vec<UserTierChange>)> {
+                    let all_tiers = loyalty_tiers::table.load(sql_conn)?;
+                    let latest = user_tier_history::table
+                        .select(TIER_CHANGE_COLUMNS)
+                        .distinct_on(user_tier_history::user_id)
+                        .order((user_tier_history::user_id, user_tier_history::id.desc()))
+                        .load(sql_conn)?;
+                    Ok((all_tiers, latest))
+                },
+            )
+            .await?;
+
+        let tiers_by_id: HashMap<i32, LoyaltyTier> =
+            all_tiers.into_iter().map(|tier| (tier.id, tier)).collect();
+        let mut result = HashMap::new();
+        for change in latest {
+            if let Some(tier) = change.tier_id.and_then(|tier_id| tiers_by_id.get(&tier_id)) {
+                result.insert(
+                    change.user_id,
+                    LoyaltyTiersControl {
+                        id: tier.id,
+                        version: tier.version,
+                        name: tier.name.clone(),
+                        min_amount: tier.min_amount.0 as f64 / 100f64,
+                        badge: tier.badge.clone(),
+                    },
+                );
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub async fn get_tier_history(
+        conn: &DBConnection,
+        user_id_for_lookup: i32,
+    ) -> Result<Vec<UserTierChangeControl>> {
+        let history = conn
+            .run(move |sql_conn| -> Result<Vec<UserTierChange>> {
+                Ok(user_tier_history::table
+                    .select(TIER_CHANGE_COLUMNS)
+                    .filter(user_tier_history::user_id.eq(user_id_for_lookup))
+                    .order(user_tier_history::id.asc())
+                    .load(sql_conn)?)
+            })
+            .await?;
+
+        Ok(history
+            .into_iter()
+            .map(UserTierChangeControl::from)
+            .collect())
+    }
+
+    pub async fn get_tiers(conn: &DBConnection) -> Result<Vec<LoyaltyTiersControl>> {
+        use crate::schema::loyalty_tiers::dsl::*;
+
+        let results = conn
+            .run(move |sql_conn| -> Result<Vec<LoyaltyTier>> {
+                Ok(loyalty_tiers
+                    .order(min_amount.asc())
+                    .load::<LoyaltyTier>(sql_conn)?)
+            })
+            .await?;
+
+        Ok(results.into_iter().map(LoyaltyTiersControl::from).collect())
+    }
+
+    pub async fn get_tier_by_id(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+    ) -> Result<LoyaltyTiersControl> {
+        use crate::schema::loyalty_tiers::dsl::*;
+
+        conn.run(move |sql_conn| -> Result<LoyaltyTiersControl> {
+            let result: LoyaltyTier = loyalty_tiers
+                .filter(id.eq(id_for_lookup))
+                .first(sql_conn)
+                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+            Ok(LoyaltyTiersControl::from(result))
+        })
+        .await
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for LoyaltyTiersControl {
+    type Form = AddTier;
+    type New = NewTier;
+    type Table = loyalty_tiers::table;
+    type Version = loyalty_tiers::version;
+
+    const NAME: &'static str = "tiers";
+    const TABLE: &'static str = "Рівні лояльності";
+
+    fn parse(form: AddTier) -> Result<Self::New, ServerError> {
+        NewTier::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_tier_by_id(conn, id).await
+    }
+
+    /// Every user's tier depends on the thresholds.
+    fn refresh(sql_conn: &PgConnection) -> Result<()> {
+        Self::refresh_all(sql_conn)
+    }
+}
@@ -1,17 +1,89 @@
+use anyhow::Result;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::Connection;
+
+use crate::errors::ServerError;
+
+mod contracts_controller;
+mod departments_controller;
+mod donation_flags_controller;
+mod donation_refunds_controller;
 mod donations_controller;
 mod games_controller;
+mod genres_controller;
 mod investments_controller;
 mod investors_controller;
 mod jobs_controller;
+mod leaderboards_controller;
+mod loyalty_tiers_controller;
+mod payouts_controller;
+mod platforms_controller;
 mod publishers_controller;
+mod releases_controller;
+mod resource;
+mod revenue_controller;
+mod sales_controller;
+mod scheduled_runs_controller;
 mod staff_controller;
+mod statistics_controller;
+mod subscriptions_controller;
+mod teams_controller;
 mod users_controller;
 
+pub use contracts_controller::*;
+pub use departments_controller::*;
+pub use donation_flags_controller::*;
+pub use donation_refunds_controller::*;
 pub use donations_controller::*;
 pub use games_controller::*;
+pub use genres_controller::*;
 pub use investments_controller::*;
 pub use investors_controller::*;
 pub use jobs_controller::*;
+pub use leaderboards_controller::*;
+pub use loyalty_tiers_controller::*;
+pub use payouts_controller::*;
+pub use platforms_controller::*;
 pub use publishers_controller::*;
+pub use releases_controller::*;
+pub use resource::*;
+pub use revenue_controller::*;
+pub use sales_controller::*;
+pub use scheduled_runs_controller::*;
 pub use staff_controller::*;
+pub use statistics_controller::*;
+pub use subscriptions_controller::*;
+pub use teams_controller::*;
 pub use users_controller::*;
+
+/// Runs `action` for every selected row in one transaction. Each row gets its
+/// own savepoint, so a failed row is rolled back alone and reported as
+/// "#id: error" while the changes to the other rows are kept.
+pub fn for_each_selected<F>(sql_conn: &PgConnection, ids: &[i32], action: F) -> Result<Vec<String>>
+where
+    F: Fn(i32) -> Result<()>,
+{
+    sql_conn.transaction::<_, anyhow::Error, _>(|| {
+        let mut failures = vec![];
+        for id in ids {
+            if let Err(err) = sql_conn.transaction::<_, anyhow::Error, _>(|| action(*id)) {
+                failures.push(format!("#{}: {}", id, err));
+            }
+        }
+        Ok(failures)
+    })
+}
+
+/// Maps a failed insert or update to the error shown on the form. Database
+/// errors carry the constraint message, and an update that found no row means
+/// the record's version changed after the form was opened.
+pub fn write_error(err: DieselError) -> ServerError {
+    match err {
+        DieselError::DatabaseError(_, info) => {
+            ServerError::InvalidForeignKey(info.message().to_string())
+        }
+        DieselError::NotFound => ServerError::EditConflict,
+        _ => panic!("PREKOL"),
+    }
+}
@@ -0,0 +1,208 @@
+use crate::config;
+use crate::controllers::InvestmentsControl;
+use crate::controllers::RevenueControl;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddPayoutPeriod;
+use crate::schema::payouts;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use diesel::pg::data_types::PgMoney;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use serde::Serialize;
+
+#[derive(Insertable)]
+#[table_name = "payouts"]
+pub struct NewPayout {
+    pub investment_id: i32,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub net_income: PgMoney,
+    pub amount: PgMoney,
+    pub paid_at: NaiveDateTime,
+}
+
+pub struct PayoutPeriod {
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+}
+
+impl PayoutPeriod {
+    pub fn from(period: AddPayoutPeriod) -> Result<Self, ServerError> {
+        let period_start = NaiveDate::parse_from_str(&period.period_start, "%Y-%m-%d");
+        let period_end = NaiveDate::parse_from_str(&period.period_end, "%Y-%m-%d");
+        if period_start.is_err() || period_end.is_err() {
+            return Err(ServerError::InvalidDate);
+        }
+
+        let (period_start, period_end) = (period_start.unwrap(), period_end.unwrap());
+        if period_end < period_start || period_end > Local::today().naive_local() {
+            return Err(ServerError::InvalidDate);
+        }
+
+        Ok(PayoutPeriod {
+            period_start,
+            period_end,
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PayoutsControl {
+    pub id: i32,
+    pub investment_id: i32,
+    pub investor: String,
+    pub investor_id: i32,
+    pub game: String,
+    pub game_id: i32,
+    pub share: i16,
+    pub period_start: String,
+    pub period_end: String,
+    pub net_income: f64,
+    pub amount: f64,
+    pub paid_at: String,
+}
+
+impl PayoutsControl {
+    pub async fn make_payouts_control(conn: &DBConnection, payouts_struct: Payout) -> Self {
+        let investment =
+            InvestmentsControl::get_investment_by_id(conn, payouts_struct.investment_id)
+                .await
+                .unwrap();
+
+        PayoutsControl {
+            id: payouts_struct.id,
+            investment_id: payouts_struct.investment_id,
+            investor: investment.investor,
+            investor_id: investment.investor_id,
+            game: investment.game,
+            game_id: investment.game_id,
+            share: investment.share,
+            period_start: payouts_struct
+                .period_start
+                .format(&config::formats().date)
+                .to_string(),
+            period_end: payouts_struct
+                .period_end
+                .format(&config::formats().date)
+                .to_string(),
+            net_income: payouts_struct.net_income.0 as f64 / 100f64,
+            amount: payouts_struct.amount.0 as f64 / 100f64,
+            paid_at: payouts_struct
+                .paid_at
+                .format(&config::formats().date_time)
+                .to_string(),
+        }
+    }
+
+    pub async fn get_payouts(conn: &DBConnection) -> Result<Vec<PayoutsControl>> {
+        use crate::schema::payouts::dsl::*;
+
+        let results = conn
+            .run(move |sql_conn| -> Result<Vec<Payout>> {
+                Ok(payouts.order(id.asc()).load::<Payout>(sql_conn)?)
+            })
+            .await?;
+
+        let mut payouts_result: Vec<PayoutsControl> = vec![];
+        for payout in results {
+            payouts_result.push(PayoutsControl::make_payouts_control(conn, payout).await);
+        }
+
+        Ok(payouts_result)
+    }
+
+    /// Splits the game's net income over the period between its investors by
+    /// their share and records one payout per investment. A period can be paid
+    /// out only once, so overlapping periods are rejected.
+    pub async fn distribute(
+        conn: &DBConnection,
+        game_id_for_lookup: i32,
+        period: PayoutPeriod,
+    ) -> Result<usize> {
+        use crate::schema::investments;
+
+        let net_income = RevenueControl::get_net_income(
+            conn,
+            game_id_for_lookup,
+            period.period_start,
+            period.period_end,
+        )
+        .await?;
+        if net_income <= 0f64 {
+            return Err(ServerError::NoNetIncome.into());
+        }
+
+        conn.run(move |sql_connection| -> Result<usize> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let game_investments: Vec<Investment> = investments::table
+                    .filter(investments::game_id.eq(game_id_for_lookup))
+                    .for_update()
+                    .load(sql_connection)?;
+                let investment_ids: Vec<i32> = game_investments
+                    .iter()
+                    .map(|investment| investment.id)
+                    .collect();
+
+                let already_paid: i64 = payouts::table
+                    .filter(payouts::investment_id.eq_any(investment_ids))
+                    .filter(payouts::period_start.le(period.period_end))
+                    .filter(payouts::period_end.ge(period.period_start))
+                    .count()
+                    .get_result(sql_connection)?;
+                if already_paid > 0 {
+                    return Err(ServerError::PeriodAlreadyPaid(
+                        period
+                            .period_start
+                            .format(&config::formats().date)
+                            .to_string(),
+                        period
+                            .period_end
+                            .format(&config::formats().date)
+                            .to_string(),
+                    )
+                    .into());
+                }
+
+                let paid_at = Local::now().naive_local();
+                let new_payouts: Vec<NewPayout> = game_investments
+                    .iter()
+                    .map(|investment| NewPayout {
+                        investment_id: investment.id,
+                        period_start: period.period_start,
+                        period_end: period.period_end,
+                        net_income: PgMoney((net_income * 100f64) as i64),
+                        amount: PgMoney((net_income * investment.share as f64) as i64),
+                        paid_at,
+                    })
+                    .collect();
+
+                diesel::insert_into(payouts::table)
+                    .values(&new_payouts)
+                    .execute(sql_connection)
+                    .map_err(|err| match err {
+                        DieselError::DatabaseError(_, info) => {
+                            ServerError::InvalidForeignKey(info.message().to_string()).into()
+                        }
+                        err => anyhow::Error::from(err),
+                    })
+            })
+        })
+        .await
+    }
+
+    pub async fn delete_payout(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
+        use crate::schema::payouts::dsl::*;
+
+        conn.run(move |sql_conn| -> Result<()> {
+            diesel::delete(payouts)
+                .filter(&id.eq(id_for_delete))
+                .get_result::<Payout>(sql_conn)
+                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+            Ok(())
+        })
+        .await
+    }
+}
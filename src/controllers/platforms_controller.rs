@@ -0,0 +1,119 @@
+use crate::cache;
+use crate::controllers::Resource;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddPlatform;
+use crate::schema::platforms;
+use crate::DBConnection;
+use anyhow::Result;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "platforms"]
+pub struct NewPlatform {
+    pub name: String,
+}
+
+impl NewPlatform {
+    pub fn from(platform: AddPlatform) -> Result<Self, ServerError> {
+        let name = platform.name.trim().to_string();
+        if name.is_empty() {
+            return Err(ServerError::NullValues(vec!["name".to_string()]));
+        }
+
+        Ok(NewPlatform { name })
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PlatformsControl {
+    pub id: i32,
+    pub version: i32,
+    pub name: String,
+    pub releases: i64,
+}
+
+impl PlatformsControl {
+    pub async fn get_platforms(conn: &DBConnection) -> Result<Vec<PlatformsControl>> {
+        use crate::schema::game_releases;
+
+        cache::cached("platforms".to_string(), async {
+            let (all_platforms, released_on) = conn
+                .run(move |sql_conn| -> Result<(Vec<Platform>, Vec<i32>)> {
+                    let all_platforms = platforms::table
+                        .order(platforms::name.asc())
+                        .load(sql_conn)?;
+                    let released_on = game_releases::table
+                        .select(game_releases::platform_id)
+                        .load(sql_conn)?;
+                    Ok((all_platforms, released_on))
+                })
+                .await?;
+
+            let mut releases: HashMap<i32, i64> = HashMap::new();
+            for platform_id in released_on {
+                *releases.entry(platform_id).or_default() += 1;
+            }
+
+            Ok((
+                all_platforms
+                    .into_iter()
+                    .map(|platform| PlatformsControl {
+                        id: platform.id,
+                        version: platform.version,
+                        releases: releases.get(&platform.id).copied().unwrap_or_default(),
+                        name: platform.name,
+                    })
+                    .collect::<Vec<_>>(),
+                vec![("platforms", None), ("game_releases", None)],
+            ))
+        })
+        .await
+    }
+
+    pub async fn get_platform_by_id(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+    ) -> Result<PlatformsControl> {
+        use crate::schema::platforms::dsl::*;
+
+        conn.run(move |sql_conn| -> Result<PlatformsControl> {
+            let result: Platform = platforms
+                .filter(id.eq(id_for_lookup))
+                .first(sql_conn)
+                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+            Ok(PlatformsControl {
+                id: result.id,
+                version: result.version,
+                name: result.name,
+                releases: 0,
+            })
+        })
+        .await
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for PlatformsControl {
+    type Form = AddPlatform;
+    type New = NewPlatform;
+    type Table = platforms::table;
+    type Version = platforms::version;
+
+    const NAME: &'static str = "platforms";
+    const TABLE: &'static str = "Платформи";
+
+    fn parse(form: AddPlatform) -> Result<Self::New, ServerError> {
+        NewPlatform::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_platform_by_id(conn, id).await
+    }
+
+    fn cached(_id: Option<i32>) -> Vec<cache::Dependency> {
+        vec![("platforms", None), ("game_releases", None)]
+    }
+}
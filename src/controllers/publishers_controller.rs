@@ -1,25 +1,55 @@
+use crate::cache;
+use crate::config;
 use crate::controllers::*;
 use crate::errors::ServerError;
 use crate::models::*;
 use crate::requests_handler::AddPublisher;
-use crate::schema::publishers;
+use crate::schema::{publisher_popularity, publishers};
 use crate::DBConnection;
 use anyhow::Result;
+use chrono::{Local, NaiveDate};
+use diesel::dsl::sql;
 use diesel::pg::data_types::PgMoney;
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::result::Error as DieselError;
+use diesel::sql_types::{Money, Nullable};
 use serde::Serialize;
+use std::collections::HashMap;
 
-#[derive(Insertable)]
+/// The columns of `publisher_popularity` loaded as a `PublisherPopularity`.
+const SNAPSHOT_COLUMNS: (
+    publisher_popularity::publisher_id,
+    publisher_popularity::score,
+    publisher_popularity::computed_score,
+    publisher_popularity::income,
+    publisher_popularity::taken_on,
+) = (
+    publisher_popularity::publisher_id,
+    publisher_popularity::score,
+    publisher_popularity::computed_score,
+    publisher_popularity::income,
+    publisher_popularity::taken_on,
+);
+
+#[derive(Insertable, AsChangeset)]
 #[table_name = "publishers"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct NewPublisher {
     pub name: String,
     pub price: PgMoney,
-    pub popularity: i16,
+    pub popularity: Option<i16>,
 }
 
 impl NewPublisher {
     pub fn from(publisher: AddPublisher) -> Result<Self, ServerError> {
+        if publisher
+            .popularity
+            .is_some_and(|popularity| !(0..=10).contains(&popularity))
+        {
+            return Err(ServerError::InvalidValue(vec!["popularity".to_string()]));
+        }
+
         Ok(NewPublisher {
             name: publisher.name,
             price: PgMoney((publisher.price * 100f64) as i64),
@@ -28,21 +58,54 @@ impl NewPublisher {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Insertable)]
+#[table_name = "publisher_popularity"]
+struct NewPopularitySnapshot {
+    publisher_id: i32,
+    score: i16,
+    computed_score: i16,
+    income: PgMoney,
+    taken_on: NaiveDate,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PopularityPoint {
+    pub taken_on: String,
+    pub score: i16,
+    pub computed_score: i16,
+    pub income: f64,
+}
+
+/// `popularity` is the manual override; `score` is the effective popularity,
+/// which falls back to the score computed from the income of the games.
+/// `trend` is the change of `score` since the last snapshot taken before today.
+#[derive(Serialize, Clone)]
 pub struct PublishersControl {
     pub id: i32,
+    pub version: i32,
     pub name: String,
     pub price: f64,
-    pub popularity: i16,
+    pub popularity: Option<i16>,
+    pub computed_score: i16,
+    pub score: i16,
+    pub income: f64,
+    pub rank: usize,
+    pub trend: i16,
 }
 
 impl std::convert::From<Publisher> for PublishersControl {
     fn from(publishers_struct: Publisher) -> Self {
         PublishersControl {
             id: publishers_struct.id,
+            version: publishers_struct.version,
             name: publishers_struct.name,
             price: publishers_struct.price.0 as f64 / 100f64,
             popularity: publishers_struct.popularity,
+            computed_score: 0,
+            score: publishers_struct.popularity.unwrap_or_default(),
+            income: 0f64,
+            rank: 0,
+            trend: 0,
         }
     }
 }
@@ -51,13 +114,186 @@ impl PublishersControl {
     pub async fn get_statistic(
         conn: &DBConnection,
         id_for_lookup: i32,
-    ) -> (i32, Vec<GamesControl>) {
+    ) -> (
+        i32,
+        Vec<GamesControl>,
+        Vec<ContractsControl>,
+        f64,
+        f64,
+        Vec<PopularityPoint>,
+    ) {
+        let contracts = PublishersControl::get_contracts(conn, id_for_lookup).await;
+        let committed_advances = contracts
+            .iter()
+            .fold(0f64, |sum, contract| sum + contract.advance_payment);
+        let projected_payouts = contracts
+            .iter()
+            .fold(0f64, |sum, contract| sum + contract.projected_payout);
         (
             id_for_lookup,
             PublishersControl::get_games(conn, id_for_lookup).await,
+            contracts,
+            committed_advances,
+            projected_payouts,
+            PublishersControl::get_popularity_history(conn, id_for_lookup)
+                .await
+                .unwrap(),
         )
     }
 
+    /// Net donations plus net sales of the games of every publisher, in cents.
+    fn incomes(sql_conn: &PgConnection) -> Result<HashMap<i32, i64>> {
+        use crate::schema::{games, sales};
+
+        let publisher_of: HashMap<i32, i32> = games::table
+            .select((games::id, games::publisher_id))
+            .load(sql_conn)?
+            .into_iter()
+            .collect();
+        let donated = StatisticsControl::donated_by_game(sql_conn)?
+            .into_iter()
+            .filter_map(|(game, amount)| {
+                publisher_of
+                    .get(&game)
+                    .map(|publisher_id| (*publisher_id, Some(PgMoney(amount))))
+            });
+        let sold: Vec<(i32, Option<PgMoney>)> = sales::table
+            .inner_join(games::table)
+            .select((
+                games::publisher_id,
+                sql::<Nullable<Money>>("sum(sales.gross - sales.refunds)"),
+            ))
+            .group_by(games::publisher_id)
+            .load(sql_conn)?;
+
+        let mut incomes: HashMap<i32, i64> = HashMap::new();
+        for (publisher_id, amount) in donated.into_iter().chain(sold) {
+            *incomes.entry(publisher_id).or_default() += amount.map_or(0, |amount| amount.0);
+        }
+        Ok(incomes)
+    }
+
+    /// Popularity from 0 to 10 relative to the publisher with the biggest income.
+    fn computed_score(income: i64, max_income: i64) -> i16 {
+        if max_income <= 0 || income <= 0 {
+            return 0;
+        }
+        (income as f64 * 10f64 / max_income as f64).round() as i16
+    }
+
+    /// Publishers with their computed and effective popularity.
+    fn with_scores(sql_conn: &PgConnection) -> Result<Vec<PublishersControl>> {
+        let all_publishers: Vec<Publisher> = publishers::table
+            .order(publishers::id.asc())
+            .load(sql_conn)?;
+        let incomes = PublishersControl::incomes(sql_conn)?;
+        let max_income = incomes.values().copied().max().unwrap_or_default();
+
+        Ok(all_publishers
+            .into_iter()
+            .map(|publisher| {
+                let income = incomes.get(&publisher.id).copied().unwrap_or_default();
+                let computed_score = PublishersControl::computed_score(income, max_income);
+                PublishersControl {
+                    score: publisher.popularity.unwrap_or(computed_score),
+                    computed_score,
+                    income: income as f64 / 100f64,
+                    ..PublishersControl::from(publisher)
+                }
+            })
+            .collect())
+    }
+
+    /// Records the current popularity of every publisher, replacing a
+    /// snapshot already taken on the same day.
+    pub fn take_popularity_snapshot(sql_conn: &PgConnection, day: NaiveDate) -> Result<usize> {
+        use crate::schema::publisher_popularity::dsl::*;
+
+        let snapshots: Vec<NewPopularitySnapshot> = PublishersControl::with_scores(sql_conn)?
+            .into_iter()
+            .map(|publisher| NewPopularitySnapshot {
+                publisher_id: publisher.id,
+                score: publisher.score,
+                computed_score: publisher.computed_score,
+                income: PgMoney((publisher.income * 100f64).round() as i64),
+                taken_on: day,
+            })
+            .collect();
+
+        Ok(diesel::insert_into(publisher_popularity)
+            .values(&snapshots)
+            .on_conflict((publisher_id, taken_on))
+            .do_update()
+            .set((
+                score.eq(excluded(score)),
+                computed_score.eq(excluded(computed_score)),
+                income.eq(excluded(income)),
+            ))
+            .execute(sql_conn)?)
+    }
+
+    pub async fn snapshot_popularity(conn: &DBConnection) -> Result<usize> {
+        let today = Local::today().naive_local();
+
+        let taken = conn
+            .run(move |sql_conn| PublishersControl::take_popularity_snapshot(sql_conn, today))
+            .await?;
+        cache::invalidate(&[("publisher_popularity", None)]);
+        Ok(taken)
+    }
+
+    pub async fn get_popularity_history(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+    ) -> Result<Vec<PopularityPoint>> {
+        use crate::schema::publisher_popularity::dsl::*;
+
+        let snapshots = conn
+            .run(move |sql_conn| -> Result<Vec<PublisherPopularity>> {
+                Ok(publisher_popularity
+                    .select(SNAPSHOT_COLUMNS)
+                    .filter(publisher_id.eq(id_for_lookup))
+                    .order(taken_on.asc())
+                    .load(sql_conn)?)
+            })
+            .await?;
+
+        Ok(snapshots
+            .into_iter()
+            .map(|snapshot| PopularityPoint {
+                taken_on: snapshot
+                    .taken_on
+                    .format(&config::formats().date)
+                    .to_string(),
+                score: snapshot.score,
+                computed_score: snapshot.computed_score,
+                income: snapshot.income.0 as f64 / 100f64,
+            })
+            .collect())
+    }
+
+    pub async fn get_contracts(conn: &DBConnection, id_for_lookup: i32) -> Vec<ContractsControl> {
+        use crate::schema::contracts;
+        use crate::schema::publishers::dsl::*;
+
+        let table = conn
+            .run(move |sql_conn| -> Vec<(Publisher, Contract)> {
+                publishers
+                    .filter(id.eq(id_for_lookup))
+                    .inner_join(contracts::table)
+                    .load(sql_conn)
+                    .unwrap()
+            })
+            .await;
+
+        let mut vec = Vec::new();
+        for (_, contract) in table {
+            vec.push(ContractsControl::make_contracts_control(conn, contract).await);
+        }
+
+        vec
+    }
+
     pub async fn get_games(conn: &DBConnection, id_for_lookup: i32) -> Vec<GamesControl> {
         use crate::schema::games;
         use crate::schema::publishers::dsl::*;
@@ -79,16 +315,65 @@ impl PublishersControl {
 
         vec
     }
+
+    /// Publishers ranked by their effective popularity. Publishers with the
+    /// same score share a rank.
     pub async fn get_publishers(conn: &DBConnection) -> Result<Vec<PublishersControl>> {
-        use crate::schema::publishers::dsl::*;
+        cache::cached("publishers".to_string(), async {
+            let today = Local::today().naive_local();
 
-        let results = conn
-            .run(move |sql_conn| -> Result<Vec<Publisher>> {
-                Ok(publishers.order(id.asc()).load::<Publisher>(sql_conn)?)
-            })
-            .await?;
+            let (mut results, snapshots) = conn
+                .run(
+                    move |sql_conn| -> Result<(Vec<PublishersControl>, Vec<PublisherPopularity>)> {
+                        let results = PublishersControl::with_scores(sql_conn)?;
+                        let snapshots = publisher_popularity::table
+                            .select(SNAPSHOT_COLUMNS)
+                            .filter(publisher_popularity::taken_on.lt(today))
+                            .order(publisher_popularity::taken_on.desc())
+                            .load(sql_conn)?;
+                        Ok((results, snapshots))
+                    },
+                )
+                .await?;
+
+            let mut previous: HashMap<i32, i16> = HashMap::new();
+            for snapshot in snapshots {
+                previous
+                    .entry(snapshot.publisher_id)
+                    .or_insert(snapshot.score);
+            }
 
-        Ok(results.into_iter().map(PublishersControl::from).collect())
+            results.sort_by(|first, second| {
+                second
+                    .score
+                    .cmp(&first.score)
+                    .then(second.income.partial_cmp(&first.income).unwrap())
+                    .then(first.name.cmp(&second.name))
+            });
+            let mut last_score = None;
+            for (position, publisher) in results.iter_mut().enumerate() {
+                publisher.rank = match last_score {
+                    Some((score, rank)) if score == publisher.score => rank,
+                    _ => position + 1,
+                };
+                last_score = Some((publisher.score, publisher.rank));
+                publisher.trend = previous
+                    .get(&publisher.id)
+                    .map_or(0, |score| publisher.score - score);
+            }
+
+            Ok((
+                results,
+                vec![
+                    ("publishers", None),
+                    ("games", None),
+                    ("sales", None),
+                    ("donation_user_stats", None),
+                    ("publisher_popularity", None),
+                ],
+            ))
+        })
+        .await
     }
 
     pub async fn get_publisher_by_id(
@@ -97,70 +382,43 @@ impl PublishersControl {
     ) -> Result<PublishersControl> {
         use crate::schema::publishers::dsl::*;
 
-        conn.run(move |sql_conn| -> Result<PublishersControl> {
-            let result: Publisher = publishers
-                .filter(id.eq(id_for_lookup))
-                .first(sql_conn)
-                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(PublishersControl::from(result))
+        cache::cached(format!("publishers/{}", id_for_lookup), async {
+            conn.run(move |sql_conn| -> Result<PublishersControl> {
+                let result: Publisher = publishers
+                    .filter(id.eq(id_for_lookup))
+                    .first(sql_conn)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                Ok(PublishersControl::from(result))
+            })
+            .await
+            .map(|publisher| (publisher, vec![("publishers", Some(id_for_lookup))]))
         })
         .await
     }
+}
 
-    pub async fn add_publisher(conn: &DBConnection, publisher: NewPublisher) -> Result<()> {
-        use crate::schema::publishers::dsl::*;
+#[rocket::async_trait]
+impl Resource for PublishersControl {
+    type Form = AddPublisher;
+    type New = NewPublisher;
+    type Table = publishers::table;
+    type Version = publishers::version;
 
-        conn.run(move |sql_connection| -> Result<()> {
-            diesel::insert_into(publishers)
-                .values(&publisher)
-                .get_result::<Publisher>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
-        })
-        .await
-    }
+    const NAME: &'static str = "publishers";
+    const TABLE: &'static str = "Видавництва";
 
-    pub async fn update_publisher(
-        conn: &DBConnection,
-        id_for_update: i32,
-        publisher: NewPublisher,
-    ) -> Result<()> {
-        use crate::schema::publishers::dsl::*;
-
-        conn.run(move |sql_connection| -> Result<()> {
-            diesel::update(publishers.filter(&id.eq(id_for_update)))
-                .set((
-                    name.eq(publisher.name),
-                    price.eq(publisher.price),
-                    popularity.eq(publisher.popularity),
-                ))
-                .get_result::<Publisher>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
-        })
-        .await
+    fn parse(form: AddPublisher) -> Result<Self::New, ServerError> {
+        NewPublisher::from(form)
     }
 
-    pub async fn delete_publisher(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
-        use crate::schema::publishers::dsl::*;
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_publisher_by_id(conn, id).await
+    }
 
-        conn.run(move |sql_conn| -> Result<()> {
-            diesel::delete(publishers)
-                .filter(&id.eq(id_for_delete))
-                .get_result::<Publisher>(sql_conn)
-                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(())
-        })
-        .await
+    /// The publisher's own row, and the game rows that show its name.
+    fn cached(id: Option<i32>) -> Vec<cache::Dependency> {
+        let mut changed = vec![("publishers", id)];
+        changed.extend_from_slice(&GAME_ROWS);
+        changed
     }
 }
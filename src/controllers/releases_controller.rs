@@ -0,0 +1,203 @@
+use crate::cache;
+use crate::config;
+use crate::controllers::write_error;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddRelease;
+use crate::schema::game_releases;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::NaiveDate;
+use diesel::pg::data_types::PgMoney;
+use diesel::prelude::*;
+use serde::Serialize;
+
+/// The columns of `game_releases` loaded as a `GameRelease`.
+const RELEASE_COLUMNS: (
+    game_releases::id,
+    game_releases::game_id,
+    game_releases::region,
+    game_releases::release_date,
+    game_releases::price,
+) = (
+    game_releases::id,
+    game_releases::game_id,
+    game_releases::region,
+    game_releases::release_date,
+    game_releases::price,
+);
+
+#[derive(Insertable)]
+#[table_name = "game_releases"]
+pub struct NewRelease {
+    pub game_id: i32,
+    pub platform_id: i32,
+    pub region: String,
+    pub release_date: NaiveDate,
+    pub price: PgMoney,
+}
+
+impl NewRelease {
+    pub fn from(game_id: i32, release: AddRelease) -> Result<Self, ServerError> {
+        let region = release.region.trim().to_string();
+        if region.is_empty() {
+            return Err(ServerError::NullValues(vec!["region".to_string()]));
+        }
+        let release_date = NaiveDate::parse_from_str(&release.release_date, "%Y-%m-%d")
+            .map_err(|_| ServerError::InvalidDate)?;
+        if release.price < 0f64 {
+            return Err(ServerError::InvalidValue(vec!["price".to_string()]));
+        }
+
+        Ok(NewRelease {
+            game_id,
+            platform_id: release.platform_id,
+            region,
+            release_date,
+            price: PgMoney((release.price * 100f64) as i64),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReleaseCell {
+    pub id: i32,
+    pub release_date: String,
+    pub price: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReleaseMatrixRow {
+    pub platform: String,
+    pub cells: Vec<Option<ReleaseCell>>,
+}
+
+/// Releases of a game with a row per platform and a column per region.
+#[derive(Serialize, Debug)]
+pub struct ReleaseMatrix {
+    pub regions: Vec<String>,
+    pub rows: Vec<ReleaseMatrixRow>,
+}
+
+pub struct ReleasesControl;
+
+impl ReleasesControl {
+    /// Date of the earliest release of the game on any platform.
+    pub async fn get_first_release(
+        conn: &DBConnection,
+        game_id_for_lookup: i32,
+    ) -> Result<Option<NaiveDate>> {
+        use crate::schema::game_releases::dsl::*;
+
+        conn.run(move |sql_conn| -> Result<Option<NaiveDate>> {
+            Ok(game_releases
+                .select(release_date)
+                .filter(game_id.eq(game_id_for_lookup))
+                .order(release_date.asc())
+                .first(sql_conn)
+                .optional()?)
+        })
+        .await
+    }
+
+    pub async fn get_release_matrix(
+        conn: &DBConnection,
+        game_id_for_lookup: i32,
+    ) -> Result<ReleaseMatrix> {
+        use crate::schema::platforms;
+
+        let releases = conn
+            .run(move |sql_conn| -> Result<Vec<(GameRelease, String)>> {
+                Ok(game_releases::table
+                    .inner_join(platforms::table)
+                    .select((RELEASE_COLUMNS, platforms::name))
+                    .filter(game_releases::game_id.eq(game_id_for_lookup))
+                    .order((platforms::name.asc(), game_releases::region.asc()))
+                    .load(sql_conn)?)
+            })
+            .await?;
+
+        let mut regions: Vec<String> = releases
+            .iter()
+            .map(|(release, _)| release.region.clone())
+            .collect();
+        regions.sort();
+        regions.dedup();
+
+        let mut rows: Vec<ReleaseMatrixRow> = vec![];
+        for (release, platform) in releases {
+            if rows.last().is_none_or(|row| row.platform != platform) {
+                rows.push(ReleaseMatrixRow {
+                    platform,
+                    cells: regions.iter().map(|_| None).collect(),
+                });
+            }
+            let column = regions
+                .iter()
+                .position(|region| *region == release.region)
+                .unwrap();
+            rows.last_mut().unwrap().cells[column] = Some(ReleaseCell {
+                id: release.id,
+                release_date: release
+                    .release_date
+                    .format(&config::formats().date)
+                    .to_string(),
+                price: release.price.0 as f64 / 100f64,
+            });
+        }
+
+        Ok(ReleaseMatrix { regions, rows })
+    }
+
+    /// A game is released on a platform at most once per region.
+    pub async fn add_release(conn: &DBConnection, release: NewRelease) -> Result<()> {
+        use crate::schema::platforms;
+
+        let game = release.game_id;
+        conn.run(move |sql_connection| -> Result<()> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let platform: String = platforms::table
+                    .select(platforms::name)
+                    .filter(platforms::id.eq(release.platform_id))
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["platform id".to_string()]))?;
+                let released: i64 = game_releases::table
+                    .filter(game_releases::game_id.eq(release.game_id))
+                    .filter(game_releases::platform_id.eq(release.platform_id))
+                    .filter(game_releases::region.eq(&release.region))
+                    .count()
+                    .get_result(sql_connection)?;
+                if released > 0 {
+                    return Err(ServerError::DuplicateRelease(platform, release.region).into());
+                }
+
+                diesel::insert_into(game_releases::table)
+                    .values(&release)
+                    .execute(sql_connection)
+                    .map_err(write_error)?;
+                Ok(())
+            })
+        })
+        .await?;
+        cache::invalidate(&[("game_releases", Some(game))]);
+        Ok(())
+    }
+
+    /// Deletes the release and returns the id of its game.
+    pub async fn delete_release(conn: &DBConnection, id_for_delete: i32) -> Result<i32> {
+        use crate::schema::game_releases::dsl::*;
+
+        let game = conn
+            .run(move |sql_conn| -> Result<i32> {
+                let release = diesel::delete(game_releases)
+                    .filter(&id.eq(id_for_delete))
+                    .returning(RELEASE_COLUMNS)
+                    .get_result::<GameRelease>(sql_conn)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                Ok(release.game_id)
+            })
+            .await?;
+        cache::invalidate(&[("game_releases", Some(game))]);
+        Ok(game)
+    }
+}
@@ -0,0 +1,188 @@
+use crate::cache::{self, Dependency};
+use crate::controllers::{for_each_selected, write_error};
+use crate::errors::ServerError;
+use crate::DBConnection;
+use anyhow::Result;
+use diesel::associations::HasTable;
+use diesel::dsl::{AsExprOf, Eq};
+use diesel::expression::operators::And;
+use diesel::expression::ops::Add;
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use diesel::query_builder::{
+    AsChangeset, DeleteStatement, InsertStatement, IntoUpdateTarget, QueryFragment,
+};
+use diesel::query_dsl::methods::{ExecuteDsl, FilterDsl};
+use diesel::sql_types::Integer;
+use diesel::Insertable;
+use serde::Serialize;
+
+/// An entity with add, edit and delete pages. The pages live under
+/// `/{NAME}/add`, `/{NAME}/edit?<id>` and `/{NAME}/delete?<id>`, render the
+/// `{NAME}_add` and `{NAME}_edit` templates and redirect back to `/{NAME}`.
+/// The routes themselves are generated by `resource_routes!`.
+///
+/// Rows are written to `Table`: the checked form values are inserted as they
+/// are, and an edit sets them on the row whose `Version` still matches the
+/// one the form was opened with.
+#[rocket::async_trait]
+pub trait Resource: Serialize + Send + Sized + 'static {
+    /// Fields of the add and edit forms.
+    type Form: Serialize + Send;
+    /// Checked form values, inserted as a new row or set on an edited one.
+    type New: Row<Self::Table, Self::Version> + Send + 'static;
+    /// The table of the rows, keyed by `id`.
+    type Table;
+    /// The `version` column of `Table`.
+    type Version;
+
+    const NAME: &'static str;
+    const TABLE: &'static str;
+
+    fn parse(form: Self::Form) -> Result<Self::New, ServerError>;
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self>;
+
+    /// Checks the values against the other rows before they are written.
+    /// `id` is the edited row, `None` for a new one.
+    fn check(_sql_conn: &PgConnection, _new: &Self::New, _id: Option<i32>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Brings the data derived from the rows up to date after a write.
+    fn refresh(_sql_conn: &PgConnection) -> Result<()> {
+        Ok(())
+    }
+
+    /// Cached reads to drop after the row `id` is written or deleted; `None`
+    /// stands for a new row or several of them.
+    fn cached(_id: Option<i32>) -> Vec<Dependency> {
+        vec![]
+    }
+
+    async fn add(conn: &DBConnection, new: Self::New) -> Result<()> {
+        conn.run(move |sql_conn| {
+            sql_conn.transaction::<_, anyhow::Error, _>(|| {
+                Self::check(sql_conn, &new, None)?;
+                new.insert(sql_conn)?;
+                Self::refresh(sql_conn)
+            })
+        })
+        .await?;
+        cache::invalidate(&Self::cached(None));
+        Ok(())
+    }
+
+    async fn update(conn: &DBConnection, id: i32, version: i32, new: Self::New) -> Result<()> {
+        conn.run(move |sql_conn| {
+            sql_conn.transaction::<_, anyhow::Error, _>(|| {
+                Self::check(sql_conn, &new, Some(id))?;
+                new.update(sql_conn, id, version)?;
+                Self::refresh(sql_conn)
+            })
+        })
+        .await?;
+        cache::invalidate(&Self::cached(Some(id)));
+        Ok(())
+    }
+
+    /// Deletes one row together with whatever has to go with it. Shared by
+    /// the delete page and the bulk delete, which runs it in a savepoint.
+    fn remove(sql_conn: &PgConnection, id: i32) -> Result<()> {
+        sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            Self::New::delete(sql_conn, id)?;
+            Self::refresh(sql_conn)
+        })
+    }
+
+    async fn delete(conn: &DBConnection, id: i32) -> Result<()> {
+        conn.run(move |sql_conn| Self::remove(sql_conn, id)).await?;
+        cache::invalidate(&Self::cached(Some(id)));
+        Ok(())
+    }
+
+    /// Deletes the selected rows and returns the ones that failed.
+    async fn delete_selected(conn: &DBConnection, ids: Vec<i32>) -> Result<Vec<String>> {
+        let failed = conn
+            .run(move |sql_conn| for_each_selected(sql_conn, &ids, |id| Self::remove(sql_conn, id)))
+            .await?;
+        cache::invalidate(&Self::cached(None));
+        Ok(failed)
+    }
+
+    /// Choices for the select fields of the forms.
+    async fn form_lists(_conn: &DBConnection) -> Vec<Vec<String>> {
+        vec![]
+    }
+
+    /// Puts the values into the shape the edit form inputs expect.
+    fn for_edit(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The values of a row of `T`, whose `version` column is `V`. Implemented for
+/// every struct that derives `Insertable` and `AsChangeset` for its table.
+/// The errors are the ones shown on the forms.
+pub trait Row<T, V>: Sized {
+    fn insert(self, sql_conn: &PgConnection) -> Result<()>;
+
+    /// Sets the values on the row `id` if it is still at `version`, and
+    /// bumps the version.
+    fn update(self, sql_conn: &PgConnection, id: i32, version: i32) -> Result<()>;
+
+    fn delete(sql_conn: &PgConnection, id: i32) -> Result<()>;
+}
+
+type ById<T> = Eq<<T as Table>::PrimaryKey, i32>;
+type ByVersion<T, V> = And<ById<T>, Eq<V, i32>>;
+
+impl<T, V, N, E, D> Row<T, V> for N
+where
+    T: Table + HasTable<Table = T>,
+    T: FilterDsl<ByVersion<T, V>, Output = E> + FilterDsl<ById<T>, Output = D>,
+    T::PrimaryKey: ExpressionMethods + Expression<SqlType = Integer>,
+    V: Column<Table = T> + ExpressionMethods + AppearsOnTable<T> + QueryFragment<Pg> + Default,
+    V: Expression<SqlType = Integer> + std::ops::Add<i32, Output = Add<V, AsExprOf<i32, Integer>>>,
+    N: Insertable<T> + AsChangeset<Target = T>,
+    E: IntoUpdateTarget<Table = T>,
+    D: IntoUpdateTarget<Table = T>,
+    InsertStatement<T, N::Values>: ExecuteDsl<PgConnection>,
+    T::FromClause: QueryFragment<Pg>,
+    E::WhereClause: QueryFragment<Pg>,
+    N::Changeset: QueryFragment<Pg>,
+    DeleteStatement<T, D::WhereClause>: ExecuteDsl<PgConnection>,
+{
+    fn insert(self, sql_conn: &PgConnection) -> Result<()> {
+        diesel::insert_into(T::table())
+            .values(self)
+            .execute(sql_conn)
+            .map_err(write_error)?;
+        Ok(())
+    }
+
+    fn update(self, sql_conn: &PgConnection, id: i32, version: i32) -> Result<()> {
+        let edited = T::table()
+            .primary_key()
+            .eq(id)
+            .and(V::default().eq(version));
+        let updated = diesel::update(T::table().filter(edited))
+            .set((V::default().eq(V::default() + 1), self))
+            .execute(sql_conn)
+            .map_err(write_error)?;
+        if updated == 0 {
+            return Err(ServerError::EditConflict.into());
+        }
+        Ok(())
+    }
+
+    fn delete(sql_conn: &PgConnection, id: i32) -> Result<()> {
+        let deleted = diesel::delete(T::table().filter(T::table().primary_key().eq(id)))
+            .execute(sql_conn)
+            .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+        if deleted == 0 {
+            return Err(ServerError::InvalidValue(vec!["Id".to_string()]).into());
+        }
+        Ok(())
+    }
+}
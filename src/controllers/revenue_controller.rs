@@ -0,0 +1,246 @@
+use crate::controllers::{JobsControl, StatisticsControl, SubscriptionPlan};
+use crate::models::*;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Local, NaiveDate};
+use diesel::dsl::sql;
+use diesel::pg::data_types::PgMoney;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Money, Nullable};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Units sold, gross and refunds of every game.
+type SalesSums = Vec<(i32, Option<i64>, Option<PgMoney>, Option<PgMoney>)>;
+/// Games, their sales and donations, subscriptions and jobs with the salary
+/// changes, as read for `get_revenue`.
+type RevenueRows = (
+    Vec<Game>,
+    SalesSums,
+    HashMap<i32, i64>,
+    Vec<Subscription>,
+    Vec<(Job, Vec<SalaryChange>)>,
+);
+
+/// Donated, sold and refunded amounts of a game, its subscriptions and its
+/// jobs with the salary changes, as read for `get_net_income`.
+type NetIncomeRows = (
+    Option<PgMoney>,
+    Option<PgMoney>,
+    Option<PgMoney>,
+    Vec<Subscription>,
+    Vec<(Job, Vec<SalaryChange>)>,
+);
+
+#[derive(Serialize, Debug, Default)]
+pub struct RevenueControl {
+    pub game_id: i32,
+    pub game: String,
+    pub units_sold: i64,
+    pub sales: f64,
+    pub donations: f64,
+    pub subscriptions: f64,
+    pub income: f64,
+    pub prime_cost: f64,
+    pub salary_costs: f64,
+    pub profit: f64,
+}
+
+impl RevenueControl {
+    /// Income and costs of every game together with the studio-wide totals.
+    /// Sales and donations are counted net of refunds, subscriptions by the
+    /// periods paid.
+    ///
+    /// With a `period` (first and last day, inclusive) only the sales,
+    /// donations, subscription periods started and salaries of those days are
+    /// counted, and the prime cost, which is not tied to any day, is left out.
+    pub async fn get_revenue(
+        conn: &DBConnection,
+        period: Option<(NaiveDate, NaiveDate)>,
+    ) -> Result<(Vec<RevenueControl>, RevenueControl)> {
+        use crate::schema::{games, jobs, sales, subscriptions};
+
+        let (all_games, sales_by_game, donations_by_game, all_subscriptions, all_jobs) = conn
+            .run(move |sql_conn| -> Result<RevenueRows> {
+                let all_games = games::table.order(games::id.asc()).load(sql_conn)?;
+                let mut sales_query = sales::table
+                    .select((
+                        sales::game_id,
+                        sql::<Nullable<BigInt>>("sum(units)"),
+                        sql::<Nullable<Money>>("sum(gross)"),
+                        sql::<Nullable<Money>>("sum(refunds)"),
+                    ))
+                    .group_by(sales::game_id)
+                    .into_boxed();
+                if let Some((from, to)) = period {
+                    sales_query = sales_query.filter(sales::sale_date.between(from, to));
+                }
+                let sales_by_game = sales_query.load(sql_conn)?;
+                let donations_by_game = match period {
+                    Some((from, to)) => {
+                        StatisticsControl::donated_by_game_between(sql_conn, from, to)?
+                    }
+                    None => StatisticsControl::donated_by_game(sql_conn)?,
+                };
+                let all_subscriptions = subscriptions::table.load(sql_conn)?;
+                let all_jobs =
+                    JobsControl::with_salary_changes(sql_conn, jobs::table.load(sql_conn)?)?;
+                Ok((
+                    all_games,
+                    sales_by_game,
+                    donations_by_game,
+                    all_subscriptions,
+                    all_jobs,
+                ))
+            })
+            .await?;
+
+        let sales_by_game: HashMap<i32, (i64, f64)> = sales_by_game
+            .into_iter()
+            .map(|(game_id, units, gross, refunds)| {
+                let net = gross.map_or(0, |gross| gross.0) - refunds.map_or(0, |refunds| refunds.0);
+                (game_id, (units.unwrap_or(0), net as f64 / 100f64))
+            })
+            .collect();
+        let donations_by_game: HashMap<i32, f64> = donations_by_game
+            .into_iter()
+            .map(|(game_id, amount)| (game_id, amount as f64 / 100f64))
+            .collect();
+
+        let mut subscriptions_by_game: HashMap<i32, f64> = HashMap::new();
+        for subscription in all_subscriptions.iter() {
+            let periods = match period {
+                Some((from, to)) => {
+                    let plan = SubscriptionPlan::parse(&subscription.plan)?;
+                    (0..subscription.periods_paid)
+                        .map(|paid| plan.paid_until(subscription.start_date, paid))
+                        .filter(|period_start| from <= *period_start && *period_start <= to)
+                        .count() as i32
+                }
+                None => subscription.periods_paid,
+            };
+            *subscriptions_by_game
+                .entry(subscription.game_id)
+                .or_default() += subscription.price.0 as f64 / 100f64 * periods as f64;
+        }
+
+        let today = Local::today().naive_local();
+        let mut salaries_by_game: HashMap<i32, f64> = HashMap::new();
+        for (job, changes) in all_jobs.iter() {
+            *salaries_by_game.entry(job.game_id).or_default() += match period {
+                Some((from, to)) => JobsControl::salary_cost_between(job, changes, from, to),
+                None => JobsControl::salary_cost(job, changes, today),
+            };
+        }
+
+        let mut total = RevenueControl {
+            game: "Усього".to_string(),
+            ..Default::default()
+        };
+        let revenue = all_games
+            .into_iter()
+            .map(|game| {
+                let (units_sold, sales) = sales_by_game.get(&game.id).copied().unwrap_or_default();
+                let donations = donations_by_game.get(&game.id).copied().unwrap_or_default();
+                let subscriptions = subscriptions_by_game
+                    .get(&game.id)
+                    .copied()
+                    .unwrap_or_default();
+                let salary_costs = salaries_by_game.get(&game.id).copied().unwrap_or_default();
+                let prime_cost = match period {
+                    Some(_) => 0f64,
+                    None => game.prime_cost.0 as f64 / 100f64,
+                };
+                let income = sales + donations + subscriptions;
+
+                total.units_sold += units_sold;
+                total.sales += sales;
+                total.donations += donations;
+                total.subscriptions += subscriptions;
+                total.income += income;
+                total.prime_cost += prime_cost;
+                total.salary_costs += salary_costs;
+                total.profit += income - prime_cost - salary_costs;
+
+                RevenueControl {
+                    game_id: game.id,
+                    game: game.name,
+                    units_sold,
+                    sales,
+                    donations,
+                    subscriptions,
+                    income,
+                    prime_cost,
+                    salary_costs,
+                    profit: income - prime_cost - salary_costs,
+                }
+            })
+            .collect();
+
+        Ok((revenue, total))
+    }
+
+    /// Net income of a game between `from` and `to` inclusive: donations,
+    /// sales net of refunds and subscription periods started in that time,
+    /// minus the salaries paid for the game over the same days.
+    pub async fn get_net_income(
+        conn: &DBConnection,
+        game_id_for_lookup: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<f64> {
+        use crate::schema::{jobs, sales, subscriptions};
+
+        let (donated, sold, refunded, game_subscriptions, game_jobs) = conn
+            .run(move |sql_conn| -> Result<NetIncomeRows> {
+                let donated = Some(PgMoney(StatisticsControl::donated_between(
+                    sql_conn,
+                    game_id_for_lookup,
+                    from,
+                    to,
+                )?));
+                let (sold, refunded) = sales::table
+                    .select((
+                        sql::<Nullable<Money>>("sum(gross)"),
+                        sql::<Nullable<Money>>("sum(refunds)"),
+                    ))
+                    .filter(sales::game_id.eq(game_id_for_lookup))
+                    .filter(sales::sale_date.between(from, to))
+                    .first(sql_conn)?;
+                let game_subscriptions = subscriptions::table
+                    .filter(subscriptions::game_id.eq(game_id_for_lookup))
+                    .load(sql_conn)?;
+                let game_jobs = JobsControl::with_salary_changes(
+                    sql_conn,
+                    jobs::table
+                        .filter(jobs::game_id.eq(game_id_for_lookup))
+                        .load(sql_conn)?,
+                )?;
+                Ok((donated, sold, refunded, game_subscriptions, game_jobs))
+            })
+            .await?;
+
+        let mut income = ([donated, sold]
+            .iter()
+            .flatten()
+            .map(|money| money.0)
+            .sum::<i64>()
+            - refunded.map_or(0, |money| money.0)) as f64
+            / 100f64;
+
+        for subscription in game_subscriptions.iter() {
+            let plan = SubscriptionPlan::parse(&subscription.plan)?;
+            let periods_started = (0..subscription.periods_paid)
+                .map(|period| plan.paid_until(subscription.start_date, period))
+                .filter(|period_start| from <= *period_start && *period_start <= to)
+                .count();
+            income += subscription.price.0 as f64 / 100f64 * periods_started as f64;
+        }
+
+        let salaries = game_jobs.iter().fold(0f64, |sum, (job, changes)| {
+            sum + JobsControl::salary_cost_between(job, changes, from, to)
+        });
+
+        Ok(income - salaries)
+    }
+}
@@ -0,0 +1,171 @@
+use crate::cache;
+use crate::config;
+use crate::controllers::GamesControl;
+use crate::controllers::Resource;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddSale;
+use crate::schema::sales;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::NaiveDate;
+use diesel::pg::data_types::PgMoney;
+use diesel::prelude::*;
+use serde::Serialize;
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "sales"]
+pub struct NewSale {
+    pub game_id: i32,
+    pub platform: String,
+    pub sale_date: NaiveDate,
+    pub units: i32,
+    pub gross: PgMoney,
+    pub refunds: PgMoney,
+}
+
+impl NewSale {
+    pub fn from(sale: AddSale) -> Result<Self, ServerError> {
+        let sale_date = NaiveDate::parse_from_str(&sale.sale_date, "%Y-%m-%d");
+        if sale_date.is_err() {
+            return Err(ServerError::InvalidDate);
+        }
+
+        let mut invalid = Vec::new();
+        if sale.units < 0 {
+            invalid.push("units".to_string());
+        }
+        if sale.gross < 0f64 {
+            invalid.push("gross".to_string());
+        }
+        if sale.refunds < 0f64 || sale.refunds > sale.gross {
+            invalid.push("refunds".to_string());
+        }
+        if !invalid.is_empty() {
+            return Err(ServerError::InvalidValue(invalid));
+        }
+
+        Ok(NewSale {
+            game_id: sale.game_id,
+            platform: sale.platform,
+            sale_date: sale_date.unwrap(),
+            units: sale.units,
+            gross: PgMoney((sale.gross * 100f64) as i64),
+            refunds: PgMoney((sale.refunds * 100f64) as i64),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SalesControl {
+    pub id: i32,
+    pub version: i32,
+    pub game: String,
+    pub game_id: i32,
+    pub platform: String,
+    pub sale_date: String,
+    pub units: i32,
+    pub gross: f64,
+    pub refunds: f64,
+}
+
+impl SalesControl {
+    pub async fn make_sales_control(conn: &DBConnection, sales_struct: Sale) -> Self {
+        let game = GamesControl::get_game_by_id(conn, sales_struct.game_id)
+            .await
+            .unwrap()
+            .name;
+
+        let sale_date = sales_struct
+            .sale_date
+            .format(&config::formats().date)
+            .to_string();
+        SalesControl {
+            id: sales_struct.id,
+            version: sales_struct.version,
+            game,
+            game_id: sales_struct.game_id,
+            platform: sales_struct.platform,
+            sale_date,
+            units: sales_struct.units,
+            gross: sales_struct.gross.0 as f64 / 100f64,
+            refunds: sales_struct.refunds.0 as f64 / 100f64,
+        }
+    }
+
+    pub fn change_date_format(&mut self, from: &str, to: &str) -> Result<()> {
+        let sale_date = NaiveDate::parse_from_str(&self.sale_date, from)?;
+        self.sale_date = sale_date.format(to).to_string();
+        Ok(())
+    }
+
+    pub async fn get_sales(conn: &DBConnection) -> Result<Vec<SalesControl>> {
+        use crate::schema::sales::dsl::*;
+
+        let results = conn
+            .run(move |sql_conn| -> Result<Vec<Sale>> {
+                Ok(sales.order(id.asc()).load::<Sale>(sql_conn)?)
+            })
+            .await?;
+
+        let mut sales_result: Vec<SalesControl> = vec![];
+        for sale in results {
+            sales_result.push(SalesControl::make_sales_control(conn, sale).await);
+        }
+
+        Ok(sales_result)
+    }
+
+    pub async fn get_sale_by_id(conn: &DBConnection, id_for_lookup: i32) -> Result<SalesControl> {
+        use crate::schema::sales::dsl::*;
+
+        let sale = conn
+            .run(move |sql_conn| -> Result<Sale> {
+                let result: Sale = sales
+                    .filter(id.eq(id_for_lookup))
+                    .first(sql_conn)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                Ok(result)
+            })
+            .await?;
+
+        Ok(SalesControl::make_sales_control(conn, sale).await)
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for SalesControl {
+    type Form = AddSale;
+    type New = NewSale;
+    type Table = sales::table;
+    type Version = sales::version;
+
+    const NAME: &'static str = "sales";
+    const TABLE: &'static str = "Продажі";
+
+    fn parse(form: AddSale) -> Result<Self::New, ServerError> {
+        NewSale::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_sale_by_id(conn, id).await
+    }
+
+    fn cached(_id: Option<i32>) -> Vec<cache::Dependency> {
+        vec![("sales", None)]
+    }
+
+    /// Game choices.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let games = GamesControl::get_games(conn).await.unwrap();
+        let games_id = games.iter().map(|game| game.id.to_string()).collect();
+
+        let games_name = games.into_iter().map(|game| game.name).collect();
+
+        vec![games_id, games_name]
+    }
+
+    fn for_edit(&mut self) -> Result<()> {
+        self.change_date_format(&config::formats().date, "%Y-%m-%d")
+    }
+}
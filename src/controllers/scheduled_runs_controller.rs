@@ -0,0 +1,104 @@
+use crate::models::*;
+use crate::schema::scheduled_runs;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+
+const HISTORY_SIZE: i64 = 100;
+
+#[derive(Insertable)]
+#[table_name = "scheduled_runs"]
+struct NewScheduledRun {
+    task: String,
+    started_at: NaiveDateTime,
+    duration_ms: i64,
+    message: String,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScheduledRunsControl {
+    pub id: i32,
+    pub task: String,
+    pub started_at: String,
+    pub duration_ms: i64,
+    pub message: String,
+    pub error: Option<String>,
+}
+
+impl std::convert::From<ScheduledRun> for ScheduledRunsControl {
+    fn from(run: ScheduledRun) -> Self {
+        ScheduledRunsControl {
+            id: run.id,
+            task: run.task,
+            started_at: run.started_at.format("%d-%m-%Y, %H:%M:%S").to_string(),
+            duration_ms: run.duration_ms,
+            message: run.message,
+            error: run.error,
+        }
+    }
+}
+
+impl ScheduledRunsControl {
+    /// Saves the outcome of a task run: its summary on success, the error
+    /// otherwise.
+    pub async fn record(
+        conn: &DBConnection,
+        task: &str,
+        started_at: NaiveDateTime,
+        duration_ms: i64,
+        outcome: &Result<String>,
+    ) -> Result<()> {
+        let (message, error) = match outcome {
+            Ok(message) => (message.clone(), None),
+            Err(err) => (String::new(), Some(err.to_string())),
+        };
+        let run = NewScheduledRun {
+            task: task.to_string(),
+            started_at,
+            duration_ms,
+            message,
+            error,
+        };
+
+        conn.run(move |sql_conn| -> Result<()> {
+            diesel::insert_into(scheduled_runs::table)
+                .values(&run)
+                .execute(sql_conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// The latest runs of all tasks, newest first.
+    pub async fn get_history(conn: &DBConnection) -> Result<Vec<ScheduledRunsControl>> {
+        use crate::schema::scheduled_runs::dsl::*;
+
+        let runs = conn
+            .run(|sql_conn| -> Result<Vec<ScheduledRun>> {
+                Ok(scheduled_runs
+                    .order((started_at.desc(), id.desc()))
+                    .limit(HISTORY_SIZE)
+                    .load(sql_conn)?)
+            })
+            .await?;
+        Ok(runs.into_iter().map(ScheduledRunsControl::from).collect())
+    }
+
+    /// The last run of every task that has run at least once.
+    pub async fn get_last_runs(conn: &DBConnection) -> Result<Vec<ScheduledRunsControl>> {
+        use crate::schema::scheduled_runs::dsl::*;
+
+        let runs = conn
+            .run(|sql_conn| -> Result<Vec<ScheduledRun>> {
+                Ok(scheduled_runs
+                    .distinct_on(task)
+                    .order((task, started_at.desc(), id.desc()))
+                    .load(sql_conn)?)
+            })
+            .await?;
+        Ok(runs.into_iter().map(ScheduledRunsControl::from).collect())
+    }
+}
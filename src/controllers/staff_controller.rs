@@ -1,3 +1,4 @@
+use crate::config;
 use crate::controllers::*;
 use crate::errors::ServerError;
 use crate::models::*;
@@ -5,17 +6,25 @@ use crate::requests_handler::AddStaff;
 use crate::schema::staff;
 use crate::DBConnection;
 use anyhow::Result;
-use chrono::{Datelike, NaiveDate};
+use chrono::{Datelike, Local, NaiveDate};
 use diesel::pg::data_types::PgDate;
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::result::Error as DieselError;
 use serde::Serialize;
+use std::collections::HashMap;
 
-#[derive(Insertable)]
+#[derive(Insertable, AsChangeset)]
 #[table_name = "staff"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct NewStaff {
     pub name: String,
     pub birth: PgDate,
+    pub department_id: Option<i32>,
+    pub job_title: String,
+    pub manager_id: Option<i32>,
+    pub employment_start: Option<NaiveDate>,
+    pub email: String,
+    pub phone: String,
 }
 
 impl NewStaff {
@@ -30,37 +39,155 @@ impl NewStaff {
             birth.unwrap().day(),
         );
 
+        let employment_start = match staff.employment_start.as_deref() {
+            None | Some("") => None,
+            Some(employment_start) => Some(
+                NaiveDate::parse_from_str(employment_start, "%Y-%m-%d")
+                    .map_err(|_| ServerError::InvalidDate)?,
+            ),
+        };
+
+        let email = staff.email.trim().to_string();
+        if !email.is_empty() && !email.contains('@') {
+            return Err(ServerError::InvalidValue(vec!["email".to_string()]));
+        }
+
         Ok(NewStaff {
             name: staff.name,
             birth: PgDate(birth.num_days_from_ce()),
+            department_id: staff.department_id,
+            job_title: staff.job_title.trim().to_string(),
+            manager_id: staff.manager_id,
+            employment_start,
+            email,
+            phone: staff.phone.trim().to_string(),
         })
     }
 }
 #[derive(Serialize, Debug)]
 pub struct StaffControl {
     pub id: i32,
+    pub version: i32,
     pub name: String,
     pub birth: String,
+    pub department_id: Option<i32>,
+    pub department: String,
+    pub job_title: String,
+    pub manager_id: Option<i32>,
+    pub manager: String,
+    pub employment_start: String,
+    pub employment_end: String,
+    pub is_employed: bool,
+    pub email: String,
+    pub phone: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OrgChartNode {
+    pub id: i32,
+    pub name: String,
+    pub job_title: String,
+    pub department: String,
+    pub subordinates: Vec<OrgChartNode>,
 }
 
 impl std::convert::From<Staff> for StaffControl {
     fn from(staff_struct: Staff) -> Self {
         let birth = NaiveDate::from_num_days_from_ce(staff_struct.birth.0);
         let birth = NaiveDate::from_ymd(birth.year() + 1999, birth.month(), birth.day());
-        let birth = birth.format("%d-%m-%Y").to_string();
+        let birth = birth.format(&config::formats().date).to_string();
+        let is_employed = StaffControl::is_employed(&staff_struct);
+        let format_date = |date: Option<NaiveDate>| {
+            date.map(|date| date.format(&config::formats().date).to_string())
+                .unwrap_or_default()
+        };
         StaffControl {
             id: staff_struct.id,
+            version: staff_struct.version,
             name: staff_struct.name,
             birth: birth,
+            department_id: staff_struct.department_id,
+            department: String::new(),
+            job_title: staff_struct.job_title,
+            manager_id: staff_struct.manager_id,
+            manager: String::new(),
+            employment_start: format_date(staff_struct.employment_start),
+            employment_end: format_date(staff_struct.employment_end),
+            is_employed,
+            email: staff_struct.email,
+            phone: staff_struct.phone,
         }
     }
 }
 
 impl StaffControl {
-    pub async fn get_statistic(conn: &DBConnection, id_for_lookup: i32) -> (i32, Vec<JobsControl>) {
+    pub fn is_employed(staff_struct: &Staff) -> bool {
+        let today = Local::today().naive_local();
+        staff_struct.employment_start.is_some()
+            && staff_struct.employment_end.is_none_or(|end| end >= today)
+    }
+
+    /// Fills department and manager names in from the given lookups.
+    fn with_names(
+        mut staff_control: StaffControl,
+        departments_by_id: &HashMap<i32, String>,
+        names_by_id: &HashMap<i32, String>,
+    ) -> StaffControl {
+        if let Some(department_id) = staff_control.department_id {
+            staff_control.department = departments_by_id
+                .get(&department_id)
+                .cloned()
+                .unwrap_or_default();
+        }
+        if let Some(manager_id) = staff_control.manager_id {
+            staff_control.manager = names_by_id.get(&manager_id).cloned().unwrap_or_default();
+        }
+        staff_control
+    }
+
+    fn load_with_departments(sql_conn: &PgConnection) -> Result<(Vec<Staff>, Vec<Department>)> {
+        use crate::schema::departments;
+        use crate::schema::staff::dsl::*;
+
+        let all_staff = staff.order(id.asc()).load::<Staff>(sql_conn)?;
+        let all_departments = departments::table.load::<Department>(sql_conn)?;
+        Ok((all_staff, all_departments))
+    }
+
+    /// Rejects a manager that is the employee or one of the employee's
+    /// subordinates, which would turn the hierarchy into a loop.
+    fn check_manager(
+        sql_conn: &PgConnection,
+        id_for_check: i32,
+        new_manager_id: Option<i32>,
+    ) -> Result<()> {
+        use crate::schema::staff::dsl::*;
+
+        let mut next = new_manager_id;
+        while let Some(current) = next {
+            if current == id_for_check {
+                return Err(ServerError::ManagerCycle.into());
+            }
+            next = staff
+                .select(manager_id)
+                .filter(id.eq(current))
+                .first::<Option<i32>>(sql_conn)
+                .optional()?
+                .flatten();
+        }
+        Ok(())
+    }
+
+    pub async fn get_statistic(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+    ) -> (i32, Vec<JobsControl>, Vec<SalaryChangesControl>) {
         (
             id_for_lookup,
             StaffControl::get_jobs(conn, id_for_lookup).await,
+            JobsControl::get_salary_timeline(conn, id_for_lookup)
+                .await
+                .unwrap(),
         )
     }
 
@@ -93,15 +220,29 @@ impl StaffControl {
     }
 
     pub async fn get_staff(conn: &DBConnection) -> Result<Vec<StaffControl>> {
-        use crate::schema::staff::dsl::*;
-
-        let results = conn
-            .run(move |sql_conn| -> Result<Vec<Staff>> {
-                Ok(staff.order(id.asc()).load::<Staff>(sql_conn)?)
-            })
+        let (results, all_departments) = conn
+            .run(move |sql_conn| StaffControl::load_with_departments(sql_conn))
             .await?;
 
-        Ok(results.into_iter().map(StaffControl::from).collect())
+        let departments_by_id: HashMap<i32, String> = all_departments
+            .into_iter()
+            .map(|department| (department.id, department.name))
+            .collect();
+        let names_by_id: HashMap<i32, String> = results
+            .iter()
+            .map(|member| (member.id, member.name.clone()))
+            .collect();
+
+        Ok(results
+            .into_iter()
+            .map(|member| {
+                StaffControl::with_names(
+                    StaffControl::from(member),
+                    &departments_by_id,
+                    &names_by_id,
+                )
+            })
+            .collect())
     }
 
     pub async fn get_staff_by_id(conn: &DBConnection, id_for_lookup: i32) -> Result<StaffControl> {
@@ -117,20 +258,135 @@ impl StaffControl {
         .await
     }
 
-    pub async fn add_staff(conn: &DBConnection, new_staff: NewStaff) -> Result<()> {
+    /// Currently employed staff arranged under their managers. People whose
+    /// manager has left the studio are shown at the top level.
+    pub async fn get_org_chart(conn: &DBConnection) -> Result<Vec<OrgChartNode>> {
+        let (results, all_departments) = conn
+            .run(move |sql_conn| StaffControl::load_with_departments(sql_conn))
+            .await?;
+
+        let departments_by_id: HashMap<i32, String> = all_departments
+            .into_iter()
+            .map(|department| (department.id, department.name))
+            .collect();
+        let employed: Vec<Staff> = results
+            .into_iter()
+            .filter(StaffControl::is_employed)
+            .collect();
+        let employed_ids: Vec<i32> = employed.iter().map(|member| member.id).collect();
+
+        let mut subordinates_of: HashMap<Option<i32>, Vec<&Staff>> = HashMap::new();
+        for member in employed.iter() {
+            let manager = member
+                .manager_id
+                .filter(|manager_id| employed_ids.contains(manager_id));
+            subordinates_of.entry(manager).or_default().push(member);
+        }
+
+        fn build(
+            manager: Option<i32>,
+            subordinates_of: &HashMap<Option<i32>, Vec<&Staff>>,
+            departments_by_id: &HashMap<i32, String>,
+        ) -> Vec<OrgChartNode> {
+            subordinates_of
+                .get(&manager)
+                .map(|members| {
+                    members
+                        .iter()
+                        .map(|member| OrgChartNode {
+                            id: member.id,
+                            name: member.name.clone(),
+                            job_title: member.job_title.clone(),
+                            department: member
+                                .department_id
+                                .and_then(|department_id| departments_by_id.get(&department_id))
+                                .cloned()
+                                .unwrap_or_default(),
+                            subordinates: build(
+                                Some(member.id),
+                                subordinates_of,
+                                departments_by_id,
+                            ),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+
+        Ok(build(None, &subordinates_of, &departments_by_id))
+    }
+
+    pub async fn hire(conn: &DBConnection, id_for_update: i32, start: NaiveDate) -> Result<()> {
         use crate::schema::staff::dsl::*;
 
         conn.run(move |sql_connection| -> Result<()> {
-            diesel::insert_into(staff)
-                .values(&new_staff)
-                .get_result::<Staff>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let member: Staff = staff
+                    .filter(id.eq(id_for_update))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                if StaffControl::is_employed(&member) {
+                    return Err(ServerError::AlreadyEmployed(member.name).into());
+                }
+                if matches!(member.employment_end, Some(end) if start <= end) {
+                    return Err(ServerError::InvalidDate.into());
+                }
+
+                diesel::update(staff.filter(id.eq(id_for_update)))
+                    .set((
+                        employment_start.eq(Some(start)),
+                        employment_end.eq(None::<NaiveDate>),
+                        version.eq(version + 1),
+                    ))
+                    .execute(sql_connection)?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Terminates the employment on `end` and closes the jobs still open on
+    /// that day. Jobs that were planned to start later are removed.
+    pub async fn fire(conn: &DBConnection, id_for_update: i32, end: NaiveDate) -> Result<()> {
+        use crate::schema::jobs;
+        use crate::schema::staff::dsl::*;
+
+        conn.run(move |sql_connection| -> Result<()> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let member: Staff = staff
+                    .filter(id.eq(id_for_update))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                if !StaffControl::is_employed(&member) {
+                    return Err(ServerError::NotEmployed(member.name).into());
+                }
+                if matches!(member.employment_start, Some(start) if end < start) {
+                    return Err(ServerError::InvalidDate.into());
+                }
+
+                diesel::update(staff.filter(id.eq(id_for_update)))
+                    .set((employment_end.eq(Some(end)), version.eq(version + 1)))
+                    .execute(sql_connection)?;
+
+                let member_jobs = jobs::table.filter(jobs::staff_id.eq(id_for_update));
+                diesel::delete(member_jobs.filter(jobs::first_work_day.gt(end)))
+                    .execute(sql_connection)?;
+                diesel::update(
+                    member_jobs.filter(
+                        jobs::last_work_day
+                            .is_null()
+                            .or(jobs::last_work_day.gt(end)),
+                    ),
+                )
+                .set((
+                    jobs::last_work_day.eq(Some(end)),
+                    jobs::version.eq(jobs::version + 1),
+                ))
+                .execute(sql_connection)?;
+                Ok(())
+            })
         })
         .await
     }
@@ -138,35 +394,82 @@ impl StaffControl {
     pub async fn update_staff(
         conn: &DBConnection,
         id_for_update: i32,
+        version_for_update: i32,
         new_staff: NewStaff,
     ) -> Result<()> {
         use crate::schema::staff::dsl::*;
 
         conn.run(move |sql_connection| -> Result<()> {
-            diesel::update(staff.filter(&id.eq(id_for_update)))
-                .set((name.eq(new_staff.name), birth.eq(new_staff.birth)))
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                StaffControl::check_manager(sql_connection, id_for_update, new_staff.manager_id)?;
+                diesel::update(
+                    staff
+                        .filter(&id.eq(id_for_update))
+                        .filter(version.eq(version_for_update)),
+                )
+                .set((
+                    version.eq(version + 1),
+                    name.eq(new_staff.name),
+                    birth.eq(new_staff.birth),
+                    department_id.eq(new_staff.department_id),
+                    job_title.eq(new_staff.job_title),
+                    manager_id.eq(new_staff.manager_id),
+                    email.eq(new_staff.email),
+                    phone.eq(new_staff.phone),
+                ))
                 .get_result::<Staff>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
+                .map_err(write_error)?;
+                Ok(())
+            })
         })
         .await
     }
+}
 
-    pub async fn delete_staff(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
-        use crate::schema::staff::dsl::*;
+#[rocket::async_trait]
+impl Resource for StaffControl {
+    type Form = AddStaff;
+    type New = NewStaff;
+    type Table = staff::table;
+    type Version = staff::version;
 
-        conn.run(move |sql_conn| -> Result<()> {
-            diesel::delete(staff)
-                .filter(&id.eq(id_for_delete))
-                .get_result::<Staff>(sql_conn)
-                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(())
-        })
-        .await
+    const NAME: &'static str = "staff";
+    const TABLE: &'static str = "Працівники";
+
+    fn parse(form: AddStaff) -> Result<Self::New, ServerError> {
+        NewStaff::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_staff_by_id(conn, id).await
+    }
+
+    async fn update(conn: &DBConnection, id: i32, version: i32, new: Self::New) -> Result<()> {
+        Self::update_staff(conn, id, version, new).await
+    }
+
+    /// Department and manager choices.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let departments = DepartmentsControl::get_departments(conn).await.unwrap();
+        let departments_id = departments
+            .iter()
+            .map(|department| department.id.to_string())
+            .collect();
+
+        let departments_name = departments
+            .into_iter()
+            .map(|department| department.name)
+            .collect();
+
+        let staff = Self::get_staff(conn).await.unwrap();
+        let staff_id = staff.iter().map(|member| member.id.to_string()).collect();
+
+        let staff_name = staff.into_iter().map(|member| member.name).collect();
+
+        vec![departments_id, departments_name, staff_id, staff_name]
+    }
+
+    fn for_edit(&mut self) -> Result<()> {
+        self.change_date_format(&config::formats().date, "%Y-%m-%d")
     }
 }
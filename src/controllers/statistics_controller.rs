@@ -0,0 +1,453 @@
+use crate::config;
+use crate::models::*;
+use crate::schema::{donation_daily_stats, donation_user_stats, games, investment_stats};
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::NaiveDate;
+use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Insertable)]
+#[table_name = "donation_daily_stats"]
+struct NewDonationDailyStats {
+    game_id: i32,
+    day: NaiveDate,
+    donations_count: i32,
+    amount: PgMoney,
+}
+
+#[derive(Insertable)]
+#[table_name = "donation_user_stats"]
+struct NewDonationUserStats {
+    game_id: i32,
+    user_id: i32,
+    donations_count: i32,
+    amount: PgMoney,
+}
+
+#[derive(Insertable)]
+#[table_name = "investment_stats"]
+struct NewInvestmentStats {
+    game_id: i32,
+    investments_count: i32,
+    invested: PgMoney,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DailyDonationsControl {
+    pub day: String,
+    pub donations_count: i32,
+    pub amount: f64,
+}
+
+/// Size of the studio for `/metrics`. The sum donated is net of refunds.
+#[derive(Debug)]
+pub struct StudioTotals {
+    pub games: i64,
+    pub users: i64,
+    pub donations_today: i64,
+    pub donated_today: f64,
+}
+
+/// Count and sum in cents of the rows behind one aggregate row.
+type Tally = (i32, i64);
+
+/// Aggregates of every game, keyed by game and then by day, user or nothing.
+#[derive(Default, Debug)]
+struct Aggregates {
+    daily: BTreeMap<(i32, NaiveDate), Tally>,
+    by_user: BTreeMap<(i32, i32), Tally>,
+    invested: BTreeMap<i32, Tally>,
+}
+
+fn add(tally: &mut Tally, cents: i64) {
+    tally.0 += 1;
+    tally.1 += cents;
+}
+
+fn money(cents: i64) -> String {
+    format!(
+        "{:.2} {}",
+        cents as f64 / 100f64,
+        config::formats().currency
+    )
+}
+
+/// Lines describing the rows where the stored aggregates differ from the
+/// ones computed from the raw tables.
+fn compare<K: Ord + Copy>(
+    table: &str,
+    stored: &BTreeMap<K, Tally>,
+    computed: &BTreeMap<K, Tally>,
+    describe: impl Fn(K) -> String,
+) -> Vec<String> {
+    let keys: BTreeSet<K> = stored.keys().chain(computed.keys()).copied().collect();
+    let show = |tally: Option<&Tally>| match tally {
+        Some((count, cents)) => format!("{} на {}", count, money(*cents)),
+        None => "немає".to_string(),
+    };
+
+    keys.into_iter()
+        .filter(|key| stored.get(key) != computed.get(key))
+        .map(|key| {
+            format!(
+                "{}, {}: збережено {}, має бути {}",
+                table,
+                describe(key),
+                show(stored.get(&key)),
+                show(computed.get(&key))
+            )
+        })
+        .collect()
+}
+
+/// Materialized aggregates of donations and investments. Donation amounts are
+/// net of refunds. The rows of a game are rebuilt whenever its donations,
+/// refunds or investments change, and `refresh_all` rebuilds everything.
+pub struct StatisticsControl;
+
+impl StatisticsControl {
+    /// Aggregates computed from the raw tables, for one game or for all.
+    fn compute(sql_conn: &PgConnection, game: Option<i32>) -> Result<Aggregates> {
+        use crate::schema::{donation_refunds, donations, investments};
+
+        let mut donations_query = donations::table.into_boxed();
+        let mut refunds_query = donation_refunds::table
+            .inner_join(donations::table)
+            .select((donation_refunds::donation_id, donation_refunds::amount))
+            .into_boxed();
+        let mut investments_query = investments::table.into_boxed();
+        if let Some(game) = game {
+            donations_query = donations_query.filter(donations::game_id.eq(game));
+            refunds_query = refunds_query.filter(donations::game_id.eq(game));
+            investments_query = investments_query.filter(investments::game_id.eq(game));
+        }
+        let all_donations: Vec<Donation> = donations_query.load(sql_conn)?;
+        let refunds: Vec<(i32, PgMoney)> = refunds_query.load(sql_conn)?;
+        let all_investments: Vec<Investment> = investments_query.load(sql_conn)?;
+
+        let mut refunded: HashMap<i32, i64> = HashMap::new();
+        for (donation_id, amount) in refunds {
+            *refunded.entry(donation_id).or_default() += amount.0;
+        }
+
+        let mut aggregates = Aggregates::default();
+        for donation in all_donations {
+            let net = donation.amount.0 - refunded.get(&donation.id).copied().unwrap_or_default();
+            let day = donation.donation_time.date();
+            add(
+                aggregates.daily.entry((donation.game_id, day)).or_default(),
+                net,
+            );
+            add(
+                aggregates
+                    .by_user
+                    .entry((donation.game_id, donation.user_id))
+                    .or_default(),
+                net,
+            );
+        }
+        for investment in all_investments {
+            add(
+                aggregates.invested.entry(investment.game_id).or_default(),
+                investment.invested.0,
+            );
+        }
+        Ok(aggregates)
+    }
+
+    /// Aggregates as they are stored.
+    fn stored(sql_conn: &PgConnection) -> Result<Aggregates> {
+        let daily: Vec<DonationDailyStats> = donation_daily_stats::table.load(sql_conn)?;
+        let by_user: Vec<DonationUserStats> = donation_user_stats::table.load(sql_conn)?;
+        let invested: Vec<InvestmentStats> = investment_stats::table.load(sql_conn)?;
+
+        Ok(Aggregates {
+            daily: daily
+                .into_iter()
+                .map(|row| ((row.game_id, row.day), (row.donations_count, row.amount.0)))
+                .collect(),
+            by_user: by_user
+                .into_iter()
+                .map(|row| {
+                    (
+                        (row.game_id, row.user_id),
+                        (row.donations_count, row.amount.0),
+                    )
+                })
+                .collect(),
+            invested: invested
+                .into_iter()
+                .map(|row| (row.game_id, (row.investments_count, row.invested.0)))
+                .collect(),
+        })
+    }
+
+    fn write(sql_conn: &PgConnection, aggregates: Aggregates) -> Result<()> {
+        let daily: Vec<NewDonationDailyStats> = aggregates
+            .daily
+            .into_iter()
+            .map(
+                |((game_id, day), (donations_count, amount))| NewDonationDailyStats {
+                    game_id,
+                    day,
+                    donations_count,
+                    amount: PgMoney(amount),
+                },
+            )
+            .collect();
+        let by_user: Vec<NewDonationUserStats> = aggregates
+            .by_user
+            .into_iter()
+            .map(
+                |((game_id, user_id), (donations_count, amount))| NewDonationUserStats {
+                    game_id,
+                    user_id,
+                    donations_count,
+                    amount: PgMoney(amount),
+                },
+            )
+            .collect();
+        let invested: Vec<NewInvestmentStats> = aggregates
+            .invested
+            .into_iter()
+            .map(
+                |(game_id, (investments_count, invested))| NewInvestmentStats {
+                    game_id,
+                    investments_count,
+                    invested: PgMoney(invested),
+                },
+            )
+            .collect();
+
+        diesel::insert_into(donation_daily_stats::table)
+            .values(&daily)
+            .execute(sql_conn)?;
+        diesel::insert_into(donation_user_stats::table)
+            .values(&by_user)
+            .execute(sql_conn)?;
+        diesel::insert_into(investment_stats::table)
+            .values(&invested)
+            .execute(sql_conn)?;
+        Ok(())
+    }
+
+    /// Rebuilds the aggregates of one game. Called in the transaction of
+    /// every write to its donations, refunds or investments.
+    ///
+    /// The row of the game stays locked until that transaction ends, so two
+    /// writes to the same game rebuild its aggregates one after the other and
+    /// the second one sees the rows of the first. The lock is `FOR NO KEY
+    /// UPDATE`: the key share lock that inserting a donation or an investment
+    /// takes on the game must not block it, or two such writes would deadlock.
+    pub fn refresh_game(sql_conn: &PgConnection, game: i32) -> Result<()> {
+        games::table
+            .filter(games::id.eq(game))
+            .select(games::id)
+            .for_no_key_update()
+            .load::<i32>(sql_conn)?;
+        let aggregates = StatisticsControl::compute(sql_conn, Some(game))?;
+
+        diesel::delete(donation_daily_stats::table.filter(donation_daily_stats::game_id.eq(game)))
+            .execute(sql_conn)?;
+        diesel::delete(donation_user_stats::table.filter(donation_user_stats::game_id.eq(game)))
+            .execute(sql_conn)?;
+        diesel::delete(investment_stats::table.filter(investment_stats::game_id.eq(game)))
+            .execute(sql_conn)?;
+        StatisticsControl::write(sql_conn, aggregates)
+    }
+
+    /// Rebuilds all aggregates from the raw tables, with every game locked
+    /// as in `refresh_game`.
+    pub fn refresh_all(sql_conn: &PgConnection) -> Result<()> {
+        sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            games::table
+                .select(games::id)
+                .for_no_key_update()
+                .load::<i32>(sql_conn)?;
+            let aggregates = StatisticsControl::compute(sql_conn, None)?;
+
+            diesel::delete(donation_daily_stats::table).execute(sql_conn)?;
+            diesel::delete(donation_user_stats::table).execute(sql_conn)?;
+            diesel::delete(investment_stats::table).execute(sql_conn)?;
+            StatisticsControl::write(sql_conn, aggregates)
+        })
+    }
+
+    /// Compares the stored aggregates to the raw tables and describes every
+    /// row that differs. An empty list means they are consistent.
+    pub fn check(sql_conn: &PgConnection) -> Result<Vec<String>> {
+        let (stored, computed) = sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            Ok((
+                StatisticsControl::stored(sql_conn)?,
+                StatisticsControl::compute(sql_conn, None)?,
+            ))
+        })?;
+
+        let mut mismatches = compare(
+            "Донати по днях",
+            &stored.daily,
+            &computed.daily,
+            |(game, day)| format!("гра #{}, {}", game, day.format(&config::formats().date)),
+        );
+        mismatches.extend(compare(
+            "Донати користувачів",
+            &stored.by_user,
+            &computed.by_user,
+            |(game, user)| format!("гра #{}, користувач #{}", game, user),
+        ));
+        mismatches.extend(compare(
+            "Інвестиції",
+            &stored.invested,
+            &computed.invested,
+            |game| format!("гра #{}", game),
+        ));
+        Ok(mismatches)
+    }
+
+    /// Net donations of every game, in cents.
+    pub fn donated_by_game(sql_conn: &PgConnection) -> Result<HashMap<i32, i64>> {
+        let rows: Vec<(i32, PgMoney)> = donation_user_stats::table
+            .select((donation_user_stats::game_id, donation_user_stats::amount))
+            .load(sql_conn)?;
+
+        let mut donated: HashMap<i32, i64> = HashMap::new();
+        for (game_id, amount) in rows {
+            *donated.entry(game_id).or_default() += amount.0;
+        }
+        Ok(donated)
+    }
+
+    /// Net donations of every game made between `from` and `to` inclusive,
+    /// in cents.
+    pub fn donated_by_game_between(
+        sql_conn: &PgConnection,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<HashMap<i32, i64>> {
+        let rows: Vec<(i32, PgMoney)> = donation_daily_stats::table
+            .filter(donation_daily_stats::day.between(from, to))
+            .select((donation_daily_stats::game_id, donation_daily_stats::amount))
+            .load(sql_conn)?;
+
+        let mut donated: HashMap<i32, i64> = HashMap::new();
+        for (game_id, amount) in rows {
+            *donated.entry(game_id).or_default() += amount.0;
+        }
+        Ok(donated)
+    }
+
+    pub fn donated_between(
+        sql_conn: &PgConnection,
+        game: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<i64> {
+        let amounts: Vec<PgMoney> = donation_daily_stats::table
+            .filter(donation_daily_stats::game_id.eq(game))
+            .filter(donation_daily_stats::day.between(from, to))
+            .select(donation_daily_stats::amount)
+            .load(sql_conn)?;
+
+        Ok(amounts.iter().map(|amount| amount.0).sum())
+    }
+
+    /// Net donations of every user to the game, biggest first.
+    pub async fn get_game_donors(conn: &DBConnection, game: i32) -> Result<Vec<(String, f64)>> {
+        use crate::schema::users;
+
+        let rows = conn
+            .run(move |sql_conn| -> Result<Vec<(String, PgMoney)>> {
+                Ok(donation_user_stats::table
+                    .inner_join(users::table)
+                    .filter(donation_user_stats::game_id.eq(game))
+                    .select((users::nickname, donation_user_stats::amount))
+                    .order((donation_user_stats::amount.desc(), users::nickname.asc()))
+                    .load(sql_conn)?)
+            })
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(nickname, amount)| (nickname, amount.0 as f64 / 100f64))
+            .collect())
+    }
+
+    /// Net donations of the user to all games.
+    pub async fn get_user_total(conn: &DBConnection, user: i32) -> Result<f64> {
+        let amounts = conn
+            .run(move |sql_conn| -> Result<Vec<PgMoney>> {
+                Ok(donation_user_stats::table
+                    .filter(donation_user_stats::user_id.eq(user))
+                    .select(donation_user_stats::amount)
+                    .load(sql_conn)?)
+            })
+            .await?;
+
+        Ok(amounts.iter().map(|amount| amount.0).sum::<i64>() as f64 / 100f64)
+    }
+
+    pub async fn get_daily_donations(
+        conn: &DBConnection,
+        game: i32,
+    ) -> Result<Vec<DailyDonationsControl>> {
+        let rows = conn
+            .run(move |sql_conn| -> Result<Vec<DonationDailyStats>> {
+                Ok(donation_daily_stats::table
+                    .filter(donation_daily_stats::game_id.eq(game))
+                    .order(donation_daily_stats::day.asc())
+                    .load(sql_conn)?)
+            })
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DailyDonationsControl {
+                day: row.day.format(&config::formats().date).to_string(),
+                donations_count: row.donations_count,
+                amount: row.amount.0 as f64 / 100f64,
+            })
+            .collect())
+    }
+
+    pub async fn get_totals(conn: &DBConnection, today: NaiveDate) -> Result<StudioTotals> {
+        use crate::schema::{games, users};
+
+        conn.run(move |sql_conn| -> Result<StudioTotals> {
+            let today_stats: Vec<(i32, PgMoney)> = donation_daily_stats::table
+                .filter(donation_daily_stats::day.eq(today))
+                .select((
+                    donation_daily_stats::donations_count,
+                    donation_daily_stats::amount,
+                ))
+                .load(sql_conn)?;
+            Ok(StudioTotals {
+                games: games::table.count().get_result(sql_conn)?,
+                users: users::table.count().get_result(sql_conn)?,
+                donations_today: today_stats.iter().map(|(count, _)| *count as i64).sum(),
+                donated_today: today_stats.iter().map(|(_, amount)| amount.0).sum::<i64>() as f64
+                    / 100f64,
+            })
+        })
+        .await
+    }
+
+    /// Number of investments into the game and their sum.
+    pub async fn get_invested(conn: &DBConnection, game: i32) -> Result<(i32, f64)> {
+        let row = conn
+            .run(move |sql_conn| -> Result<Option<InvestmentStats>> {
+                Ok(investment_stats::table
+                    .filter(investment_stats::game_id.eq(game))
+                    .first(sql_conn)
+                    .optional()?)
+            })
+            .await?;
+
+        Ok(row.map_or((0, 0f64), |row| {
+            (row.investments_count, row.invested.0 as f64 / 100f64)
+        }))
+    }
+}
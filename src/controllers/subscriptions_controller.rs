@@ -0,0 +1,396 @@
+use crate::config;
+use crate::controllers::write_error;
+use crate::controllers::GamesControl;
+use crate::controllers::Resource;
+use crate::controllers::UsersControl;
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddSubscription;
+use crate::schema::subscriptions;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Datelike, Local, NaiveDate};
+use diesel::dsl::sql;
+use diesel::pg::data_types::PgMoney;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{Nullable, Timestamp};
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubscriptionPlan {
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl SubscriptionPlan {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionPlan::Monthly => "monthly",
+            SubscriptionPlan::Quarterly => "quarterly",
+            SubscriptionPlan::Yearly => "yearly",
+        }
+    }
+
+    pub fn parse(plan: &str) -> Result<Self, ServerError> {
+        match plan {
+            "monthly" => Ok(SubscriptionPlan::Monthly),
+            "quarterly" => Ok(SubscriptionPlan::Quarterly),
+            "yearly" => Ok(SubscriptionPlan::Yearly),
+            _ => Err(ServerError::InvalidValue(vec!["plan".to_string()])),
+        }
+    }
+
+    pub fn months(&self) -> u32 {
+        match self {
+            SubscriptionPlan::Monthly => 1,
+            SubscriptionPlan::Quarterly => 3,
+            SubscriptionPlan::Yearly => 12,
+        }
+    }
+
+    /// End of the period paid for `periods` times in a row starting at `start_date`.
+    pub fn paid_until(&self, start_date: NaiveDate, periods: i32) -> NaiveDate {
+        let months = start_date.month0() + self.months() * periods.max(0) as u32;
+        let year = start_date.year() + (months / 12) as i32;
+        let month = months % 12 + 1;
+
+        // Clamp to the last day of shorter months, e.g. 31 January + 1 month.
+        let mut day = start_date.day();
+        loop {
+            if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
+                return date;
+            }
+            day -= 1;
+        }
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "subscriptions"]
+pub struct NewSubscription {
+    pub user_id: i32,
+    pub game_id: i32,
+    pub plan: String,
+    pub price: PgMoney,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+impl NewSubscription {
+    pub fn from(subscription: AddSubscription) -> Result<Self, ServerError> {
+        let start_date = NaiveDate::parse_from_str(&subscription.start_date, "%Y-%m-%d");
+        if start_date.is_err() {
+            return Err(ServerError::InvalidDate);
+        }
+        let start_date = start_date.unwrap();
+
+        let plan = SubscriptionPlan::parse(&subscription.plan)?;
+        if subscription.price < 0f64 {
+            return Err(ServerError::InvalidValue(vec!["price".to_string()]));
+        }
+
+        Ok(NewSubscription {
+            user_id: subscription.user_id,
+            game_id: subscription.game_id,
+            plan: plan.as_str().to_string(),
+            price: PgMoney((subscription.price * 100f64) as i64),
+            start_date,
+            end_date: plan.paid_until(start_date, 1),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SubscriptionsControl {
+    pub id: i32,
+    pub version: i32,
+    pub user: String,
+    pub user_id: i32,
+    pub game: String,
+    pub game_id: i32,
+    pub plan: String,
+    pub price: f64,
+    pub start_date: String,
+    pub end_date: String,
+    pub periods_paid: i32,
+    pub is_active: bool,
+    pub is_cancelled: bool,
+}
+
+impl SubscriptionsControl {
+    pub async fn make_subscriptions_control(
+        conn: &DBConnection,
+        subscriptions_struct: Subscription,
+    ) -> Self {
+        let game = GamesControl::get_game_by_id(conn, subscriptions_struct.game_id)
+            .await
+            .unwrap()
+            .name;
+        let user = UsersControl::get_user_by_id(conn, subscriptions_struct.user_id)
+            .await
+            .unwrap()
+            .nickname;
+
+        let today = Local::today().naive_local();
+        let is_active =
+            subscriptions_struct.start_date <= today && today <= subscriptions_struct.end_date;
+
+        SubscriptionsControl {
+            id: subscriptions_struct.id,
+            version: subscriptions_struct.version,
+            user,
+            user_id: subscriptions_struct.user_id,
+            game,
+            game_id: subscriptions_struct.game_id,
+            plan: subscriptions_struct.plan,
+            price: subscriptions_struct.price.0 as f64 / 100f64,
+            start_date: subscriptions_struct
+                .start_date
+                .format(&config::formats().date)
+                .to_string(),
+            end_date: subscriptions_struct
+                .end_date
+                .format(&config::formats().date)
+                .to_string(),
+            periods_paid: subscriptions_struct.periods_paid,
+            is_active,
+            is_cancelled: subscriptions_struct.cancelled_at.is_some(),
+        }
+    }
+
+    pub fn change_date_format(&mut self, from: &str, to: &str) -> Result<()> {
+        let start_date = NaiveDate::parse_from_str(&self.start_date, from)?;
+        self.start_date = start_date.format(to).to_string();
+        let end_date = NaiveDate::parse_from_str(&self.end_date, from)?;
+        self.end_date = end_date.format(to).to_string();
+        Ok(())
+    }
+
+    /// Monthly recurring revenue of a single subscription.
+    pub fn monthly_revenue(subscription: &Subscription) -> f64 {
+        let months = SubscriptionPlan::parse(&subscription.plan)
+            .map(|plan| plan.months())
+            .unwrap_or(1);
+        subscription.price.0 as f64 / 100f64 / months as f64
+    }
+
+    fn check_subscribable(sql_conn: &PgConnection, game_id_for_lookup: i32) -> Result<()> {
+        use crate::schema::games::dsl::*;
+
+        let game: Game = games
+            .filter(id.eq(game_id_for_lookup))
+            .first(sql_conn)
+            .map_err(|_| ServerError::InvalidForeignKey("game id".to_string()))?;
+        if !game.is_subscribable {
+            return Err(ServerError::NotSubscribable(game.name).into());
+        }
+        Ok(())
+    }
+
+    pub async fn get_subscriptions(conn: &DBConnection) -> Result<Vec<SubscriptionsControl>> {
+        use crate::schema::subscriptions::dsl::*;
+
+        let results = conn
+            .run(move |sql_conn| -> Result<Vec<Subscription>> {
+                Ok(subscriptions
+                    .order(id.asc())
+                    .load::<Subscription>(sql_conn)?)
+            })
+            .await?;
+
+        let mut subscriptions_result: Vec<SubscriptionsControl> = vec![];
+        for subscription in results {
+            subscriptions_result
+                .push(SubscriptionsControl::make_subscriptions_control(conn, subscription).await);
+        }
+
+        Ok(subscriptions_result)
+    }
+
+    pub async fn get_subscription_by_id(
+        conn: &DBConnection,
+        id_for_lookup: i32,
+    ) -> Result<SubscriptionsControl> {
+        use crate::schema::subscriptions::dsl::*;
+
+        let subscription = conn
+            .run(move |sql_conn| -> Result<Subscription> {
+                let result: Subscription = subscriptions
+                    .filter(id.eq(id_for_lookup))
+                    .first(sql_conn)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                Ok(result)
+            })
+            .await?;
+
+        Ok(SubscriptionsControl::make_subscriptions_control(conn, subscription).await)
+    }
+
+    pub async fn update_subscription(
+        conn: &DBConnection,
+        id_for_update: i32,
+        version_for_update: i32,
+        subscription: NewSubscription,
+    ) -> Result<()> {
+        use crate::schema::subscriptions::dsl::*;
+
+        conn.run(move |sql_connection| -> Result<()> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                SubscriptionsControl::check_subscribable(sql_connection, subscription.game_id)?;
+                let current: Subscription = subscriptions
+                    .filter(id.eq(id_for_update))
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                let new_end_date = SubscriptionPlan::parse(&subscription.plan)?
+                    .paid_until(subscription.start_date, current.periods_paid);
+
+                diesel::update(
+                    subscriptions
+                        .filter(&id.eq(id_for_update))
+                        .filter(version.eq(version_for_update)),
+                )
+                .set((
+                    version.eq(version + 1),
+                    user_id.eq(subscription.user_id),
+                    game_id.eq(subscription.game_id),
+                    plan.eq(&subscription.plan),
+                    price.eq(subscription.price),
+                    start_date.eq(subscription.start_date),
+                    end_date.eq(new_end_date),
+                ))
+                .get_result::<Subscription>(sql_connection)
+                .map_err(write_error)?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Pays for one more plan period, extending the subscription end date.
+    pub async fn renew_subscription(conn: &DBConnection, id_for_update: i32) -> Result<()> {
+        use crate::schema::subscriptions::dsl::*;
+
+        conn.run(move |sql_connection| -> Result<()> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let current: Subscription = subscriptions
+                    .filter(id.eq(id_for_update))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                if current.cancelled_at.is_some() {
+                    return Err(ServerError::SubscriptionCancelled.into());
+                }
+                SubscriptionsControl::check_subscribable(sql_connection, current.game_id)?;
+
+                let periods = current.periods_paid + 1;
+                let new_end_date =
+                    SubscriptionPlan::parse(&current.plan)?.paid_until(current.start_date, periods);
+                diesel::update(subscriptions.filter(&id.eq(id_for_update)))
+                    .set((
+                        periods_paid.eq(periods),
+                        end_date.eq(new_end_date),
+                        version.eq(version + 1),
+                    ))
+                    .execute(sql_connection)?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Stops renewals. The subscription stays active until the paid period ends.
+    pub async fn cancel_subscription(conn: &DBConnection, id_for_update: i32) -> Result<()> {
+        use crate::schema::subscriptions::dsl::*;
+
+        conn.run(move |sql_connection| -> Result<()> {
+            let updated = diesel::update(
+                subscriptions
+                    .filter(id.eq(id_for_update))
+                    .filter(cancelled_at.is_null()),
+            )
+            .set((
+                cancelled_at.eq(Local::now().naive_local()),
+                version.eq(version + 1),
+            ))
+            .execute(sql_connection)?;
+            if updated == 0 {
+                return Err(ServerError::SubscriptionCancelled.into());
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Cancels the subscriptions whose paid period ended before `today`, as of
+    /// the first day after the period. Returns the number of expired ones.
+    pub fn expire_lapsed(sql_conn: &PgConnection, today: NaiveDate) -> Result<usize> {
+        use crate::schema::subscriptions::dsl::*;
+
+        Ok(diesel::update(
+            subscriptions
+                .filter(cancelled_at.is_null())
+                .filter(end_date.lt(today)),
+        )
+        .set((
+            cancelled_at.eq(sql::<Nullable<Timestamp>>("(end_date + 1)::timestamp")),
+            version.eq(version + 1),
+        ))
+        .execute(sql_conn)?)
+    }
+}
+
+#[rocket::async_trait]
+impl Resource for SubscriptionsControl {
+    type Form = AddSubscription;
+    type New = NewSubscription;
+    type Table = subscriptions::table;
+    type Version = subscriptions::version;
+
+    const NAME: &'static str = "subscriptions";
+    const TABLE: &'static str = "Підписки";
+
+    fn parse(form: AddSubscription) -> Result<Self::New, ServerError> {
+        NewSubscription::from(form)
+    }
+
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_subscription_by_id(conn, id).await
+    }
+
+    fn check(sql_conn: &PgConnection, new: &NewSubscription, _id: Option<i32>) -> Result<()> {
+        Self::check_subscribable(sql_conn, new.game_id)
+    }
+
+    async fn update(conn: &DBConnection, id: i32, version: i32, new: Self::New) -> Result<()> {
+        Self::update_subscription(conn, id, version, new).await
+    }
+
+    /// User choices and the games that can be subscribed to.
+    async fn form_lists(conn: &DBConnection) -> Vec<Vec<String>> {
+        let users = UsersControl::get_users(conn).await.unwrap();
+        let users_id = users.iter().map(|user| user.id.to_string()).collect();
+
+        let users_name = users.into_iter().map(|user| user.nickname).collect();
+
+        let games = GamesControl::get_games(conn).await.unwrap();
+        let games_id = games
+            .iter()
+            .filter(|game| game.is_subscribable)
+            .map(|game| game.id.to_string())
+            .collect();
+
+        let games_name = games
+            .into_iter()
+            .filter(|game| game.is_subscribable)
+            .map(|game| game.name)
+            .collect();
+
+        vec![users_id, users_name, games_id, games_name]
+    }
+
+    fn for_edit(&mut self) -> Result<()> {
+        self.change_date_format(&config::formats().date, "%Y-%m-%d")
+    }
+}
@@ -0,0 +1,346 @@
+use crate::config;
+use crate::controllers::write_error;
+use crate::controllers::{JobsControl, NewJob};
+use crate::errors::ServerError;
+use crate::models::*;
+use crate::requests_handler::AddGameRole;
+use crate::schema::game_roles;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Datelike, Local, NaiveDate};
+use diesel::pg::data_types::PgMoney;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// Name of the game, all jobs, the game's roles and all staff, as read for
+/// `get_team`.
+type TeamRows = (String, Vec<Job>, Vec<GameRole>, Vec<Staff>);
+
+#[derive(Insertable)]
+#[table_name = "game_roles"]
+pub struct NewGameRole {
+    pub game_id: i32,
+    pub position: String,
+    pub headcount: i32,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub salary: PgMoney,
+}
+
+impl NewGameRole {
+    pub fn from(game_id: i32, role: AddGameRole) -> Result<Self, ServerError> {
+        let position = role.position.trim().to_string();
+        if position.is_empty() {
+            return Err(ServerError::NullValues(vec!["position".to_string()]));
+        }
+        if role.headcount <= 0 {
+            return Err(ServerError::InvalidValue(vec!["headcount".to_string()]));
+        }
+        if role.salary <= 0f64 {
+            return Err(ServerError::InvalidValue(vec!["salary".to_string()]));
+        }
+
+        let start_date = NaiveDate::parse_from_str(&role.start_date, "%Y-%m-%d")
+            .map_err(|_| ServerError::InvalidDate)?;
+        let end_date = if role.end_date.is_empty() {
+            None
+        } else {
+            Some(
+                NaiveDate::parse_from_str(&role.end_date, "%Y-%m-%d")
+                    .map_err(|_| ServerError::InvalidDate)?,
+            )
+        };
+        if end_date.is_some_and(|end_date| end_date < start_date) {
+            return Err(ServerError::InvalidDate);
+        }
+
+        Ok(NewGameRole {
+            game_id,
+            position,
+            headcount: role.headcount,
+            start_date,
+            end_date,
+            salary: PgMoney((role.salary * 100f64) as i64),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TeamPosition {
+    pub position: String,
+    pub members: Vec<JobsControl>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HeadcountPoint {
+    pub month: String,
+    pub headcount: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Candidate {
+    pub id: i32,
+    pub name: String,
+    pub job_title: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GameRolesControl {
+    pub id: i32,
+    pub position: String,
+    pub headcount: i32,
+    pub filled: usize,
+    pub open: usize,
+    pub start_date: String,
+    pub end_date: String,
+    pub salary: f64,
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TeamsControl {
+    pub game_id: i32,
+    pub game: String,
+    pub positions: Vec<TeamPosition>,
+    pub headcount_history: Vec<HeadcountPoint>,
+    pub roles: Vec<GameRolesControl>,
+}
+
+impl TeamsControl {
+    /// Whether the job runs on any day between `from` and `to`, where a
+    /// missing `to` means the period is open-ended.
+    fn overlaps(job: &Job, from: NaiveDate, to: Option<NaiveDate>) -> bool {
+        to.is_none_or(|to| job.first_work_day <= to)
+            && job.last_work_day.is_none_or(|last_day| from <= last_day)
+    }
+
+    /// Jobs of the game that take a place of the role.
+    fn filled(role: &GameRole, game_jobs: &[Job]) -> usize {
+        game_jobs
+            .iter()
+            .filter(|job| job.position == role.position)
+            .filter(|job| TeamsControl::overlaps(job, role.start_date, role.end_date))
+            .count()
+    }
+
+    /// Whether the staff member is employed for the whole period of the role.
+    fn is_available(member: &Staff, role: &GameRole) -> bool {
+        let hired = member
+            .employment_start
+            .is_some_and(|start| start <= role.start_date);
+        let stays = match (member.employment_end, role.end_date) {
+            (None, _) => true,
+            (Some(end), Some(role_end)) => role_end <= end,
+            (Some(_), None) => false,
+        };
+        hired && stays
+    }
+
+    /// Whether any of the jobs of the staff member overlaps the role.
+    fn is_busy(member: &Staff, jobs: &[Job], role: &GameRole) -> bool {
+        jobs.iter()
+            .filter(|job| job.staff_id == member.id)
+            .any(|job| TeamsControl::overlaps(job, role.start_date, role.end_date))
+    }
+
+    fn month_after(month: NaiveDate) -> NaiveDate {
+        if month.month() == 12 {
+            NaiveDate::from_ymd(month.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd(month.year(), month.month() + 1, 1)
+        }
+    }
+
+    /// Number of people that worked on the game in every month from its
+    /// first job up to the current month.
+    fn headcount_history(game_jobs: &[Job], today: NaiveDate) -> Vec<HeadcountPoint> {
+        let first_day = match game_jobs.iter().map(|job| job.first_work_day).min() {
+            Some(first_day) => first_day,
+            None => return vec![],
+        };
+
+        let mut history = vec![];
+        let mut month = NaiveDate::from_ymd(first_day.year(), first_day.month(), 1);
+        while month <= today {
+            let next_month = TeamsControl::month_after(month);
+            let people: HashSet<i32> = game_jobs
+                .iter()
+                .filter(|job| TeamsControl::overlaps(job, month, Some(next_month.pred())))
+                .map(|job| job.staff_id)
+                .collect();
+            history.push(HeadcountPoint {
+                month: month.format("%m-%Y").to_string(),
+                headcount: people.len(),
+            });
+            month = next_month;
+        }
+        history
+    }
+
+    pub async fn get_team(conn: &DBConnection, game_id_for_lookup: i32) -> Result<TeamsControl> {
+        use crate::schema::{games, jobs, staff};
+
+        let (game, all_jobs, roles, all_staff) = conn
+            .run(move |sql_conn| -> Result<TeamRows> {
+                let game = games::table
+                    .select(games::name)
+                    .filter(games::id.eq(game_id_for_lookup))
+                    .first(sql_conn)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                let all_jobs = jobs::table.order(jobs::id.asc()).load(sql_conn)?;
+                let roles = game_roles::table
+                    .filter(game_roles::game_id.eq(game_id_for_lookup))
+                    .order((game_roles::start_date.asc(), game_roles::id.asc()))
+                    .load(sql_conn)?;
+                let all_staff = staff::table.order(staff::name.asc()).load(sql_conn)?;
+                Ok((game, all_jobs, roles, all_staff))
+            })
+            .await?;
+
+        let today = Local::today().naive_local();
+        let (game_jobs, other_jobs): (Vec<Job>, Vec<Job>) = all_jobs
+            .into_iter()
+            .partition(|job| job.game_id == game_id_for_lookup);
+
+        let roles = roles
+            .into_iter()
+            .map(|role| {
+                let filled = TeamsControl::filled(&role, &game_jobs);
+                let open = (role.headcount as usize).saturating_sub(filled);
+                let candidates = if open > 0 {
+                    all_staff
+                        .iter()
+                        .filter(|member| {
+                            TeamsControl::is_available(member, &role)
+                                && !TeamsControl::is_busy(member, &game_jobs, &role)
+                                && !TeamsControl::is_busy(member, &other_jobs, &role)
+                        })
+                        .map(|member| Candidate {
+                            id: member.id,
+                            name: member.name.clone(),
+                            job_title: member.job_title.clone(),
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                };
+
+                GameRolesControl {
+                    id: role.id,
+                    position: role.position,
+                    headcount: role.headcount,
+                    filled,
+                    open,
+                    start_date: role.start_date.format(&config::formats().date).to_string(),
+                    end_date: role
+                        .end_date
+                        .map(|end_date| end_date.format(&config::formats().date).to_string())
+                        .unwrap_or_default(),
+                    salary: role.salary.0 as f64 / 100f64,
+                    candidates,
+                }
+            })
+            .collect();
+
+        let headcount_history = TeamsControl::headcount_history(&game_jobs, today);
+
+        let mut current: BTreeMap<String, Vec<JobsControl>> = BTreeMap::new();
+        for job in game_jobs {
+            if TeamsControl::overlaps(&job, today, Some(today)) {
+                let position = job.position.clone();
+                let job = JobsControl::make_jobs_control(conn, job).await;
+                current.entry(position).or_default().push(job);
+            }
+        }
+        let positions = current
+            .into_iter()
+            .map(|(position, members)| TeamPosition { position, members })
+            .collect();
+
+        Ok(TeamsControl {
+            game_id: game_id_for_lookup,
+            game,
+            positions,
+            headcount_history,
+            roles,
+        })
+    }
+
+    pub async fn add_role(conn: &DBConnection, role: NewGameRole) -> Result<()> {
+        use crate::schema::game_roles::dsl::*;
+
+        conn.run(move |sql_connection| -> Result<()> {
+            diesel::insert_into(game_roles)
+                .values(&role)
+                .get_result::<GameRole>(sql_connection)
+                .map_err(write_error)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Deletes the role and returns the id of its game.
+    pub async fn delete_role(conn: &DBConnection, id_for_delete: i32) -> Result<i32> {
+        use crate::schema::game_roles::dsl::*;
+
+        conn.run(move |sql_conn| -> Result<i32> {
+            let role = diesel::delete(game_roles)
+                .filter(&id.eq(id_for_delete))
+                .get_result::<GameRole>(sql_conn)
+                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+            Ok(role.game_id)
+        })
+        .await
+    }
+
+    /// Fills a place of the role with a free staff member by creating a job
+    /// for the role's dates and salary. Returns the id of the role's game.
+    pub async fn assign(conn: &DBConnection, role_id: i32, staff_id: i32) -> Result<i32> {
+        use crate::schema::{jobs, staff};
+
+        conn.run(move |sql_connection| -> Result<i32> {
+            sql_connection.transaction::<_, anyhow::Error, _>(|| {
+                let role: GameRole = game_roles::table
+                    .filter(game_roles::id.eq(role_id))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
+                let member: Staff = staff::table
+                    .filter(staff::id.eq(staff_id))
+                    .for_update()
+                    .first(sql_connection)
+                    .map_err(|_| ServerError::InvalidValue(vec!["staff id".to_string()]))?;
+
+                let game_jobs: Vec<Job> = jobs::table
+                    .filter(jobs::game_id.eq(role.game_id))
+                    .load(sql_connection)?;
+                if TeamsControl::filled(&role, &game_jobs) >= role.headcount as usize {
+                    return Err(ServerError::RoleFilled(role.position).into());
+                }
+                let member_jobs: Vec<Job> = jobs::table
+                    .filter(jobs::staff_id.eq(staff_id))
+                    .load(sql_connection)?;
+                if !TeamsControl::is_available(&member, &role)
+                    || TeamsControl::is_busy(&member, &member_jobs, &role)
+                {
+                    return Err(ServerError::StaffBusy(member.name).into());
+                }
+
+                JobsControl::insert_job(
+                    sql_connection,
+                    &NewJob {
+                        game_id: role.game_id,
+                        staff_id,
+                        position: role.position.clone(),
+                        first_work_day: role.start_date,
+                        last_work_day: role.end_date,
+                        salary: role.salary,
+                    },
+                )?;
+                Ok(role.game_id)
+            })
+        })
+        .await
+    }
+}
@@ -1,3 +1,5 @@
+use crate::cache;
+use crate::config;
 use crate::controllers::*;
 use crate::errors::ServerError;
 use crate::models::*;
@@ -7,11 +9,12 @@ use crate::DBConnection;
 use anyhow::Result;
 use chrono::{Datelike, NaiveDate};
 use diesel::pg::data_types::PgDate;
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::result::Error as DieselError;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use serde::Serialize;
 
-#[derive(Insertable)]
+#[derive(Insertable, AsChangeset)]
 #[table_name = "users"]
 pub struct NewUser {
     pub nickname: String,
@@ -39,8 +42,11 @@ impl NewUser {
 #[derive(Serialize)]
 pub struct UsersControl {
     pub id: i32,
+    pub version: i32,
     pub nickname: String,
     pub registration_date: String,
+    pub tier: String,
+    pub badge: String,
 }
 
 impl std::convert::From<User> for UsersControl {
@@ -51,11 +57,16 @@ impl std::convert::From<User> for UsersControl {
             registration_date.month(),
             registration_date.day(),
         );
-        let registration_date = registration_date.format("%d-%m-%Y").to_string();
+        let registration_date = registration_date
+            .format(&config::formats().date)
+            .to_string();
         UsersControl {
             id: users_struct.id,
+            version: users_struct.version,
             nickname: users_struct.nickname,
             registration_date: registration_date,
+            tier: String::new(),
+            badge: String::new(),
         }
     }
 }
@@ -64,10 +75,16 @@ impl UsersControl {
     pub async fn get_statistic(
         conn: &DBConnection,
         id_for_lookup: i32,
-    ) -> (i32, Vec<DonationsControl>) {
+    ) -> (i32, Vec<DonationsControl>, Vec<UserTierChangeControl>, f64) {
         (
             id_for_lookup,
             UsersControl::get_donations(conn, id_for_lookup).await,
+            LoyaltyTiersControl::get_tier_history(conn, id_for_lookup)
+                .await
+                .unwrap(),
+            StatisticsControl::get_user_total(conn, id_for_lookup)
+                .await
+                .unwrap(),
         )
     }
 
@@ -108,7 +125,18 @@ impl UsersControl {
             })
             .await?;
 
-        Ok(results.into_iter().map(UsersControl::from).collect())
+        let user_tiers = LoyaltyTiersControl::get_user_tiers(conn).await?;
+        Ok(results
+            .into_iter()
+            .map(|user| {
+                let mut user = UsersControl::from(user);
+                if let Some(tier) = user_tiers.get(&user.id) {
+                    user.tier = tier.name.clone();
+                    user.badge = tier.badge.clone();
+                }
+                user
+            })
+            .collect())
     }
 
     pub async fn get_user_by_id(conn: &DBConnection, id_for_lookup: i32) -> Result<UsersControl> {
@@ -124,55 +152,65 @@ impl UsersControl {
         .await
     }
 
-    pub async fn add_user(conn: &DBConnection, user: NewUser) -> Result<()> {
+    /// Deletes the user with their donations, and rebuilds the statistics of
+    /// the games they donated to.
+    fn remove_user(sql_conn: &PgConnection, id_for_delete: i32) -> Result<()> {
+        use crate::schema::donations;
         use crate::schema::users::dsl::*;
 
-        conn.run(move |sql_connection| -> Result<()> {
-            diesel::insert_into(users)
-                .values(&user)
-                .get_result::<User>(sql_connection)
+        sql_conn.transaction::<_, anyhow::Error, _>(|| {
+            let donated_to: Vec<i32> = donations::table
+                .filter(donations::user_id.eq(id_for_delete))
+                .select(donations::game_id)
+                .distinct()
+                .load(sql_conn)?;
+            diesel::delete(users)
+                .filter(&id.eq(id_for_delete))
+                .get_result::<User>(sql_conn)
                 .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
+                    // The donations go with the user, but refunds keep theirs.
+                    DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info)
+                        if info.constraint_name() == Some("donation_refunds_donation_id_fkey") =>
+                    {
+                        ServerError::UserHasRefunds
                     }
-                    _ => panic!("PREKOL"),
+                    _ => ServerError::InvalidValue(vec!["Id".to_string()]),
                 })?;
+            for game in donated_to {
+                StatisticsControl::refresh_game(sql_conn, game)?;
+            }
             Ok(())
         })
-        .await
     }
+}
 
-    pub async fn update_user(conn: &DBConnection, id_for_update: i32, user: NewUser) -> Result<()> {
-        use crate::schema::users::dsl::*;
+#[rocket::async_trait]
+impl Resource for UsersControl {
+    type Form = AddUser;
+    type New = NewUser;
+    type Table = users::table;
+    type Version = users::version;
 
-        conn.run(move |sql_connection| -> Result<()> {
-            diesel::update(users.filter(&id.eq(id_for_update)))
-                .set((
-                    nickname.eq(user.nickname),
-                    registration_date.eq(user.registration_date),
-                ))
-                .get_result::<User>(sql_connection)
-                .map_err(|err| match err {
-                    DieselError::DatabaseError(_, info) => {
-                        ServerError::InvalidForeignKey(info.message().to_string())
-                    }
-                    _ => panic!("PREKOL"),
-                })?;
-            Ok(())
-        })
-        .await
+    const NAME: &'static str = "users";
+    const TABLE: &'static str = "Користувачі";
+
+    fn parse(form: AddUser) -> Result<Self::New, ServerError> {
+        NewUser::from(form)
     }
 
-    pub async fn delete_users(conn: &DBConnection, id_for_delete: i32) -> Result<()> {
-        use crate::schema::users::dsl::*;
+    async fn get(conn: &DBConnection, id: i32) -> Result<Self> {
+        Self::get_user_by_id(conn, id).await
+    }
 
-        conn.run(move |sql_conn| -> Result<()> {
-            diesel::delete(users)
-                .filter(&id.eq(id_for_delete))
-                .get_result::<User>(sql_conn)
-                .map_err(|_| ServerError::InvalidValue(vec!["Id".to_string()]))?;
-            Ok(())
-        })
-        .await
+    fn remove(sql_conn: &PgConnection, id: i32) -> Result<()> {
+        Self::remove_user(sql_conn, id)
+    }
+
+    fn cached(_id: Option<i32>) -> Vec<cache::Dependency> {
+        vec![("donation_user_stats", None)]
+    }
+
+    fn for_edit(&mut self) -> Result<()> {
+        self.change_date_format(&config::formats().date, "%Y-%m-%d")
     }
 }
@@ -0,0 +1,205 @@
+use crate::logging::{self, RequestId};
+use crate::metrics;
+use log::Level;
+use rocket::fairing::Fairing;
+use rocket::figment::Figment;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::{Build, Ignite, Phase, Rocket, Sentinel};
+use rocket_sync_db_pools::{database, diesel as rdiesel, ConnectionPool};
+use serde_json::{json, Value};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// The name of the database in `Rocket.toml`.
+pub const DATABASE: &str = "gamestudio";
+
+/// Ties the start and the end line of a query together.
+static NEXT_QUERY_ID: AtomicU64 = AtomicU64::new(1);
+
+#[database("gamestudio")]
+pub struct PooledConnection(rdiesel::PgConnection);
+
+/// A connection from the pool. Reports the connections in use and the time
+/// of every query to `/metrics`, and logs every query with the id of the
+/// request the connection was taken for.
+pub struct DBConnection {
+    connection: PooledConnection,
+    request_id: Option<String>,
+}
+
+impl DBConnection {
+    pub fn fairing() -> impl Fairing {
+        PooledConnection::fairing()
+    }
+
+    pub async fn get_one<P: Phase>(rocket: &Rocket<P>) -> Option<Self> {
+        let started = Instant::now();
+        match PooledConnection::get_one(rocket).await {
+            Some(connection) => Some(DBConnection::acquired(connection, None, started)),
+            None => {
+                metrics::connection_failed();
+                None
+            }
+        }
+    }
+
+    fn acquired(
+        connection: PooledConnection,
+        request_id: Option<String>,
+        started: Instant,
+    ) -> Self {
+        metrics::connection_acquired(started.elapsed());
+        DBConnection {
+            connection,
+            request_id,
+        }
+    }
+
+    /// The id of the request the connection was taken for, if any.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    fn log(&self, level: Level, message: &str, fields: Value) {
+        logging::with_request_id(self.request_id(), || {
+            logging::event(level, "web_app::db", message, fields)
+        });
+    }
+
+    /// Runs the query on a thread of the blocking pool. Its time is counted
+    /// for the function that defined `query`, and its start and end are
+    /// logged under one `query_id`, so a query that hangs or panics can be
+    /// told from one that finished. A panic of the query is passed on after
+    /// it is logged.
+    pub async fn run<F, R>(&self, query: F) -> R
+    where
+        F: FnOnce(&mut rdiesel::PgConnection) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let function = metrics::function_name(std::any::type_name::<F>());
+        let query_id = NEXT_QUERY_ID.fetch_add(1, Ordering::Relaxed);
+        let request_id = self.request_id.clone();
+
+        self.log(
+            Level::Debug,
+            "query started",
+            json!({ "query_id": query_id, "function": &function }),
+        );
+        let started = Instant::now();
+        let result = self
+            .connection
+            .run(move |sql_conn| {
+                logging::with_request_id(request_id.as_deref(), || {
+                    panic::catch_unwind(AssertUnwindSafe(|| query(sql_conn)))
+                })
+            })
+            .await;
+        let elapsed = started.elapsed();
+
+        let (level, outcome, error) = match &result {
+            Ok(_) => (Level::Debug, "ok", None),
+            Err(panic) => (Level::Error, "panic", logging::panic_message(&**panic)),
+        };
+        self.log(
+            level,
+            "query finished",
+            json!({
+                "query_id": query_id,
+                "function": &function,
+                "duration_ms": elapsed.as_secs_f64() * 1000f64,
+                "outcome": outcome,
+                "error": error,
+            }),
+        );
+        metrics::observe_query(function, elapsed);
+        result.unwrap_or_else(|panic| panic::resume_unwind(panic))
+    }
+}
+
+impl Drop for DBConnection {
+    fn drop(&mut self) {
+        metrics::connection_released();
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DBConnection {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let started = Instant::now();
+        match PooledConnection::from_request(request).await {
+            Outcome::Success(connection) => {
+                let request_id = Some(RequestId::of(request));
+                Outcome::Success(DBConnection::acquired(connection, request_id, started))
+            }
+            Outcome::Failure(failure) => {
+                metrics::connection_failed();
+                Outcome::Failure(failure)
+            }
+            Outcome::Forward(forward) => Outcome::Forward(forward),
+        }
+    }
+}
+
+/// The pool itself, for work that takes a connection only while it runs
+/// rather than for the whole request or the lifetime of the server.
+#[derive(Clone)]
+pub struct DBPool {
+    // `rocket_sync_db_pools` hands out connections only through the state of
+    // a `Rocket`, so the shared pool is kept in an otherwise empty one.
+    holder: Arc<Rocket<Build>>,
+    request_id: Option<String>,
+}
+
+impl DBPool {
+    pub fn get<P: Phase>(rocket: &Rocket<P>) -> Option<Self> {
+        let pool = rocket
+            .state::<ConnectionPool<PooledConnection, rdiesel::PgConnection>>()?
+            .clone();
+        Some(DBPool {
+            holder: Arc::new(rocket::custom(Figment::new()).manage(pool)),
+            request_id: None,
+        })
+    }
+
+    /// A connection that goes back to the pool when dropped.
+    pub async fn get_one(&self) -> Option<DBConnection> {
+        let started = Instant::now();
+        match PooledConnection::get_one(&*self.holder).await {
+            Some(connection) => Some(DBConnection::acquired(
+                connection,
+                self.request_id.clone(),
+                started,
+            )),
+            None => {
+                metrics::connection_failed();
+                None
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DBPool {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        match DBPool::get(request.rocket()) {
+            Some(pool) => Outcome::Success(DBPool {
+                request_id: Some(RequestId::of(request)),
+                ..pool
+            }),
+            None => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
+
+impl Sentinel for DBConnection {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        <PooledConnection as Sentinel>::abort(rocket)
+    }
+}
@@ -13,4 +13,102 @@ pub enum ServerError {
 
     #[error("Ці поля мають бути заповнені: {0:?}")]
     NullValues(Vec<String>),
+
+    #[error("Невідомий статус гри: {0}")]
+    InvalidStatus(String),
+
+    #[error("Неможливо змінити статус гри з {0} на {1}")]
+    InvalidStatusTransition(String, String),
+
+    #[error("Гра вже має активний контракт на території {0}")]
+    ContractOverlap(String),
+
+    #[error("Гра {0} не підтримує підписку")]
+    NotSubscribable(String),
+
+    #[error("Підписку вже скасовано")]
+    SubscriptionCancelled,
+
+    #[error("Виплати за період {0} - {1} вже нараховано")]
+    PeriodAlreadyPaid(String, String),
+
+    #[error("Гра не мала чистого доходу за цей період")]
+    NoNetIncome,
+
+    #[error("Донат не може бути зроблений у майбутньому")]
+    DonationInFuture,
+
+    #[error("Донат зроблено до реєстрації користувача {0}")]
+    DonationBeforeRegistration(String),
+
+    #[error("Донат зроблено до виходу гри {0}")]
+    DonationBeforeRelease(String),
+
+    #[error("Донат вже перевірено")]
+    FlagAlreadyReviewed,
+
+    #[error("Сума повернення перевищує неповернений залишок донату ({0} ₴)")]
+    RefundExceedsDonation(f64),
+
+    #[error("Сума донату не може бути меншою за вже повернену ({0} ₴)")]
+    DonationBelowRefunded(f64),
+
+    #[error("Донат має повернення, тому його не можна видалити. Оформіть повне повернення")]
+    DonationHasRefunds,
+
+    #[error("Донати користувача мають повернення, тому його не можна видалити")]
+    UserHasRefunds,
+
+    #[error("Працівник не може бути керівником сам собі, навіть опосередковано")]
+    ManagerCycle,
+
+    #[error("Працівник {0} вже працює у студії")]
+    AlreadyEmployed(String),
+
+    #[error("Працівник {0} зараз не працює у студії")]
+    NotEmployed(String),
+
+    #[error("Усі місця на позицію {0} вже зайнято")]
+    RoleFilled(String),
+
+    #[error("Працівник {0} зайнятий на іншому проекті у ці дати")]
+    StaffBusy(String),
+
+    #[error("Жанр не може бути піджанром сам собі, навіть опосередковано")]
+    GenreCycle,
+
+    #[error("Гра вже виходила на платформі {0} у регіоні {1}")]
+    DuplicateRelease(String, String),
+
+    #[error("Запис змінили після того, як ви відкрили форму. Перевірте поточні значення та збережіть ще раз")]
+    EditConflict,
+
+    #[error("Не вибрано жодного рядка")]
+    NothingSelected,
+
+    #[error("Невідомий формат звіту: {0}. Доступні формати: html, pdf")]
+    UnknownReportFormat(String),
+
+    #[error(
+        "Неправильний квартал звіту: {0}. Потрібен номер кварталу від 1 до 4 і, за бажанням, рік"
+    )]
+    InvalidQuarter(String),
+
+    #[error("Не вдалося відкрити шрифт для PDF: {0}")]
+    ReportFont(String),
+
+    #[error("Невідоме завдання: {0}")]
+    UnknownTask(String),
+
+    #[error("Неправильний розклад завдання {0}: {1}. Потрібні п'ять полів: хвилина, година, день місяця, місяць, день тижня")]
+    InvalidCron(String, String),
+
+    #[error("Завдання аварійно завершилося: {0}")]
+    TaskPanicked(String),
+
+    #[error("Неправильна конфігурація профілю {0}: {1}")]
+    InvalidConfig(String, String),
+
+    #[error("Цю функцію вимкнено у конфігурації (features.{0})")]
+    FeatureDisabled(String),
 }
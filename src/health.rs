@@ -0,0 +1,187 @@
+use crate::DBConnection;
+use anyhow::Result;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::Varchar;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Orbit, Rocket};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+#[derive(Serialize, Debug)]
+pub struct Health {
+    pub status: &'static str,
+    pub version: &'static str,
+    pub uptime_seconds: u64,
+}
+
+/// The process is up and serving requests.
+pub fn health() -> Health {
+    Health {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_seconds: STARTED
+            .get()
+            .map_or(0, |started| started.elapsed().as_secs()),
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Check {
+    pub status: &'static str,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Migrations in `migrations/` not applied to the database yet.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<String>,
+}
+
+impl Check {
+    fn new(started: Instant, outcome: Result<Vec<String>>) -> Self {
+        let duration_ms = started.elapsed().as_secs_f64() * 1000f64;
+        match outcome {
+            Ok(pending) if pending.is_empty() => Check {
+                status: "ok",
+                duration_ms,
+                error: None,
+                pending,
+            },
+            Ok(pending) => Check {
+                status: "error",
+                duration_ms,
+                error: Some("migrations are not applied".to_string()),
+                pending,
+            },
+            Err(err) => Check {
+                status: "error",
+                duration_ms,
+                error: Some(err.to_string()),
+                pending: vec![],
+            },
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Checks {
+    pub database: Check,
+    pub migrations: Check,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Readiness {
+    pub status: &'static str,
+    pub checks: Checks,
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+#[derive(QueryableByName)]
+struct AppliedMigration {
+    #[sql_type = "Varchar"]
+    version: String,
+}
+
+// `MIGRATIONS`: names of the directories under `migrations` at build time,
+// oldest first. Written by `build.rs`.
+include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+/// Names of the migrations the binary was built with whose version is not in
+/// the table `diesel migration run` keeps, oldest first.
+fn pending_migrations(sql_conn: &PgConnection) -> Result<Vec<String>> {
+    let applied: HashSet<String> =
+        diesel::sql_query("SELECT version FROM __diesel_schema_migrations")
+            .load::<AppliedMigration>(sql_conn)?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect();
+
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|name| {
+            let version = name.split('_').next().unwrap_or_default().replace('-', "");
+            !applied.contains(&version)
+        })
+        .map(|name| name.to_string())
+        .collect())
+}
+
+/// The database gives a connection that runs queries, and its schema is
+/// up to date. `conn` is `None` when the pool gave no connection in time.
+pub async fn readiness(conn: Option<DBConnection>) -> Readiness {
+    let started = Instant::now();
+    let (database, migrations) = match conn {
+        None => (
+            Check::new(
+                started,
+                Err(anyhow::anyhow!("no connection available in the pool")),
+            ),
+            Check::new(started, Err(anyhow::anyhow!("database is unavailable"))),
+        ),
+        Some(conn) => {
+            conn.run(move |sql_conn| {
+                let database = Check::new(
+                    started,
+                    diesel::sql_query("SELECT 1")
+                        .execute(sql_conn)
+                        .map(|_| vec![])
+                        .map_err(anyhow::Error::from),
+                );
+                let started = Instant::now();
+                let migrations = if database.is_ok() {
+                    Check::new(started, pending_migrations(sql_conn))
+                } else {
+                    Check::new(started, Err(anyhow::anyhow!("database is unavailable")))
+                };
+                (database, migrations)
+            })
+            .await
+        }
+    };
+
+    Readiness {
+        status: if database.is_ok() && migrations.is_ok() {
+            "ok"
+        } else {
+            "unavailable"
+        },
+        checks: Checks {
+            database,
+            migrations,
+        },
+    }
+}
+
+/// Notes when the server started, for the uptime in `/healthz`.
+pub fn fairing() -> impl Fairing {
+    Uptime
+}
+
+struct Uptime;
+
+#[rocket::async_trait]
+impl Fairing for Uptime {
+    fn info(&self) -> Info {
+        Info {
+            name: "Uptime",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, _: &Rocket<Orbit>) {
+        STARTED.get_or_init(Instant::now);
+    }
+}
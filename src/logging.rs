@@ -0,0 +1,319 @@
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::figment::Figment;
+use rocket::http::Header;
+use rocket::yansi::Paint;
+use rocket::{Data, Request, Response};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::any::Any;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// The header a proxy may set to pass its own request id, and the one the
+/// id is returned in.
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The `logging` table of `Rocket.toml`. `modules` sets the level of the
+/// module and all its submodules, the longest matching prefix wins; `_` is
+/// the target of Rocket's indented lines.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct LoggingConfig {
+    /// `json` for one JSON object per line, `text` for plain lines.
+    format: String,
+    level: String,
+    modules: BTreeMap<String, String>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            format: "json".to_string(),
+            level: "info".to_string(),
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+struct Logger {
+    json: bool,
+    level: LevelFilter,
+    /// Sorted by length, longest first.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+thread_local! {
+    static REQUEST_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Runs `action` with log lines of this thread tagged with the request.
+pub fn with_request_id<T>(request_id: Option<&str>, action: impl FnOnce() -> T) -> T {
+    let previous = REQUEST_ID.with(|id| id.replace(request_id.map(str::to_string)));
+    let result = action();
+    REQUEST_ID.with(|id| *id.borrow_mut() = previous);
+    result
+}
+
+fn parse_level(module: &str, level: &str) -> Result<LevelFilter, String> {
+    level.parse().map_err(|_| {
+        format!(
+            "Invalid logging config: unknown level `{}` for `{}`, expected off, error, warn, info, debug or trace",
+            level, module
+        )
+    })
+}
+
+impl Logger {
+    fn from_config(config: LoggingConfig) -> Result<Self, String> {
+        let json = match config.format.as_str() {
+            "json" => true,
+            "text" => false,
+            other => {
+                return Err(format!(
+                    "Invalid logging config: unknown format `{}`, expected json or text",
+                    other
+                ))
+            }
+        };
+        let mut modules = config
+            .modules
+            .iter()
+            .map(|(module, level)| Ok((module.clone(), parse_level(module, level)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        modules.sort_by_key(|(module, _)| Reverse(module.len()));
+
+        Ok(Logger {
+            json,
+            level: parse_level("level", &config.level)?,
+            modules,
+        })
+    }
+
+    fn level_of(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, Ord::max)
+    }
+
+    fn write(&self, level: Level, target: &str, message: &str, fields: Map<String, Value>) {
+        let request_id = REQUEST_ID.with(|id| id.borrow().clone());
+        let time = Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z");
+
+        let line = if self.json {
+            let mut object = Map::new();
+            object.insert("time".to_string(), json!(time.to_string()));
+            object.insert("level".to_string(), json!(level.as_str()));
+            object.insert("target".to_string(), json!(target));
+            object.insert("message".to_string(), json!(message));
+            if let Some(request_id) = request_id {
+                object.insert("request_id".to_string(), json!(request_id));
+            }
+            object.extend(fields);
+            Value::Object(object).to_string()
+        } else {
+            let mut line = format!("{} {:5} {}", time, level.as_str(), target);
+            if let Some(request_id) = request_id {
+                line.push_str(&format!(" [{}]", request_id));
+            }
+            line.push_str(&format!(" {}", message));
+            for (key, value) in fields {
+                line.push_str(&format!(" {}={}", key, value));
+            }
+            line
+        };
+
+        let _ = writeln!(std::io::stdout().lock(), "{}", line);
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_of(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let message = record.args().to_string();
+            // Rocket pads its indented lines.
+            self.write(
+                record.level(),
+                record.target(),
+                message.trim_start(),
+                Map::new(),
+            );
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+static LOGGER: std::sync::OnceLock<Logger> = std::sync::OnceLock::new();
+
+/// Installs the logger configured in the `logging` table. Has to run before
+/// Rocket is built, or Rocket installs its own.
+pub fn init(figment: &Figment) -> Result<(), String> {
+    let config = match figment.extract_inner::<LoggingConfig>("logging") {
+        Ok(config) => config,
+        Err(err) if err.missing() => LoggingConfig::default(),
+        Err(err) => return Err(format!("Invalid logging config: {}", err)),
+    };
+    let logger = Logger::from_config(config)?;
+    let logger = LOGGER.get_or_init(|| logger);
+
+    log::set_logger(logger).map_err(|err| err.to_string())?;
+    log::set_max_level(logger.max_level());
+    // Rocket only turns its colors off with its own logger.
+    Paint::disable();
+
+    // Panics are logged with the request they happened in.
+    std::panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}", location.file(), location.line()))
+            .unwrap_or_default();
+        let message = panic_message(info.payload()).unwrap_or_default();
+        event(
+            Level::Error,
+            "panic",
+            message,
+            json!({ "location": location }),
+        );
+    }));
+    Ok(())
+}
+
+/// The text a panic was raised with, unless it was raised with a value
+/// other than a string.
+pub fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+/// Logs a line with extra fields, `fields` being a JSON object.
+pub fn event(level: Level, target: &str, message: &str, fields: Value) {
+    if let Some(logger) = LOGGER.get() {
+        if level <= logger.level_of(target) {
+            let fields = match fields {
+                Value::Object(fields) => fields,
+                _ => Map::new(),
+            };
+            logger.write(level, target, message, fields);
+        }
+    }
+}
+
+/// The id of the request, taken from the `X-Request-Id` header when a proxy
+/// set one, and generated otherwise.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn of(request: &Request<'_>) -> String {
+        request.local_cache(|| RequestId(String::new())).0.clone()
+    }
+
+    fn generate() -> String {
+        static STARTED: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        let started = STARTED.get_or_init(|| Local::now().timestamp_millis() as u64);
+        format!(
+            "{:x}-{:06x}",
+            started & 0xffff_ffff,
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+struct RequestStart(Instant);
+
+/// Gives every request an id, returns it in the `X-Request-Id` header and
+/// logs the request when the response is sent.
+pub fn fairing() -> impl Fairing {
+    RequestLogger
+}
+
+struct RequestLogger;
+
+#[rocket::async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request logger",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let request_id = request
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= 64
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            })
+            .map_or_else(RequestId::generate, str::to_string);
+        request.local_cache(|| RequestId(request_id));
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let request_id = RequestId::of(request);
+        let elapsed = request
+            .local_cache(|| RequestStart(Instant::now()))
+            .0
+            .elapsed();
+        let status = response.status().code;
+        let probe = matches!(request.uri().path().as_str(), "/healthz" | "/readyz");
+        let level = match status {
+            // Probes run every few seconds, their failures are enough.
+            503 if probe => Level::Warn,
+            500..=599 => Level::Error,
+            400..=499 => Level::Warn,
+            _ if probe => Level::Debug,
+            _ => Level::Info,
+        };
+
+        with_request_id(Some(&request_id), || {
+            event(
+                level,
+                "web_app::request",
+                &format!("{} {}", request.method(), request.uri()),
+                json!({
+                    "method": request.method().as_str(),
+                    "uri": request.uri().to_string(),
+                    "route": request.route().map(|route| route.uri.to_string()),
+                    "status": status,
+                    "duration_ms": elapsed.as_secs_f64() * 1000f64,
+                }),
+            )
+        });
+        response.set_header(Header::new(REQUEST_ID_HEADER, request_id));
+    }
+}
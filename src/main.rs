@@ -3,80 +3,169 @@ extern crate rocket;
 #[macro_use]
 extern crate diesel;
 
+use config::AppConfig;
 use dotenv::dotenv;
 use requests_handler::*;
+use rocket::figment::Figment;
 use rocket::fs::{relative, FileServer};
+use rocket::{Build, Rocket};
 use rocket_dyn_templates::Template;
-use rocket_sync_db_pools::{database, diesel as rdiesel};
 
+mod cache;
+mod config;
 mod controllers;
+mod db;
 mod errors;
+mod health;
+mod logging;
+mod metrics;
 mod models;
+mod report;
 mod requests_handler;
+mod scheduler;
 mod schema;
+mod statistics;
 
-#[database("gamestudio")]
-pub struct DBConnection(rdiesel::PgConnection);
+pub use db::{DBConnection, DBPool};
 
-#[launch]
-fn start() -> _ {
+/// Starts the server, or runs one of the commands and exits: `report ...`
+/// writes a report file (see `report::run_cli`), `stats ...` checks or
+/// rebuilds the materialized statistics (see `statistics::run_cli`).
+/// `ROCKET_PROFILE` selects the configuration, see `config::load`.
+#[rocket::main]
+async fn main() {
+    // `DATABASE_URL` may be kept in `.env`, shared with the diesel CLI.
     dotenv().ok();
-    rocket::build()
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("report") => report::run_cli(&args[1..]).await,
+        Some("stats") => statistics::run_cli(&args[1..]).await,
+        _ => serve().await,
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Runs the server, logging as configured in the `logging` table.
+async fn serve() -> anyhow::Result<()> {
+    let (figment, config) = config::load()?;
+    logging::init(&figment).map_err(|err| anyhow::anyhow!(err))?;
+    server(figment, config)
+        .launch()
+        .await
+        .map_err(|err| anyhow::anyhow!(err.to_string()))
+}
+
+/// A database connection for the commands, configured like the server's.
+async fn cli_connection() -> anyhow::Result<DBConnection> {
+    let (figment, _) = config::load()?;
+    let rocket = rocket::custom(figment.merge(("log_level", "off")))
+        .attach(DBConnection::fairing())
+        .ignite()
+        .await
+        .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+    DBConnection::get_one(&rocket)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("Не вдалося підключитися до бази даних"))
+}
+
+fn server(figment: Figment, config: AppConfig) -> Rocket<Build> {
+    rocket::custom(figment)
+        .manage(config)
         .mount(
             "/",
             routes![
                 index,
                 games,
-                games_delete_post,
-                games_edit,
-                games_edit_post,
-                games_add,
-                games_add_post,
+                games_delete_selected_post,
+                games_bulk,
+                games_bulk_post,
+                games_releases_post,
+                games_releases_delete_post,
+                games_team,
+                genres,
+                platforms,
+                games_team_roles_post,
+                games_team_roles_delete_post,
+                games_team_assign_post,
+                games_status_post,
                 publishers,
-                publishers_delete_post,
-                publishers_edit,
-                publishers_edit_post,
-                publishers_add,
-                publishers_add_post,
+                publishers_delete_selected_post,
+                publishers_popularity_post,
                 investors,
-                investors_delete_post,
-                investors_edit,
-                investors_edit_post,
-                investors_add,
-                investors_add_post,
+                investors_delete_selected_post,
                 staff,
-                staff_delete_post,
-                staff_edit,
-                staff_edit_post,
-                staff_add,
-                staff_add_post,
+                staff_delete_selected_post,
+                staff_hire_post,
+                staff_fire_post,
+                staff_org,
+                departments,
                 users,
-                users_delete_post,
-                users_edit,
-                users_edit_post,
-                users_add,
-                users_add_post,
+                users_delete_selected_post,
                 donations,
-                donations_delete_post,
-                donations_edit,
-                donations_edit_post,
-                donations_add,
-                donations_add_post,
+                donations_refund,
+                donations_refund_post,
+                donations_review,
+                donations_review_approve_post,
+                donations_review_reverse_post,
+                donations_delete_selected_post,
                 jobs,
-                jobs_delete_post,
-                jobs_edit,
-                jobs_edit_post,
-                jobs_add,
-                jobs_add_post,
+                jobs_delete_selected_post,
+                jobs_bulk,
+                jobs_bulk_post,
+                jobs_raise_post,
+                payroll,
                 investments,
-                investments_delete_post,
-                investments_edit,
-                investments_edit_post,
-                investments_add,
-                investments_add_post,
+                investments_delete_selected_post,
+                contracts,
+                sales,
+                revenue,
+                reports,
+                reports_studio,
+                reports_games,
+                schedule,
+                schedule_run_post,
+                cache_page,
+                cache_clear_post,
+                metrics_page,
+                healthz,
+                readyz,
+                subscriptions,
+                subscriptions_renew_post,
+                subscriptions_cancel_post,
+                payouts,
+                payouts_distribute_post,
+                payouts_delete_post,
+                leaderboards,
+                tiers,
             ],
         )
+        .mount("/", games_routes())
+        .mount("/", publishers_routes())
+        .mount("/", investors_routes())
+        .mount("/", staff_routes())
+        .mount("/", users_routes())
+        .mount("/", donations_routes())
+        .mount("/", jobs_routes())
+        .mount("/", investments_routes())
+        .mount("/", contracts_routes())
+        .mount("/", sales_routes())
+        .mount("/", subscriptions_routes())
+        .mount("/", tiers_routes())
+        .mount("/", departments_routes())
+        .mount("/", genres_routes())
+        .mount("/", platforms_routes())
         .mount("/", FileServer::from(relative!("front/static")))
+        .register("/", catchers![error_page])
+        .attach(logging::fairing())
         .attach(Template::fairing())
         .attach(DBConnection::fairing())
+        .attach(cache::fairing())
+        .attach(metrics::fairing())
+        .attach(health::fairing())
+        .attach(scheduler::Schedule::fairing())
 }
@@ -0,0 +1,456 @@
+use crate::cache;
+use crate::controllers::StudioTotals;
+use crate::db;
+use crate::errors::ServerError;
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::{Build, Data, Request, Response, Rocket};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    counts: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        for (bound, count) in BUCKETS.iter().zip(self.counts.iter_mut()) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for (bound, count) in BUCKETS.iter().zip(self.counts.iter()) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    /// Keyed by method, route and status.
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// Keyed by method and route.
+    request_seconds: BTreeMap<(String, String), Histogram>,
+    /// Keyed by the controller function that ran the query.
+    query_seconds: BTreeMap<String, Histogram>,
+    /// Keyed by the `ServerError` variant.
+    errors: BTreeMap<String, u64>,
+    connection_wait_seconds: Histogram,
+}
+
+#[derive(Default)]
+struct Metrics {
+    registry: Mutex<Registry>,
+    pool_size: AtomicU64,
+    connections_in_use: AtomicI64,
+    connection_errors: AtomicU64,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// `Type::function` of the function that defined the closure named by
+/// `type_name`, e.g. `GamesControl::get_games` for a query it runs.
+pub fn function_name(type_name: &str) -> String {
+    let path = type_name.replace("::{{closure}}", "");
+
+    let mut segments = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = path.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'<' => depth += 1,
+            b'>' => depth -= 1,
+            b':' if depth == 0 && bytes.get(i + 1) == Some(&b':') => {
+                segments.push(&path[start..i]);
+                start = i + 2;
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    segments.push(&path[start..]);
+
+    let simplify = |segment: &str| -> String {
+        match segment.strip_prefix('<') {
+            // `<path::Type as path::Trait>`
+            Some(inner) => {
+                let own = inner.split(" as ").next().unwrap_or(inner);
+                let own = own.split('<').next().unwrap_or(own);
+                own.rsplit("::").next().unwrap_or(own).to_string()
+            }
+            None => segment.split('<').next().unwrap_or(segment).to_string(),
+        }
+    };
+    let last = segments.len().saturating_sub(2);
+    segments[last..]
+        .iter()
+        .map(|segment| simplify(segment))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// A connection was taken from the pool after waiting `wait`.
+pub fn connection_acquired(wait: Duration) {
+    METRICS.connections_in_use.fetch_add(1, Ordering::Relaxed);
+    METRICS
+        .registry
+        .lock()
+        .unwrap()
+        .connection_wait_seconds
+        .observe(wait);
+}
+
+pub fn connection_released() {
+    METRICS.connections_in_use.fetch_sub(1, Ordering::Relaxed);
+}
+
+pub fn connection_failed() {
+    METRICS.connection_errors.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn observe_query(function: String, elapsed: Duration) {
+    METRICS
+        .registry
+        .lock()
+        .unwrap()
+        .query_seconds
+        .entry(function)
+        .or_default()
+        .observe(elapsed);
+}
+
+/// Counts an error shown to the user by its `ServerError` variant. Other
+/// errors come from the database or the runtime.
+pub fn record_error(err: &anyhow::Error) {
+    let kind = match err.downcast_ref::<ServerError>() {
+        // The variant name is the Debug output up to its fields.
+        Some(err) => format!("{:?}", err)
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        None if err.downcast_ref::<diesel::result::Error>().is_some() => "Database".to_string(),
+        None => "Other".to_string(),
+    };
+    *METRICS
+        .registry
+        .lock()
+        .unwrap()
+        .errors
+        .entry(kind)
+        .or_default() += 1;
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    header(out, name, "gauge", help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    header(out, name, "counter", help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// All metrics in the Prometheus text format.
+pub fn render(totals: Option<&StudioTotals>) -> String {
+    let mut out = String::new();
+    let registry = METRICS.registry.lock().unwrap();
+
+    header(
+        &mut out,
+        "http_requests_total",
+        "counter",
+        "Handled requests by route and status.",
+    );
+    for ((method, route, status), count) in &registry.requests {
+        let _ = writeln!(
+            out,
+            "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+            method,
+            escape(route),
+            status,
+            count
+        );
+    }
+    header(
+        &mut out,
+        "http_request_duration_seconds",
+        "histogram",
+        "Time to handle a request by route.",
+    );
+    for ((method, route), histogram) in &registry.request_seconds {
+        let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+        histogram.write(&mut out, "http_request_duration_seconds", &labels);
+    }
+
+    header(
+        &mut out,
+        "db_query_duration_seconds",
+        "histogram",
+        "Time of database queries by controller function.",
+    );
+    for (function, histogram) in &registry.query_seconds {
+        let labels = format!("function=\"{}\"", escape(function));
+        histogram.write(&mut out, "db_query_duration_seconds", &labels);
+    }
+    gauge(
+        &mut out,
+        "db_pool_size",
+        "Connections the pool may open.",
+        METRICS.pool_size.load(Ordering::Relaxed),
+    );
+    gauge(
+        &mut out,
+        "db_pool_connections_in_use",
+        "Connections taken from the pool.",
+        METRICS.connections_in_use.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "db_pool_connection_errors_total",
+        "Failed attempts to take a connection from the pool.",
+        METRICS.connection_errors.load(Ordering::Relaxed),
+    );
+    header(
+        &mut out,
+        "db_pool_wait_seconds",
+        "histogram",
+        "Time waited for a connection from the pool.",
+    );
+    registry
+        .connection_wait_seconds
+        .write(&mut out, "db_pool_wait_seconds", "");
+
+    header(
+        &mut out,
+        "server_errors_total",
+        "counter",
+        "Errors shown to users by kind.",
+    );
+    for (kind, count) in &registry.errors {
+        let _ = writeln!(out, "server_errors_total{{kind=\"{}\"}} {}", kind, count);
+    }
+    drop(registry);
+
+    let stats = cache::stats();
+    gauge(&mut out, "cache_entries", "Cached values.", stats.entries);
+    counter(
+        &mut out,
+        "cache_hits_total",
+        "Reads served from the cache.",
+        stats.hits,
+    );
+    counter(
+        &mut out,
+        "cache_misses_total",
+        "Reads loaded from the database.",
+        stats.misses,
+    );
+    counter(
+        &mut out,
+        "cache_invalidated_total",
+        "Cached values dropped after writes.",
+        stats.invalidated,
+    );
+    counter(
+        &mut out,
+        "cache_evicted_total",
+        "Cached values dropped when expired or over the limit.",
+        stats.evicted,
+    );
+
+    // Left out when the database is down, so the rest can still be scraped.
+    if let Some(totals) = totals {
+        gauge(
+            &mut out,
+            "studio_games",
+            "Games in the catalogue.",
+            totals.games,
+        );
+        gauge(&mut out, "studio_users", "Registered users.", totals.users);
+        gauge(
+            &mut out,
+            "studio_donations_today",
+            "Donations made today.",
+            totals.donations_today,
+        );
+        gauge(
+            &mut out,
+            "studio_donated_today_uah",
+            "Sum of donations made today, net of refunds.",
+            totals.donated_today,
+        );
+    }
+
+    out
+}
+
+/// Counts requests and times them by the route that handled them. Also
+/// reads the size of the database pool.
+pub fn fairing() -> impl Fairing {
+    RequestMetrics
+}
+
+struct RequestMetrics;
+
+struct RequestStart(Instant);
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        if let Ok(config) = rocket_sync_db_pools::Config::from(db::DATABASE, &rocket) {
+            METRICS
+                .pool_size
+                .store(config.pool_size as u64, Ordering::Relaxed);
+        }
+        Ok(rocket)
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let elapsed = request
+            .local_cache(|| RequestStart(Instant::now()))
+            .0
+            .elapsed();
+        let method = request.method().as_str().to_string();
+        let route = request
+            .route()
+            .map_or_else(|| "unmatched".to_string(), |route| route.uri.to_string());
+
+        let mut registry = METRICS.registry.lock().unwrap();
+        *registry
+            .requests
+            .entry((method.clone(), route.clone(), response.status().code))
+            .or_default() += 1;
+        registry
+            .request_seconds
+            .entry((method, route))
+            .or_default()
+            .observe(elapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Control;
+
+    impl Control {
+        fn query_name() -> String {
+            closure_name(|| ())
+        }
+
+        fn nested_name() -> String {
+            let outer = || closure_name(|| ());
+            outer()
+        }
+
+        async fn async_name() -> String {
+            closure_name(|| ())
+        }
+    }
+
+    fn closure_name<F: FnOnce()>(_: F) -> String {
+        function_name(std::any::type_name::<F>())
+    }
+
+    #[test]
+    fn closures() {
+        assert_eq!(Control::query_name(), "Control::query_name");
+        assert_eq!(Control::nested_name(), "Control::nested_name");
+        assert_eq!(
+            rocket::tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(Control::async_name()),
+            "Control::async_name"
+        );
+    }
+
+    #[test]
+    fn generic_segments() {
+        assert_eq!(
+            function_name(
+                "web_app::controllers::Resource<web_app::models::Game>::get_all::{{closure}}"
+            ),
+            "Resource::get_all"
+        );
+        assert_eq!(
+            function_name("<web_app::controllers::Resource<web_app::models::Game> as web_app::Loader>::load::{{closure}}"),
+            "Resource::load"
+        );
+        assert_eq!(
+            function_name("web_app::cache::cached<alloc::vec::Vec<i32>>::{{closure}}"),
+            "cache::cached"
+        );
+        assert_eq!(function_name("main"), "main");
+    }
+
+    #[test]
+    fn nested_closures() {
+        assert_eq!(
+            function_name(
+                "web_app::controllers::GamesControl::get_games::{{closure}}::{{closure}}"
+            ),
+            "GamesControl::get_games"
+        );
+    }
+}
@@ -6,19 +6,30 @@ pub struct Publisher {
     pub id: i32,
     pub name: String,
     pub price: PgMoney,
-    pub popularity: i16,
+    pub popularity: Option<i16>,
+    pub version: i32,
 }
 
 #[derive(Queryable, Debug)]
 pub struct Game {
     pub id: i32,
     pub name: String,
-    pub genre: String,
     pub release_date: PgDate,
     pub prime_cost: PgMoney,
     pub publisher_id: i32,
     pub cost: PgMoney,
     pub is_subscribable: bool,
+    pub status: String,
+    pub version: i32,
+}
+
+#[derive(Queryable, Debug)]
+pub struct GameStatusChange {
+    pub id: i32,
+    pub from_status: String,
+    pub to_status: String,
+    pub changed_at: chrono::NaiveDateTime,
+    pub changed_by: String,
 }
 
 #[derive(Queryable, Debug)]
@@ -26,6 +37,14 @@ pub struct Staff {
     pub id: i32,
     pub name: String,
     pub birth: PgDate,
+    pub department_id: Option<i32>,
+    pub job_title: String,
+    pub manager_id: Option<i32>,
+    pub employment_start: Option<chrono::NaiveDate>,
+    pub employment_end: Option<chrono::NaiveDate>,
+    pub email: String,
+    pub phone: String,
+    pub version: i32,
 }
 
 #[derive(Queryable, Debug)]
@@ -37,6 +56,7 @@ pub struct Job {
     pub first_work_day: chrono::NaiveDate,
     pub last_work_day: Option<chrono::NaiveDate>,
     pub salary: PgMoney,
+    pub version: i32,
 }
 
 #[derive(Queryable)]
@@ -44,6 +64,7 @@ pub struct User {
     pub id: i32,
     pub nickname: String,
     pub registration_date: PgDate,
+    pub version: i32,
 }
 
 #[derive(Queryable)]
@@ -53,6 +74,7 @@ pub struct Donation {
     pub game_id: i32,
     pub amount: PgMoney,
     pub donation_time: chrono::NaiveDateTime,
+    pub version: i32,
 }
 
 #[derive(Queryable)]
@@ -60,6 +82,7 @@ pub struct Investor {
     pub id: i32,
     pub name: String,
     pub is_company: bool,
+    pub version: i32,
 }
 
 #[derive(Queryable)]
@@ -69,4 +92,187 @@ pub struct Investment {
     pub game_id: i32,
     pub share: i16,
     pub invested: PgMoney,
+    pub version: i32,
+}
+
+#[derive(Queryable)]
+pub struct Contract {
+    pub id: i32,
+    pub game_id: i32,
+    pub publisher_id: i32,
+    pub advance_payment: PgMoney,
+    pub revenue_share: i16,
+    pub territory: String,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: Option<chrono::NaiveDate>,
+    pub version: i32,
+}
+
+#[derive(Queryable)]
+pub struct Sale {
+    pub id: i32,
+    pub game_id: i32,
+    pub platform: String,
+    pub sale_date: chrono::NaiveDate,
+    pub units: i32,
+    pub gross: PgMoney,
+    pub refunds: PgMoney,
+    pub version: i32,
+}
+
+#[derive(Queryable)]
+pub struct Subscription {
+    pub id: i32,
+    pub user_id: i32,
+    pub game_id: i32,
+    pub plan: String,
+    pub price: PgMoney,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: chrono::NaiveDate,
+    pub periods_paid: i32,
+    pub cancelled_at: Option<chrono::NaiveDateTime>,
+    pub version: i32,
+}
+
+#[derive(Queryable)]
+pub struct Payout {
+    pub id: i32,
+    pub investment_id: i32,
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    pub net_income: PgMoney,
+    pub amount: PgMoney,
+    pub paid_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct LoyaltyTier {
+    pub id: i32,
+    pub name: String,
+    pub min_amount: PgMoney,
+    pub badge: String,
+    pub version: i32,
+}
+
+#[derive(Queryable)]
+pub struct UserTierChange {
+    pub user_id: i32,
+    pub tier_id: Option<i32>,
+    pub tier_name: String,
+    pub total_donated: PgMoney,
+    pub reached_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct DonationFlag {
+    pub id: i32,
+    pub donation_id: Option<i32>,
+    pub user_id: i32,
+    pub game_id: i32,
+    pub amount: PgMoney,
+    pub donation_time: chrono::NaiveDateTime,
+    pub reasons: String,
+    pub flagged_at: chrono::NaiveDateTime,
+    pub resolution: Option<String>,
+    pub reviewed_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Queryable)]
+pub struct DonationRefund {
+    pub id: i32,
+    pub donation_id: i32,
+    pub amount: PgMoney,
+    pub reason: String,
+    pub refunded_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct Department {
+    pub id: i32,
+    pub name: String,
+    pub version: i32,
+}
+
+#[derive(Queryable)]
+pub struct SalaryChange {
+    pub job_id: i32,
+    pub salary: PgMoney,
+    pub effective_date: chrono::NaiveDate,
+}
+
+#[derive(Queryable)]
+pub struct GameRole {
+    pub id: i32,
+    pub game_id: i32,
+    pub position: String,
+    pub headcount: i32,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: Option<chrono::NaiveDate>,
+    pub salary: PgMoney,
+}
+
+#[derive(Queryable)]
+pub struct Genre {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub version: i32,
+}
+
+#[derive(Queryable)]
+pub struct Platform {
+    pub id: i32,
+    pub name: String,
+    pub version: i32,
+}
+
+#[derive(Queryable)]
+pub struct GameRelease {
+    pub id: i32,
+    pub game_id: i32,
+    pub region: String,
+    pub release_date: chrono::NaiveDate,
+    pub price: PgMoney,
+}
+
+#[derive(Queryable)]
+pub struct PublisherPopularity {
+    pub publisher_id: i32,
+    pub score: i16,
+    pub computed_score: i16,
+    pub income: PgMoney,
+    pub taken_on: chrono::NaiveDate,
+}
+
+#[derive(Queryable)]
+pub struct ScheduledRun {
+    pub id: i32,
+    pub task: String,
+    pub started_at: chrono::NaiveDateTime,
+    pub duration_ms: i64,
+    pub message: String,
+    pub error: Option<String>,
+}
+
+#[derive(Queryable)]
+pub struct DonationDailyStats {
+    pub game_id: i32,
+    pub day: chrono::NaiveDate,
+    pub donations_count: i32,
+    pub amount: PgMoney,
+}
+
+#[derive(Queryable)]
+pub struct DonationUserStats {
+    pub game_id: i32,
+    pub user_id: i32,
+    pub donations_count: i32,
+    pub amount: PgMoney,
+}
+
+#[derive(Queryable)]
+pub struct InvestmentStats {
+    pub game_id: i32,
+    pub investments_count: i32,
+    pub invested: PgMoney,
 }
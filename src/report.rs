@@ -0,0 +1,640 @@
+use crate::config;
+use crate::controllers::*;
+use crate::errors::ServerError;
+use crate::DBConnection;
+use anyhow::Result;
+use chrono::{Datelike, Local, NaiveDate};
+use printpdf::{
+    IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point,
+};
+use rocket_dyn_templates::tera::{Context, Tera};
+use serde::Serialize;
+use std::fs::File;
+
+/// Font embedded into PDF reports unless `REPORT_FONT` points to another
+/// TrueType file. It has to cover Cyrillic.
+const DEFAULT_FONT: &[u8] = include_bytes!("../front/fonts/DejaVuSans.ttf");
+
+// A4 landscape, in millimetres.
+const PAGE_WIDTH: f32 = 297.0;
+const PAGE_HEIGHT: f32 = 210.0;
+const MARGIN: f32 = 15.0;
+
+const TITLE_SIZE: f32 = 16.0;
+const HEADING_SIZE: f32 = 12.0;
+const TEXT_SIZE: f32 = 9.0;
+const ROW_HEIGHT: f32 = 5.5;
+// Average glyph width of the font as a share of its size, used to cut cells
+// that would not fit into their column.
+const GLYPH_WIDTH: f32 = 0.55;
+const PT_IN_MM: f32 = 0.3528;
+
+pub enum ReportFormat {
+    Html,
+    Pdf,
+}
+
+impl ReportFormat {
+    pub fn parse(format: &str) -> Result<Self, ServerError> {
+        match format {
+            "html" => Ok(ReportFormat::Html),
+            "pdf" => Ok(ReportFormat::Pdf),
+            _ => Err(ServerError::UnknownReportFormat(format.to_string())),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Html => "html",
+            ReportFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// A calendar quarter the figures of a report are limited to.
+#[derive(Clone, Copy, Debug)]
+pub struct Quarter {
+    pub year: i32,
+    pub quarter: u32,
+}
+
+impl Quarter {
+    /// `?year=&quarter=` of the report pages and `--year`/`--quarter` of the
+    /// command line. No quarter means the whole history; the year defaults to
+    /// the current one.
+    pub fn parse(year: Option<i32>, quarter: Option<u32>) -> Result<Option<Self>, ServerError> {
+        match (year, quarter) {
+            (None, None) => Ok(None),
+            (_, Some(quarter)) if (1..=4).contains(&quarter) => Ok(Some(Quarter {
+                year: year.unwrap_or_else(|| Local::today().year()),
+                quarter,
+            })),
+            (_, Some(quarter)) => Err(ServerError::InvalidQuarter(quarter.to_string())),
+            (Some(_), None) => Err(ServerError::InvalidQuarter("не вказано".to_string())),
+        }
+    }
+
+    /// First and last day of the quarter.
+    pub fn period(&self) -> (NaiveDate, NaiveDate) {
+        let first_month = (self.quarter - 1) * 3 + 1;
+        let from = NaiveDate::from_ymd(self.year, first_month, 1);
+        let to = match self.quarter {
+            4 => NaiveDate::from_ymd(self.year + 1, 1, 1),
+            _ => NaiveDate::from_ymd(self.year, first_month + 3, 1),
+        }
+        .pred();
+        (from, to)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReportTable {
+    pub title: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// A printable report: a list of headline figures followed by tables. The
+/// same report is rendered to a standalone HTML page or to a PDF file.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub name: String,
+    pub title: String,
+    pub generated: String,
+    pub summary: Vec<(String, String)>,
+    pub tables: Vec<ReportTable>,
+}
+
+fn money(amount: f64) -> String {
+    format!("{:.2} {}", amount, config::formats().currency)
+}
+
+impl Report {
+    /// The quarter, if any, goes into the file name and the title, and its
+    /// days open the summary.
+    fn new(name: String, title: String, quarter: Option<Quarter>) -> Self {
+        let mut report = Report {
+            name,
+            title,
+            generated: Local::now()
+                .format(&config::formats().date_time)
+                .to_string(),
+            summary: vec![],
+            tables: vec![],
+        };
+        if let Some(quarter) = quarter {
+            let (from, to) = quarter.period();
+            let date_format = &config::formats().date;
+            report.name = format!("{}-{}-q{}", report.name, quarter.year, quarter.quarter);
+            report.title = format!(
+                "{} за {} квартал {} року",
+                report.title, quarter.quarter, quarter.year
+            );
+            report.summary.push((
+                "Період".to_string(),
+                format!("{} – {}", from.format(date_format), to.format(date_format)),
+            ));
+        }
+        report
+    }
+
+    /// Team, donations and investments of a game, and its income against its
+    /// costs. With a quarter, the team, donations, income and salaries are
+    /// the ones of its days.
+    pub async fn game(conn: &DBConnection, id: i32, quarter: Option<Quarter>) -> Result<Report> {
+        let period = quarter.map(|quarter| quarter.period());
+        let game = GamesControl::get_game_by_id(conn, id).await?;
+        let team = GamesControl::get_game_staff(conn, id, period).await;
+        let donations = GamesControl::get_donations(conn, id, period).await;
+        let investments = GamesControl::get_investments(conn, id).await;
+        let revenue = RevenueControl::get_revenue(conn, period)
+            .await?
+            .0
+            .into_iter()
+            .find(|revenue| revenue.game_id == id)
+            .unwrap_or_default();
+
+        let mut report = Report::new(
+            format!("game-{}", id),
+            format!("Звіт по грі «{}»", game.name),
+            quarter,
+        );
+        report.summary.extend(vec![
+            ("Статус".to_string(), game.status),
+            ("Видавництво".to_string(), game.publisher),
+            ("Жанр".to_string(), game.genre),
+            ("Дата виходу".to_string(), game.release_date),
+            ("Ціна".to_string(), money(game.cost)),
+            ("Продано копій".to_string(), revenue.units_sold.to_string()),
+            ("Продажі".to_string(), money(revenue.sales)),
+            ("Донати".to_string(), money(revenue.donations)),
+            ("Підписки".to_string(), money(revenue.subscriptions)),
+            ("Усього доходу".to_string(), money(revenue.income)),
+        ]);
+        if quarter.is_none() {
+            report
+                .summary
+                .push(("Ціна розробки".to_string(), money(revenue.prime_cost)));
+        }
+        report.summary.extend(vec![
+            ("Зарплатня".to_string(), money(revenue.salary_costs)),
+            ("Прибуток".to_string(), money(revenue.profit)),
+        ]);
+
+        report.tables.push(ReportTable {
+            title: "Команда".to_string(),
+            headers: vec![
+                "Працівник".to_string(),
+                "Позиція".to_string(),
+                "Перший робочий день".to_string(),
+                "Останній робочий день".to_string(),
+                "Ставка".to_string(),
+            ],
+            rows: team
+                .into_iter()
+                .map(|job| {
+                    vec![
+                        job.staff,
+                        job.position,
+                        job.first_work_day,
+                        job.last_work_day,
+                        money(job.salary),
+                    ]
+                })
+                .collect(),
+        });
+
+        report.tables.push(ReportTable {
+            title: "Донати".to_string(),
+            headers: vec![
+                "Користувач".to_string(),
+                "Час".to_string(),
+                "Сума".to_string(),
+                "Повернено".to_string(),
+            ],
+            rows: donations
+                .into_iter()
+                .map(|donation| {
+                    vec![
+                        donation.user,
+                        donation.donation_time,
+                        money(donation.amount),
+                        money(donation.refunded),
+                    ]
+                })
+                .collect(),
+        });
+
+        report.tables.push(ReportTable {
+            title: "Інвестиції".to_string(),
+            headers: vec![
+                "Інвестор".to_string(),
+                "Частка".to_string(),
+                "Вкладено".to_string(),
+            ],
+            rows: investments
+                .into_iter()
+                .map(|investment| {
+                    vec![
+                        investment.investor,
+                        format!("{}%", investment.share),
+                        money(investment.invested),
+                    ]
+                })
+                .collect(),
+        });
+
+        Ok(report)
+    }
+
+    /// Income and costs of every game with the studio-wide totals, of the
+    /// quarter if one is given.
+    pub async fn studio(conn: &DBConnection, quarter: Option<Quarter>) -> Result<Report> {
+        let period = quarter.map(|quarter| quarter.period());
+        let (revenue, total) = RevenueControl::get_revenue(conn, period).await?;
+        let staff = StaffControl::get_staff(conn).await?;
+        let investments = InvestmentsControl::get_investments(conn).await?;
+
+        let mut report = Report::new(
+            "studio".to_string(),
+            "Зведений звіт студії".to_string(),
+            quarter,
+        );
+        report.summary.extend(vec![
+            ("Ігор".to_string(), revenue.len().to_string()),
+            (
+                "Працівників".to_string(),
+                staff
+                    .iter()
+                    .filter(|member| member.is_employed)
+                    .count()
+                    .to_string(),
+            ),
+            (
+                "Інвестовано".to_string(),
+                money(
+                    investments
+                        .iter()
+                        .map(|investment| investment.invested)
+                        .sum(),
+                ),
+            ),
+            ("Усього доходу".to_string(), money(total.income)),
+        ]);
+        if quarter.is_none() {
+            report
+                .summary
+                .push(("Ціна розробки".to_string(), money(total.prime_cost)));
+        }
+        report.summary.extend(vec![
+            ("Зарплатня".to_string(), money(total.salary_costs)),
+            ("Прибуток".to_string(), money(total.profit)),
+        ]);
+
+        let mut games = ReportTable {
+            title: "Доходи ігор".to_string(),
+            headers: vec![
+                "Гра".to_string(),
+                "Продано копій".to_string(),
+                "Продажі".to_string(),
+                "Донати".to_string(),
+                "Підписки".to_string(),
+                "Усього доходу".to_string(),
+                "Ціна розробки".to_string(),
+                "Зарплатня".to_string(),
+                "Прибуток".to_string(),
+            ],
+            rows: revenue
+                .into_iter()
+                .chain(std::iter::once(total))
+                .map(|game| {
+                    vec![
+                        game.game,
+                        game.units_sold.to_string(),
+                        money(game.sales),
+                        money(game.donations),
+                        money(game.subscriptions),
+                        money(game.income),
+                        money(game.prime_cost),
+                        money(game.salary_costs),
+                        money(game.profit),
+                    ]
+                })
+                .collect(),
+        };
+        // The prime cost is not counted within a quarter.
+        if quarter.is_some() {
+            let prime_cost = 6;
+            games.headers.remove(prime_cost);
+            for row in games.rows.iter_mut() {
+                row.remove(prime_cost);
+            }
+        }
+        report.tables.push(games);
+
+        Ok(report)
+    }
+
+    pub fn file_name(&self, format: &ReportFormat) -> String {
+        format!("{}.{}", self.name, format.extension())
+    }
+
+    pub fn render(&self, format: &ReportFormat) -> Result<Vec<u8>> {
+        match format {
+            ReportFormat::Html => Ok(self.to_html()?.into_bytes()),
+            ReportFormat::Pdf => self.to_pdf(),
+        }
+    }
+
+    /// A single HTML page with the styles inlined, so it can be saved and
+    /// opened without the server.
+    pub fn to_html(&self) -> Result<String> {
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "report.html",
+            include_str!("../front/templates/reports/report.html.tera"),
+        )?;
+        Ok(tera.render("report.html", &Context::from_serialize(self)?)?)
+    }
+
+    pub fn to_pdf(&self) -> Result<Vec<u8>> {
+        let (doc, page, layer) =
+            PdfDocument::new(&self.title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        let font = match std::env::var("REPORT_FONT") {
+            Ok(font_path) => {
+                let font_file = File::open(&font_path)
+                    .map_err(|_| ServerError::ReportFont(font_path.clone()))?;
+                doc.add_external_font(font_file)
+                    .map_err(|_| ServerError::ReportFont(font_path))?
+            }
+            Err(_) => doc
+                .add_external_font(DEFAULT_FONT)
+                .map_err(|_| ServerError::ReportFont("DejaVuSans.ttf".to_string()))?,
+        };
+        let layer = doc.get_page(page).get_layer(layer);
+
+        let mut pdf = PdfWriter {
+            doc,
+            layer,
+            font,
+            y: PAGE_HEIGHT - MARGIN,
+        };
+
+        pdf.text(&self.title, TITLE_SIZE, MARGIN);
+        pdf.advance(ROW_HEIGHT * 2.0);
+        pdf.text(&format!("Сформовано {}", self.generated), TEXT_SIZE, MARGIN);
+        pdf.advance(ROW_HEIGHT * 2.0);
+
+        for (label, value) in self.summary.iter() {
+            pdf.reserve(ROW_HEIGHT);
+            pdf.text(label, TEXT_SIZE, MARGIN);
+            pdf.text(value, TEXT_SIZE, MARGIN + 50.0);
+            pdf.advance(ROW_HEIGHT);
+        }
+
+        for table in self.tables.iter() {
+            pdf.advance(ROW_HEIGHT);
+            pdf.reserve(ROW_HEIGHT * 4.0);
+            pdf.text(&table.title, HEADING_SIZE, MARGIN);
+            pdf.advance(ROW_HEIGHT * 1.5);
+
+            if table.rows.is_empty() {
+                pdf.text("Немає записів", TEXT_SIZE, MARGIN);
+                pdf.advance(ROW_HEIGHT);
+                continue;
+            }
+
+            let column = (PAGE_WIDTH - 2.0 * MARGIN) / table.headers.len() as f32;
+            pdf.row(&table.headers, column);
+            pdf.rule();
+            for row in table.rows.iter() {
+                if pdf.reserve(ROW_HEIGHT) {
+                    pdf.row(&table.headers, column);
+                    pdf.rule();
+                }
+                pdf.row(row, column);
+            }
+        }
+
+        Ok(pdf.doc.save_to_bytes()?)
+    }
+}
+
+/// Writes lines top to bottom, starting a new page when the current one is
+/// full.
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    y: f32,
+}
+
+impl PdfWriter {
+    fn text(&self, text: &str, size: f32, x: f32) {
+        self.layer
+            .use_text(text, size, Mm(x), Mm(self.y - size * PT_IN_MM), &self.font);
+    }
+
+    fn advance(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    /// Starts a new page unless `height` still fits on the current one.
+    /// Returns whether a page was added.
+    fn reserve(&mut self, height: f32) -> bool {
+        if self.y - height >= MARGIN {
+            return false;
+        }
+
+        let (page, layer) = self
+            .doc
+            .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+        true
+    }
+
+    fn row(&mut self, cells: &[String], column: f32) {
+        let fits = (column / (TEXT_SIZE * PT_IN_MM * GLYPH_WIDTH)) as usize;
+        for (i, cell) in cells.iter().enumerate() {
+            let cell = if cell.chars().count() > fits {
+                let mut cut: String = cell.chars().take(fits.saturating_sub(2)).collect();
+                cut.push('…');
+                cut
+            } else {
+                cell.clone()
+            };
+            self.text(&cell, TEXT_SIZE, MARGIN + column * i as f32);
+        }
+        self.advance(ROW_HEIGHT);
+    }
+
+    fn rule(&mut self) {
+        let y = self.y + ROW_HEIGHT * 0.3;
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+}
+
+/// `web_app report studio [--year <year>] [--quarter <1-4>] [html|pdf] [file]`
+/// and the same with `game <id>`: writes a report without starting the
+/// server. The file defaults to `studio.html`, `game-<id>-2022-q1.pdf` and so
+/// on in the current directory.
+pub async fn run_cli(args: &[String]) -> Result<()> {
+    let usage = "Використання: web_app report studio [--year рік] [--quarter 1-4] [html|pdf] [файл]\n\
+                 \x20             web_app report game <id> [--year рік] [--quarter 1-4] [html|pdf] [файл]";
+
+    let (id, rest) = match args.first().map(String::as_str) {
+        Some("studio") => (None, &args[1..]),
+        Some("game") => {
+            let id = args
+                .get(1)
+                .and_then(|id| id.parse::<i32>().ok())
+                .ok_or_else(|| anyhow::anyhow!(usage))?;
+            (Some(id), &args[2..])
+        }
+        _ => return Err(anyhow::anyhow!(usage)),
+    };
+
+    let (mut year, mut quarter, mut positional) = (None, None, vec![]);
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--year" => {
+                year = Some(
+                    rest.next()
+                        .and_then(|year| year.parse::<i32>().ok())
+                        .ok_or_else(|| anyhow::anyhow!(usage))?,
+                )
+            }
+            "--quarter" => {
+                quarter = Some(
+                    rest.next()
+                        .and_then(|quarter| quarter.parse::<u32>().ok())
+                        .ok_or_else(|| anyhow::anyhow!(usage))?,
+                )
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let quarter = Quarter::parse(year, quarter)?;
+    let format = ReportFormat::parse(positional.first().map_or("html", |format| format.as_str()))?;
+
+    let conn = crate::cli_connection().await?;
+
+    let report = match id {
+        Some(id) => Report::game(&conn, id, quarter).await?,
+        None => Report::studio(&conn, quarter).await?,
+    };
+    let path = positional
+        .get(1)
+        .map(|path| path.to_string())
+        .unwrap_or_else(|| report.file_name(&format));
+    std::fs::write(&path, report.render(&format)?)?;
+    println!("Звіт збережено у {}", path);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Report {
+        let mut report = Report::new(
+            "studio".to_string(),
+            "Звіт студії".to_string(),
+            Some(Quarter {
+                year: 2021,
+                quarter: 4,
+            }),
+        );
+        report.summary.push(("Виручка".to_string(), money(1250.5)));
+        report.tables.push(ReportTable {
+            title: "Продажі".to_string(),
+            headers: vec!["Гра".to_string(), "Сума".to_string()],
+            // Enough rows to spill over to a second page.
+            rows: (1..=60)
+                .map(|i| vec![format!("Гра {}", i), money(i as f64)])
+                .collect(),
+        });
+        report
+    }
+
+    #[test]
+    fn parse_quarter() {
+        assert!(Quarter::parse(None, None).unwrap().is_none());
+
+        let quarter = Quarter::parse(Some(2020), Some(2)).unwrap().unwrap();
+        assert_eq!((quarter.year, quarter.quarter), (2020, 2));
+
+        let quarter = Quarter::parse(None, Some(3)).unwrap().unwrap();
+        assert_eq!(quarter.year, Local::today().year());
+
+        assert!(matches!(
+            Quarter::parse(Some(2020), Some(5)),
+            Err(ServerError::InvalidQuarter(_))
+        ));
+        assert!(matches!(
+            Quarter::parse(None, Some(0)),
+            Err(ServerError::InvalidQuarter(_))
+        ));
+        assert!(matches!(
+            Quarter::parse(Some(2020), None),
+            Err(ServerError::InvalidQuarter(_))
+        ));
+    }
+
+    #[test]
+    fn quarter_period() {
+        let period = |year, quarter| Quarter { year, quarter }.period();
+        assert_eq!(
+            period(2021, 1),
+            (
+                NaiveDate::from_ymd(2021, 1, 1),
+                NaiveDate::from_ymd(2021, 3, 31)
+            )
+        );
+        assert_eq!(
+            period(2021, 2),
+            (
+                NaiveDate::from_ymd(2021, 4, 1),
+                NaiveDate::from_ymd(2021, 6, 30)
+            )
+        );
+        assert_eq!(
+            period(2021, 4),
+            (
+                NaiveDate::from_ymd(2021, 10, 1),
+                NaiveDate::from_ymd(2021, 12, 31)
+            )
+        );
+    }
+
+    #[test]
+    fn render_html() {
+        let report = sample();
+        assert_eq!(report.file_name(&ReportFormat::Html), "studio-2021-q4.html");
+
+        let html = String::from_utf8(report.render(&ReportFormat::Html).unwrap()).unwrap();
+        assert!(html.contains("Звіт студії за 4 квартал 2021 року"));
+        assert!(html.contains("Продажі"));
+        assert!(html.contains("Гра 60"));
+        assert!(html.contains(&money(1250.5)));
+    }
+
+    #[test]
+    fn render_pdf() {
+        let report = sample();
+        assert_eq!(report.file_name(&ReportFormat::Pdf), "studio-2021-q4.pdf");
+
+        let pdf = report.render(&ReportFormat::Pdf).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert!(pdf.len() > 1000);
+    }
+}
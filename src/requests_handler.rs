@@ -1,11 +1,24 @@
+use crate::cache;
+use crate::config::{AppConfig, MetricsAccess};
 use crate::controllers::*;
 use crate::errors::ServerError;
-use crate::DBConnection;
+use crate::health;
+use crate::logging::{self, RequestId};
+use crate::metrics;
+use crate::report::{Quarter, Report, ReportFormat};
+use crate::scheduler::{Schedule, Task};
+use crate::{DBConnection, DBPool};
 use anyhow::Result;
+use chrono::{Datelike, Local, NaiveDate};
+use log::Level;
 use rocket::form::{self, Contextual, Form, FromForm};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::Request;
 use rocket::response::Redirect;
 use rocket::serde::Serialize;
+use rocket::State;
 use rocket_dyn_templates::Template;
+use serde_json::json;
 
 #[derive(Serialize)]
 struct CustomContext<'a, T: Serialize, F: Serialize> {
@@ -15,10 +28,236 @@ struct CustomContext<'a, T: Serialize, F: Serialize> {
     content: Vec<Vec<F>>,
 }
 
-#[derive(Debug, FromForm)]
+/// Values of a submitted form, one line per field, shown next to the current
+/// values when the record was changed while the form was open.
+fn submitted_values<T: Serialize>(form: &T) -> Vec<String> {
+    let fields = match serde_json::to_value(form) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        _ => return vec![],
+    };
+
+    let mut lines = vec!["Ваші значення:".to_string()];
+    for (name, value) in fields {
+        let value = match value {
+            serde_json::Value::String(value) => value,
+            serde_json::Value::Null => "-".to_string(),
+            value => value.to_string(),
+        };
+        lines.push(format!("{}: {}", name.replace("_", " "), value));
+    }
+    lines
+}
+
+/// The message of an error shown on the page. The error is counted for
+/// `/metrics` and logged, and the message names the request so the log
+/// line can be found.
+fn error_message(conn: &DBConnection, err: impl Into<anyhow::Error>) -> String {
+    let err = err.into();
+    log_error(conn, &err);
+    match conn.request_id() {
+        Some(request_id) => format!("{} (код запиту: {})", err, request_id),
+        None => err.to_string(),
+    }
+}
+
+fn log_error(conn: &DBConnection, err: &anyhow::Error) {
+    metrics::record_error(err);
+    // Errors of the user's input are expected, anything else is not.
+    let level = if err.downcast_ref::<ServerError>().is_some() {
+        Level::Warn
+    } else {
+        Level::Error
+    };
+    logging::with_request_id(conn.request_id(), || {
+        logging::event(
+            level,
+            "web_app::requests_handler",
+            &err.to_string(),
+            json!({ "error": format!("{:?}", err) }),
+        )
+    });
+}
+
+/// Errors of an edit form. An edit conflict also lists the submitted values,
+/// while the form itself is rendered with the current ones.
+fn edit_errors(conn: &DBConnection, err: anyhow::Error, submitted: Vec<String>) -> Vec<String> {
+    let conflict = matches!(
+        err.downcast_ref::<ServerError>(),
+        Some(ServerError::EditConflict)
+    );
+    let mut errors = vec![error_message(conn, err)];
+    if conflict {
+        errors.extend(submitted);
+    }
+    errors
+}
+
+/// Names of the form fields that are missing or could not be parsed.
+fn form_errors(errors: &form::Errors) -> String {
+    let names = errors
+        .iter()
+        .map(|err| {
+            let name = err.name.as_ref().unwrap().to_string();
+            name.rsplit_once('.')
+                .unwrap()
+                .1
+                .replace("_", " ")
+                .to_string()
+        })
+        .collect();
+    ServerError::NullValues(names).to_string()
+}
+
+async fn render_resource_add<R: Resource>(conn: &DBConnection, errors: Vec<String>) -> Template {
+    let ctx = CustomContext::<R, String> {
+        values: vec![],
+        table: R::TABLE,
+        errors,
+        content: R::form_lists(conn).await,
+    };
+
+    Template::render(format!("{}_add", R::NAME), ctx)
+}
+
+async fn render_resource_edit<R: Resource>(
+    conn: &DBConnection,
+    id: i32,
+    errors: Vec<String>,
+) -> Template {
+    let mut value = R::get(conn, id).await.unwrap();
+    value.for_edit().unwrap();
+
+    let ctx = CustomContext {
+        values: vec![value],
+        table: R::TABLE,
+        errors,
+        content: R::form_lists(conn).await,
+    };
+
+    Template::render(format!("{}_edit", R::NAME), ctx)
+}
+
+async fn add_resource<R: Resource>(
+    conn: &DBConnection,
+    form: form::Result<'_, R::Form>,
+) -> Result<Redirect, Template> {
+    let mut errs = Vec::new();
+
+    match form {
+        Err(errors) => errs.push(form_errors(&errors)),
+        Ok(form) => match R::parse(form) {
+            Err(err) => errs.push(error_message(conn, err)),
+            Ok(new) => {
+                if let Err(err) = R::add(conn, new).await {
+                    errs.push(error_message(conn, err));
+                }
+            }
+        },
+    }
+
+    if !errs.is_empty() {
+        Err(render_resource_add::<R>(conn, errs).await)
+    } else {
+        Ok(Redirect::to(format!("/{}", R::NAME)))
+    }
+}
+
+async fn edit_resource<R: Resource>(
+    conn: &DBConnection,
+    id: i32,
+    version: i32,
+    form: form::Result<'_, R::Form>,
+) -> Result<Redirect, Template> {
+    let mut errs = Vec::new();
+
+    match form {
+        Err(errors) => errs.push(form_errors(&errors)),
+        Ok(form) => {
+            let submitted = submitted_values(&form);
+            match R::parse(form) {
+                Err(err) => errs.push(error_message(conn, err)),
+                Ok(new) => {
+                    if let Err(err) = R::update(conn, id, version, new).await {
+                        errs.extend(edit_errors(conn, err, submitted));
+                    }
+                }
+            }
+        }
+    }
+
+    if !errs.is_empty() {
+        Err(render_resource_edit::<R>(conn, id, errs).await)
+    } else {
+        Ok(Redirect::to(format!("/{}", R::NAME)))
+    }
+}
+
+/// A refused delete shows the reason on the edit page of the row.
+async fn delete_resource<R: Resource>(conn: &DBConnection, id: i32) -> Result<Redirect, Template> {
+    match R::delete(conn, id).await {
+        Ok(()) => Ok(Redirect::to(format!("/{}", R::NAME))),
+        Err(err) => Err(render_resource_edit::<R>(conn, id, vec![error_message(conn, err)]).await),
+    }
+}
+
+/// Add, edit and delete routes of a `Resource`, and a `$routes()` function
+/// that returns them for mounting. The handlers keep their names and URIs, so
+/// they are linked with `uri!` as before.
+macro_rules! resource_routes {
+    (
+        $resource:ty,
+        $form:ident,
+        $routes:ident,
+        $add:ident, $add_post:ident => $add_uri:tt,
+        $edit:ident => $edit_uri:tt,
+        $edit_post:ident => $edit_post_uri:tt,
+        $delete_post:ident => $delete_uri:tt,
+    ) => {
+        pub fn $routes() -> Vec<rocket::Route> {
+            routes![$add, $add_post, $edit, $edit_post, $delete_post]
+        }
+
+        #[get($add_uri)]
+        pub async fn $add(conn: DBConnection) -> Template {
+            render_resource_add::<$resource>(&conn, vec![]).await
+        }
+
+        #[post($add_uri, data = "<form>")]
+        pub async fn $add_post<'r>(
+            conn: DBConnection,
+            mut form: Form<Contextual<'r, $form<'r>>>,
+        ) -> Result<Redirect, Template> {
+            let add = std::mem::replace(&mut form.value, None).unwrap().add;
+            add_resource::<$resource>(&conn, add).await
+        }
+
+        #[get($edit_uri)]
+        pub async fn $edit(conn: DBConnection, id: i32) -> Template {
+            render_resource_edit::<$resource>(&conn, id, vec![]).await
+        }
+
+        #[post($edit_post_uri, data = "<form>")]
+        pub async fn $edit_post<'r>(
+            conn: DBConnection,
+            id: i32,
+            version: i32,
+            mut form: Form<Contextual<'r, $form<'r>>>,
+        ) -> Result<Redirect, Template> {
+            let add = std::mem::replace(&mut form.value, None).unwrap().add;
+            edit_resource::<$resource>(&conn, id, version, add).await
+        }
+
+        #[post($delete_uri)]
+        pub async fn $delete_post(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
+            delete_resource::<$resource>(&conn, id).await
+        }
+    };
+}
+
+#[derive(Debug, FromForm, Serialize)]
 pub struct AddGame {
     pub name: String,
-    pub genre: String,
+    pub genre_ids: Vec<i32>,
     pub release_date: String,
     pub prime_cost: f64,
     pub publisher_id: i32,
@@ -32,10 +271,55 @@ pub struct GamesForm<'f> {
 }
 
 #[derive(Debug, FromForm)]
+pub struct ChangeGameStatus {
+    pub status: String,
+    pub changed_by: String,
+}
+
+/// Rows ticked on a list page.
+#[derive(Debug, FromForm)]
+pub struct SelectedRows {
+    pub ids: Vec<i32>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct SelectedRowsForm<'f> {
+    rows: form::Result<'f, SelectedRows>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct GamesBulkUpdate {
+    pub ids: Vec<i32>,
+    pub is_subscribable: String,
+    pub publisher_id: Option<i32>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct GamesBulkForm<'f> {
+    rows: form::Result<'f, GamesBulkUpdate>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct JobsBulkClose {
+    pub ids: Vec<i32>,
+    pub last_work_day: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct JobsBulkForm<'f> {
+    rows: form::Result<'f, JobsBulkClose>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct GameStatusForm<'f> {
+    add: form::Result<'f, ChangeGameStatus>,
+}
+
+#[derive(Debug, FromForm, Serialize)]
 pub struct AddPublisher {
     pub name: String,
     pub price: f64,
-    pub popularity: i16,
+    pub popularity: Option<i16>,
 }
 
 #[derive(Debug, FromForm)]
@@ -43,7 +327,7 @@ pub struct PublishersForm<'f> {
     add: form::Result<'f, AddPublisher>,
 }
 
-#[derive(Debug, FromForm)]
+#[derive(Debug, FromForm, Serialize)]
 pub struct AddInvestor {
     pub name: String,
     pub is_company: bool,
@@ -54,10 +338,16 @@ pub struct InvestorsForm<'f> {
     add: form::Result<'f, AddInvestor>,
 }
 
-#[derive(Debug, FromForm)]
+#[derive(Debug, FromForm, Serialize)]
 pub struct AddStaff {
     pub name: String,
     pub birth: String,
+    pub department_id: Option<i32>,
+    pub job_title: String,
+    pub manager_id: Option<i32>,
+    pub employment_start: Option<String>,
+    pub email: String,
+    pub phone: String,
 }
 
 #[derive(Debug, FromForm)]
@@ -66,6 +356,26 @@ pub struct StaffForm<'f> {
 }
 
 #[derive(Debug, FromForm)]
+pub struct AddEmploymentDate {
+    pub date: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct EmploymentForm<'f> {
+    add: form::Result<'f, AddEmploymentDate>,
+}
+
+#[derive(Debug, FromForm, Serialize)]
+pub struct AddDepartment {
+    pub name: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct DepartmentsForm<'f> {
+    add: form::Result<'f, AddDepartment>,
+}
+
+#[derive(Debug, FromForm, Serialize)]
 pub struct AddUser {
     pub nickname: String,
     pub registration_date: String,
@@ -76,7 +386,7 @@ pub struct UsersForm<'f> {
     add: form::Result<'f, AddUser>,
 }
 
-#[derive(Debug, FromForm)]
+#[derive(Debug, FromForm, Serialize)]
 pub struct AddDonation {
     pub user_id: i32,
     pub game_id: i32,
@@ -89,7 +399,7 @@ pub struct DonationsForm<'f> {
     add: form::Result<'f, AddDonation>,
 }
 
-#[derive(Debug, FromForm)]
+#[derive(Debug, FromForm, Serialize)]
 pub struct AddJob {
     pub game_id: i32,
     pub staff_id: i32,
@@ -104,7 +414,66 @@ pub struct JobsForm<'f> {
     add: form::Result<'f, AddJob>,
 }
 
+#[derive(Debug, FromForm, Serialize)]
+pub struct AddGenre {
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct GenresForm<'f> {
+    add: form::Result<'f, AddGenre>,
+}
+
+#[derive(Debug, FromForm, Serialize)]
+pub struct AddPlatform {
+    pub name: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct PlatformsForm<'f> {
+    add: form::Result<'f, AddPlatform>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct AddRelease {
+    pub platform_id: i32,
+    pub region: String,
+    pub release_date: String,
+    pub price: f64,
+}
+
+#[derive(Debug, FromForm)]
+pub struct ReleasesForm<'f> {
+    add: form::Result<'f, AddRelease>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct AddGameRole {
+    pub position: String,
+    pub headcount: i32,
+    pub start_date: String,
+    pub end_date: String,
+    pub salary: f64,
+}
+
+#[derive(Debug, FromForm)]
+pub struct GameRolesForm<'f> {
+    add: form::Result<'f, AddGameRole>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct AddRaise {
+    pub salary: f64,
+    pub effective_date: String,
+}
+
 #[derive(Debug, FromForm)]
+pub struct RaisesForm<'f> {
+    add: form::Result<'f, AddRaise>,
+}
+
+#[derive(Debug, FromForm, Serialize)]
 pub struct AddInvestment {
     pub game_id: i32,
     pub investor_id: i32,
@@ -117,6 +486,85 @@ pub struct InvestmentsForm<'f> {
     add: form::Result<'f, AddInvestment>,
 }
 
+#[derive(Debug, FromForm, Serialize)]
+pub struct AddContract {
+    pub game_id: i32,
+    pub publisher_id: i32,
+    pub advance_payment: f64,
+    pub revenue_share: i16,
+    pub territory: String,
+    pub start_date: String,
+    pub end_date: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct ContractsForm<'f> {
+    add: form::Result<'f, AddContract>,
+}
+
+#[derive(Debug, FromForm, Serialize)]
+pub struct AddSale {
+    pub game_id: i32,
+    pub platform: String,
+    pub sale_date: String,
+    pub units: i32,
+    pub gross: f64,
+    pub refunds: f64,
+}
+
+#[derive(Debug, FromForm)]
+pub struct SalesForm<'f> {
+    add: form::Result<'f, AddSale>,
+}
+
+#[derive(Debug, FromForm, Serialize)]
+pub struct AddSubscription {
+    pub user_id: i32,
+    pub game_id: i32,
+    pub plan: String,
+    pub price: f64,
+    pub start_date: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct SubscriptionsForm<'f> {
+    add: form::Result<'f, AddSubscription>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct AddPayoutPeriod {
+    pub period_start: String,
+    pub period_end: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct PayoutsForm<'f> {
+    add: form::Result<'f, AddPayoutPeriod>,
+}
+
+#[derive(Debug, FromForm)]
+pub struct AddRefund {
+    pub amount: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct RefundsForm<'f> {
+    add: form::Result<'f, AddRefund>,
+}
+
+#[derive(Debug, FromForm, Serialize)]
+pub struct AddTier {
+    pub name: String,
+    pub min_amount: f64,
+    pub badge: String,
+}
+
+#[derive(Debug, FromForm)]
+pub struct TiersForm<'f> {
+    add: form::Result<'f, AddTier>,
+}
+
 #[get("/")]
 pub async fn index() -> Template {
     let ctx = CustomContext::<String, String> {
@@ -129,1322 +577,1378 @@ pub async fn index() -> Template {
     Template::render("index", ctx)
 }
 
-#[get("/games?<id>")]
-pub async fn games(conn: DBConnection, id: Option<i32>) -> Template {
+async fn render_games(
+    conn: &DBConnection,
+    id: Option<i32>,
+    status: Option<String>,
+    mut errors: Vec<String>,
+) -> Template {
     let mut content = Vec::new();
-    if id.is_some() {
-        let stat = GamesControl::get_statistic(&conn, id.unwrap()).await;
+    if let Some(id) = id {
+        let stat = GamesControl::get_statistic(conn, id).await;
         content.push(stat);
     }
 
+    let games = match status.as_deref() {
+        Some(status) if !status.is_empty() => GamesControl::get_games_by_status(conn, status).await,
+        _ => GamesControl::get_games(conn).await,
+    };
+    let values = games.unwrap_or_else(|err| {
+        errors.push(error_message(conn, err));
+        vec![]
+    });
+
     let ctx = CustomContext {
-        values: GamesControl::get_games(&conn).await.unwrap(),
+        values,
         table: "Ігри",
-        errors: vec![],
+        errors,
         content: vec![content],
     };
 
     Template::render("games", ctx)
 }
 
-#[get("/games/add")]
-pub async fn games_add(conn: DBConnection) -> Template {
-    let publishers = PublishersControl::get_publishers(&conn).await.unwrap();
-    let publishers_id = publishers
-        .iter()
-        .map(|publisher| publisher.id.to_string())
-        .collect();
-
-    let publishers_name = publishers
-        .into_iter()
-        .map(|publisher| publisher.name)
-        .collect();
-
-    let ctx = CustomContext::<String, String> {
-        values: vec![],
-        table: "Ігри",
-        errors: vec![],
-        content: vec![publishers_id, publishers_name],
-    };
-
-    Template::render("games_add", ctx)
+#[get("/games?<id>&<status>")]
+pub async fn games(conn: DBConnection, id: Option<i32>, status: Option<String>) -> Template {
+    render_games(&conn, id, status, vec![]).await
 }
 
-#[post("/games/add", data = "<form>")]
-pub async fn games_add_post<'r>(
+#[post("/games/status?<id>", data = "<form>")]
+pub async fn games_status_post<'r>(
     conn: DBConnection,
-    mut form: Form<Contextual<'r, GamesForm<'r>>>,
+    id: i32,
+    mut form: Form<Contextual<'r, GameStatusForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let game = std::mem::replace(&mut form.value, None).unwrap().add;
+    let change = std::mem::replace(&mut form.value, None).unwrap().add;
     let mut errs = Vec::new();
 
-    match game {
+    match change {
         Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
+            errs.push(form_errors(&errors));
         }
-        Ok(game) => {
-            let game = NewGame::from(game);
-            if let Err(err) = game {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = GamesControl::add_game(&conn, game.unwrap()).await.err() {
-                    errs.push(err.to_string());
-                }
+        Ok(change) => {
+            if change.changed_by.trim().is_empty() {
+                errs.push(ServerError::NullValues(vec!["changed by".to_string()]).to_string());
+            } else if let Some(err) =
+                GamesControl::change_status(&conn, id, &change.status, change.changed_by)
+                    .await
+                    .err()
+            {
+                errs.push(error_message(&conn, err));
             }
         }
     }
 
     if !errs.is_empty() {
-        let ctx = CustomContext::<String, String> {
-            values: vec![],
-            table: "Ігри",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("games_add", ctx))
+        Err(render_games(&conn, Some(id), None, errs).await)
     } else {
-        Ok(Redirect::to(uri!(games(None::<i32>))))
+        Ok(Redirect::to(uri!(games(Some(id), None::<String>))))
     }
 }
 
-#[get("/games/edit?<id>")]
-pub async fn games_edit<'r>(conn: DBConnection, id: i32) -> Template {
-    let mut game = GamesControl::get_game_by_id(&conn, id).await.unwrap();
-    game.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-    let publishers = PublishersControl::get_publishers(&conn).await.unwrap();
-    let publishers_id = publishers
-        .iter()
-        .map(|publisher| publisher.id.to_string())
-        .collect();
+resource_routes!(
+    GamesControl,
+    GamesForm,
+    games_routes,
+    games_add, games_add_post => "/games/add",
+    games_edit => "/games/edit?<id>",
+    games_edit_post => "/games/edit?<id>&<version>",
+    games_delete_post => "/games/delete?<id>",
+);
 
-    let publishers_name = publishers
-        .into_iter()
-        .map(|publisher| publisher.name)
-        .collect();
+#[post("/games/delete_selected", data = "<form>")]
+pub async fn games_delete_selected_post<'r>(
+    conn: DBConnection,
+    mut form: Form<Contextual<'r, SelectedRowsForm<'r>>>,
+) -> Result<Redirect, Template> {
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let errs = match rows {
+        Ok(rows) if !rows.ids.is_empty() => GamesControl::delete_selected(&conn, rows.ids)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+        _ => vec![ServerError::NothingSelected.to_string()],
+    };
 
-    let ctx = CustomContext::<_, String> {
-        values: vec![game],
+    if !errs.is_empty() {
+        Err(render_games(&conn, None, None, errs).await)
+    } else {
+        Ok(Redirect::to(uri!(games(None::<i32>, None::<String>))))
+    }
+}
+
+async fn render_games_bulk(conn: &DBConnection, ids: Vec<i32>, errors: Vec<String>) -> Template {
+    let games = GamesControl::get_games(conn).await.unwrap();
+    let ctx = CustomContext {
+        values: games
+            .into_iter()
+            .filter(|game| ids.contains(&game.id))
+            .collect(),
         table: "Ігри",
-        errors: vec![],
-        content: vec![publishers_id, publishers_name],
+        errors,
+        content: GamesControl::form_lists(conn).await,
+    };
+
+    Template::render("games_bulk", ctx)
+}
+
+#[get("/games/bulk?<rows>")]
+pub async fn games_bulk(conn: DBConnection, rows: Option<SelectedRows>) -> Template {
+    match rows {
+        Some(rows) if !rows.ids.is_empty() => render_games_bulk(&conn, rows.ids, vec![]).await,
+        _ => {
+            render_games(
+                &conn,
+                None,
+                None,
+                vec![ServerError::NothingSelected.to_string()],
+            )
+            .await
+        }
+    }
+}
+
+#[post("/games/bulk", data = "<form>")]
+pub async fn games_bulk_post<'r>(
+    conn: DBConnection,
+    mut form: Form<Contextual<'r, GamesBulkForm<'r>>>,
+) -> Result<Redirect, Template> {
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let rows = match rows {
+        Ok(rows) if !rows.ids.is_empty() => rows,
+        _ => {
+            return Err(render_games(
+                &conn,
+                None,
+                None,
+                vec![ServerError::NothingSelected.to_string()],
+            )
+            .await)
+        }
+    };
+
+    let errs = match GamesBulkChange::from(&rows) {
+        Err(err) => vec![error_message(&conn, err)],
+        Ok(change) => GamesControl::update_selected(&conn, rows.ids.clone(), change)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
     };
 
-    Template::render("games_edit", ctx)
+    if !errs.is_empty() {
+        Err(render_games_bulk(&conn, rows.ids, errs).await)
+    } else {
+        Ok(Redirect::to(uri!(games(None::<i32>, None::<String>))))
+    }
 }
 
-#[post("/games/edit?<id>", data = "<form>")]
-pub async fn games_edit_post<'r>(
+#[post("/games/releases?<id>", data = "<form>")]
+pub async fn games_releases_post<'r>(
     conn: DBConnection,
     id: i32,
-    mut form: Form<Contextual<'r, GamesForm<'r>>>,
+    mut form: Form<Contextual<'r, ReleasesForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let game = std::mem::replace(&mut form.value, None).unwrap().add;
+    let release = std::mem::replace(&mut form.value, None).unwrap().add;
     let mut errs = Vec::new();
 
-    match game {
+    match release {
         Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
+            errs.push(form_errors(&errors));
         }
-        Ok(game) => {
-            let game = NewGame::from(game);
-            if let Err(err) = game {
-                errs.push(err.to_string());
+        Ok(release) => {
+            let release = NewRelease::from(id, release);
+            if let Err(err) = release {
+                errs.push(error_message(&conn, err));
             } else {
-                if let Some(err) = GamesControl::update_game(&conn, id, game.unwrap())
+                if let Some(err) = ReleasesControl::add_release(&conn, release.unwrap())
                     .await
                     .err()
                 {
-                    errs.push(err.to_string());
+                    errs.push(error_message(&conn, err));
                 }
             }
         }
     }
 
     if !errs.is_empty() {
-        let mut game = GamesControl::get_game_by_id(&conn, id).await.unwrap();
-        game.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-        let ctx = CustomContext::<_, String> {
-            values: vec![game],
-            table: "Ігри",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("games_edit", ctx))
+        Err(render_games(&conn, Some(id), None, errs).await)
     } else {
-        Ok(Redirect::to(uri!(games(None::<i32>))))
+        Ok(Redirect::to(uri!(games(Some(id), None::<String>))))
+    }
+}
+
+#[post("/games/releases/delete?<id>&<release_id>")]
+pub async fn games_releases_delete_post(
+    conn: DBConnection,
+    id: i32,
+    release_id: i32,
+) -> Result<Redirect, Template> {
+    match ReleasesControl::delete_release(&conn, release_id).await {
+        Ok(game_id) => Ok(Redirect::to(uri!(games(Some(game_id), None::<String>)))),
+        Err(err) => Err(render_games(&conn, Some(id), None, vec![error_message(&conn, err)]).await),
     }
 }
 
-#[post("/games/delete?<id>")]
-pub async fn games_delete_post<'r>(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
-    GamesControl::delete_game(&conn, id).await.unwrap();
+async fn render_games_team(conn: &DBConnection, id: i32, errors: Vec<String>) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: vec![TeamsControl::get_team(conn, id).await.unwrap()],
+        table: "Команда гри",
+        errors,
+        content: vec![],
+    };
 
-    Ok(Redirect::to(uri!(games(None::<i32>))))
+    Template::render("games_team", ctx)
 }
 
-#[get("/publishers?<id>")]
-pub async fn publishers(conn: DBConnection, id: Option<i32>) -> Template {
-    let mut content = Vec::new();
-    if id.is_some() {
-        let stat = PublishersControl::get_statistic(&conn, id.unwrap()).await;
-        content.push(stat);
-    }
-    let ctx = CustomContext {
-        values: PublishersControl::get_publishers(&conn).await.unwrap(),
-        table: "Видавництва",
-        errors: vec![],
-        content: vec![content],
-    };
-
-    Template::render("publishers", ctx)
-}
-
-#[get("/publishers/add")]
-pub async fn publishers_add() -> Template {
-    let ctx = CustomContext::<String, String> {
-        values: vec![],
-        table: "Видавництва",
-        errors: vec![],
-        content: vec![],
-    };
-
-    Template::render("publishers_add", ctx)
+#[get("/games/team?<id>")]
+pub async fn games_team(conn: DBConnection, id: i32) -> Template {
+    render_games_team(&conn, id, vec![]).await
 }
 
-#[post("/publishers/add", data = "<form>")]
-pub async fn publishers_add_post<'r>(
+#[post("/games/team/roles?<id>", data = "<form>")]
+pub async fn games_team_roles_post<'r>(
     conn: DBConnection,
-    mut form: Form<Contextual<'r, PublishersForm<'r>>>,
+    id: i32,
+    mut form: Form<Contextual<'r, GameRolesForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let publisher = std::mem::replace(&mut form.value, None).unwrap().add;
+    let role = std::mem::replace(&mut form.value, None).unwrap().add;
     let mut errs = Vec::new();
 
-    match publisher {
+    match role {
         Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
+            errs.push(form_errors(&errors));
         }
-        Ok(publisher) => {
-            let publisher = NewPublisher::from(publisher);
-            if let Err(err) = publisher {
-                errs.push(err.to_string());
+        Ok(role) => {
+            let role = NewGameRole::from(id, role);
+            if let Err(err) = role {
+                errs.push(error_message(&conn, err));
             } else {
-                if let Some(err) = PublishersControl::add_publisher(&conn, publisher.unwrap())
-                    .await
-                    .err()
-                {
-                    errs.push(err.to_string());
+                if let Some(err) = TeamsControl::add_role(&conn, role.unwrap()).await.err() {
+                    errs.push(error_message(&conn, err));
                 }
             }
         }
     }
 
     if !errs.is_empty() {
-        let ctx = CustomContext::<String, String> {
-            values: vec![],
-            table: "Видавництва",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("publishers_add", ctx))
+        Err(render_games_team(&conn, id, errs).await)
     } else {
-        Ok(Redirect::to(uri!(publishers(None::<i32>))))
+        Ok(Redirect::to(format!("/games/team?id={}", id)))
     }
 }
 
-#[get("/publishers/edit?<id>")]
-pub async fn publishers_edit<'r>(conn: DBConnection, id: i32) -> Template {
-    let publisher = PublishersControl::get_publisher_by_id(&conn, id)
-        .await
-        .unwrap();
-
-    let ctx = CustomContext::<_, String> {
-        values: vec![publisher],
-        table: "Видавництва",
-        errors: vec![],
-        content: vec![],
-    };
-
-    Template::render("publishers_edit", ctx)
-}
-
-#[post("/publishers/edit?<id>", data = "<form>")]
-pub async fn publishers_edit_post<'r>(
+#[post("/games/team/roles/delete?<id>&<role_id>")]
+pub async fn games_team_roles_delete_post(
     conn: DBConnection,
     id: i32,
-    mut form: Form<Contextual<'r, PublishersForm<'r>>>,
+    role_id: i32,
 ) -> Result<Redirect, Template> {
-    let publisher = std::mem::replace(&mut form.value, None).unwrap().add;
-    let mut errs = Vec::new();
-
-    match publisher {
-        Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
-        }
-        Ok(publisher) => {
-            let publisher = NewPublisher::from(publisher);
-            if let Err(err) = publisher {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) =
-                    PublishersControl::update_publisher(&conn, id, publisher.unwrap())
-                        .await
-                        .err()
-                {
-                    errs.push(err.to_string());
-                }
-            }
-        }
-    }
-
-    if !errs.is_empty() {
-        let publisher = PublishersControl::get_publisher_by_id(&conn, id)
-            .await
-            .unwrap();
-        let ctx = CustomContext::<_, String> {
-            values: vec![publisher],
-            table: "Видавництва",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("publishers_edit", ctx))
-    } else {
-        Ok(Redirect::to(uri!(publishers(None::<i32>))))
+    match TeamsControl::delete_role(&conn, role_id).await {
+        Ok(game_id) => Ok(Redirect::to(uri!(games_team(game_id)))),
+        Err(err) => Err(render_games_team(&conn, id, vec![error_message(&conn, err)]).await),
     }
 }
 
-#[post("/publishers/delete?<id>")]
-pub async fn publishers_delete_post<'r>(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
-    PublishersControl::delete_publisher(&conn, id)
-        .await
-        .unwrap();
-
-    Ok(Redirect::to(uri!(publishers(None::<i32>))))
+#[post("/games/team/assign?<id>&<role_id>&<staff_id>")]
+pub async fn games_team_assign_post(
+    conn: DBConnection,
+    id: i32,
+    role_id: i32,
+    staff_id: i32,
+) -> Result<Redirect, Template> {
+    match TeamsControl::assign(&conn, role_id, staff_id).await {
+        Ok(game_id) => Ok(Redirect::to(uri!(games_team(game_id)))),
+        Err(err) => Err(render_games_team(&conn, id, vec![error_message(&conn, err)]).await),
+    }
 }
 
-#[get("/investors?<id>")]
-pub async fn investors(conn: DBConnection, id: Option<i32>) -> Template {
+async fn render_publishers(conn: &DBConnection, id: Option<i32>, errors: Vec<String>) -> Template {
     let mut content = Vec::new();
-    if id.is_some() {
-        let stat = InvestorsControl::get_statistic(&conn, id.unwrap()).await;
+    if let Some(id) = id {
+        let stat = PublishersControl::get_statistic(conn, id).await;
         content.push(stat);
     }
-
     let ctx = CustomContext {
-        values: InvestorsControl::get_investors(&conn).await.unwrap(),
-        table: "Інвестори",
-        errors: vec![],
+        values: PublishersControl::get_publishers(conn).await.unwrap(),
+        table: "Видавництва",
+        errors,
         content: vec![content],
     };
 
-    Template::render("investors", ctx)
+    Template::render("publishers", ctx)
 }
 
-#[get("/investors/add")]
-pub async fn investors_add() -> Template {
-    let ctx = CustomContext::<String, String> {
-        values: vec![],
-        table: "Інвестори",
-        errors: vec![],
-        content: vec![],
-    };
-
-    Template::render("investors_add", ctx)
+#[get("/publishers?<id>")]
+pub async fn publishers(conn: DBConnection, id: Option<i32>) -> Template {
+    render_publishers(&conn, id, vec![]).await
 }
 
-#[post("/investors/add", data = "<form>")]
-pub async fn investors_add_post<'r>(
+resource_routes!(
+    PublishersControl,
+    PublishersForm,
+    publishers_routes,
+    publishers_add, publishers_add_post => "/publishers/add",
+    publishers_edit => "/publishers/edit?<id>",
+    publishers_edit_post => "/publishers/edit?<id>&<version>",
+    publishers_delete_post => "/publishers/delete?<id>",
+);
+
+#[post("/publishers/delete_selected", data = "<form>")]
+pub async fn publishers_delete_selected_post<'r>(
     conn: DBConnection,
-    mut form: Form<Contextual<'r, InvestorsForm<'r>>>,
+    mut form: Form<Contextual<'r, SelectedRowsForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let investor = std::mem::replace(&mut form.value, None).unwrap().add;
-    let mut errs = Vec::new();
-
-    match investor {
-        Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
-        }
-        Ok(investor) => {
-            let investor = NewInvestor::from(investor);
-            if let Err(err) = investor {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = InvestorsControl::add_investor(&conn, investor.unwrap())
-                    .await
-                    .err()
-                {
-                    errs.push(err.to_string());
-                }
-            }
-        }
-    }
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let errs = match rows {
+        Ok(rows) if !rows.ids.is_empty() => PublishersControl::delete_selected(&conn, rows.ids)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+        _ => vec![ServerError::NothingSelected.to_string()],
+    };
 
     if !errs.is_empty() {
-        let ctx = CustomContext::<String, String> {
-            values: vec![],
-            table: "Інвестори",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("investors_add", ctx))
+        Err(render_publishers(&conn, None, errs).await)
     } else {
-        Ok(Redirect::to(uri!(investors(None::<i32>))))
+        Ok(Redirect::to(uri!(publishers(None::<i32>))))
     }
 }
 
-#[get("/investors/edit?<id>")]
-pub async fn investors_edit<'r>(conn: DBConnection, id: i32) -> Template {
-    let investor = InvestorsControl::get_investor_by_id(&conn, id)
-        .await
-        .unwrap();
-    let ctx = CustomContext::<_, String> {
-        values: vec![investor],
+#[post("/publishers/popularity")]
+pub async fn publishers_popularity_post(conn: DBConnection) -> Result<Redirect, Template> {
+    match PublishersControl::snapshot_popularity(&conn).await {
+        Ok(_) => Ok(Redirect::to(uri!(publishers(None::<i32>)))),
+        Err(err) => Err(render_publishers(&conn, None, vec![error_message(&conn, err)]).await),
+    }
+}
+
+async fn render_investors(conn: &DBConnection, id: Option<i32>, errors: Vec<String>) -> Template {
+    let mut content = Vec::new();
+    if let Some(id) = id {
+        let stat = InvestorsControl::get_statistic(conn, id).await;
+        content.push(stat);
+    }
+    let ctx = CustomContext {
+        values: InvestorsControl::get_investors(conn).await.unwrap(),
         table: "Інвестори",
-        errors: vec![],
-        content: vec![],
+        errors,
+        content: vec![content],
     };
 
-    Template::render("investors_edit", ctx)
+    Template::render("investors", ctx)
 }
 
-#[post("/investors/edit?<id>", data = "<form>")]
-pub async fn investors_edit_post<'r>(
+#[get("/investors?<id>")]
+pub async fn investors(conn: DBConnection, id: Option<i32>) -> Template {
+    render_investors(&conn, id, vec![]).await
+}
+
+resource_routes!(
+    InvestorsControl,
+    InvestorsForm,
+    investors_routes,
+    investors_add, investors_add_post => "/investors/add",
+    investors_edit => "/investors/edit?<id>",
+    investors_edit_post => "/investors/edit?<id>&<version>",
+    investors_delete_post => "/investors/delete?<id>",
+);
+
+#[post("/investors/delete_selected", data = "<form>")]
+pub async fn investors_delete_selected_post<'r>(
     conn: DBConnection,
-    id: i32,
-    mut form: Form<Contextual<'r, InvestorsForm<'r>>>,
+    mut form: Form<Contextual<'r, SelectedRowsForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let investor = std::mem::replace(&mut form.value, None).unwrap().add;
-    let mut errs = Vec::new();
-
-    match investor {
-        Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
-        }
-        Ok(investor) => {
-            let investor = NewInvestor::from(investor);
-            if let Err(err) = investor {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = InvestorsControl::update_investor(&conn, id, investor.unwrap())
-                    .await
-                    .err()
-                {
-                    errs.push(err.to_string());
-                }
-            }
-        }
-    }
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let errs = match rows {
+        Ok(rows) if !rows.ids.is_empty() => InvestorsControl::delete_selected(&conn, rows.ids)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+        _ => vec![ServerError::NothingSelected.to_string()],
+    };
 
     if !errs.is_empty() {
-        let investor = InvestorsControl::get_investor_by_id(&conn, id)
-            .await
-            .unwrap();
-        let ctx = CustomContext::<_, String> {
-            values: vec![investor],
-            table: "Інвестори",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("investors_edit", ctx))
+        Err(render_investors(&conn, None, errs).await)
     } else {
         Ok(Redirect::to(uri!(investors(None::<i32>))))
     }
 }
 
-#[post("/investors/delete?<id>")]
-pub async fn investors_delete_post<'r>(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
-    InvestorsControl::delete_investor(&conn, id).await.unwrap();
-
-    Ok(Redirect::to(uri!(investors(None::<i32>))))
-}
-
-#[get("/staff?<id>")]
-pub async fn staff(conn: DBConnection, id: Option<i32>) -> Template {
+async fn render_staff(conn: &DBConnection, id: Option<i32>, errors: Vec<String>) -> Template {
     let mut content = Vec::new();
-    if id.is_some() {
-        let stat = StaffControl::get_statistic(&conn, id.unwrap()).await;
+    if let Some(id) = id {
+        let stat = StaffControl::get_statistic(conn, id).await;
         content.push(stat);
     }
     let ctx = CustomContext {
-        values: StaffControl::get_staff(&conn).await.unwrap(),
+        values: StaffControl::get_staff(conn).await.unwrap(),
         table: "Працівники",
-        errors: vec![],
+        errors,
         content: vec![content],
     };
 
     Template::render("staff", ctx)
 }
 
-#[get("/staff/add")]
-pub async fn staff_add() -> Template {
-    let ctx = CustomContext::<String, String> {
-        values: vec![],
-        table: "Працівники",
-        errors: vec![],
-        content: vec![],
+#[get("/staff?<id>")]
+pub async fn staff(conn: DBConnection, id: Option<i32>) -> Template {
+    render_staff(&conn, id, vec![]).await
+}
+
+resource_routes!(
+    StaffControl,
+    StaffForm,
+    staff_routes,
+    staff_add, staff_add_post => "/staff/add",
+    staff_edit => "/staff/edit?<id>",
+    staff_edit_post => "/staff/edit?<id>&<version>",
+    staff_delete_post => "/staff/delete?<id>",
+);
+
+#[post("/staff/delete_selected", data = "<form>")]
+pub async fn staff_delete_selected_post<'r>(
+    conn: DBConnection,
+    mut form: Form<Contextual<'r, SelectedRowsForm<'r>>>,
+) -> Result<Redirect, Template> {
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let errs = match rows {
+        Ok(rows) if !rows.ids.is_empty() => StaffControl::delete_selected(&conn, rows.ids)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+        _ => vec![ServerError::NothingSelected.to_string()],
     };
 
-    Template::render("staff_add", ctx)
+    if !errs.is_empty() {
+        Err(render_staff(&conn, None, errs).await)
+    } else {
+        Ok(Redirect::to(uri!(staff(None::<i32>))))
+    }
 }
 
-#[post("/staff/add", data = "<form>")]
-pub async fn staff_add_post<'r>(
+#[post("/staff/hire?<id>", data = "<form>")]
+pub async fn staff_hire_post<'r>(
     conn: DBConnection,
-    mut form: Form<Contextual<'r, StaffForm<'r>>>,
+    id: i32,
+    mut form: Form<Contextual<'r, EmploymentForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let staff = std::mem::replace(&mut form.value, None).unwrap().add;
+    let employment = std::mem::replace(&mut form.value, None).unwrap().add;
     let mut errs = Vec::new();
 
-    match staff {
+    match employment {
         Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
+            errs.push(form_errors(&errors));
         }
-        Ok(staff) => {
-            let staff = NewStaff::from(staff);
-            if let Err(err) = staff {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = StaffControl::add_staff(&conn, staff.unwrap()).await.err() {
-                    errs.push(err.to_string());
+        Ok(employment) => match NaiveDate::parse_from_str(&employment.date, "%Y-%m-%d") {
+            Err(_) => errs.push(ServerError::InvalidDate.to_string()),
+            Ok(date) => {
+                if let Some(err) = StaffControl::hire(&conn, id, date).await.err() {
+                    errs.push(error_message(&conn, err));
                 }
             }
-        }
+        },
     }
 
     if !errs.is_empty() {
-        let ctx = CustomContext::<String, String> {
-            values: vec![],
-            table: "Працівники",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("staff_add", ctx))
+        Err(render_staff(&conn, Some(id), errs).await)
     } else {
-        Ok(Redirect::to(uri!(staff(None::<i32>))))
+        Ok(Redirect::to(uri!(staff(Some(id)))))
     }
 }
 
-#[get("/staff/edit?<id>")]
-pub async fn staff_edit<'r>(conn: DBConnection, id: i32) -> Template {
-    let mut staff = StaffControl::get_staff_by_id(&conn, id).await.unwrap();
-    staff.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-
-    let ctx = CustomContext::<_, String> {
-        values: vec![staff],
-        table: "Працівники",
-        errors: vec![],
-        content: vec![],
-    };
-
-    Template::render("staff_edit", ctx)
-}
-
-#[post("/staff/edit?<id>", data = "<form>")]
-pub async fn staff_edit_post<'r>(
+#[post("/staff/fire?<id>", data = "<form>")]
+pub async fn staff_fire_post<'r>(
     conn: DBConnection,
     id: i32,
-    mut form: Form<Contextual<'r, StaffForm<'r>>>,
+    mut form: Form<Contextual<'r, EmploymentForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let staff = std::mem::replace(&mut form.value, None).unwrap().add;
+    let employment = std::mem::replace(&mut form.value, None).unwrap().add;
     let mut errs = Vec::new();
 
-    match staff {
+    match employment {
         Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
+            errs.push(form_errors(&errors));
         }
-        Ok(staff) => {
-            let staff = NewStaff::from(staff);
-            if let Err(err) = staff {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = StaffControl::update_staff(&conn, id, staff.unwrap())
-                    .await
-                    .err()
-                {
-                    errs.push(err.to_string());
+        Ok(employment) => match NaiveDate::parse_from_str(&employment.date, "%Y-%m-%d") {
+            Err(_) => errs.push(ServerError::InvalidDate.to_string()),
+            Ok(date) => {
+                if let Some(err) = StaffControl::fire(&conn, id, date).await.err() {
+                    errs.push(error_message(&conn, err));
                 }
             }
-        }
+        },
     }
 
     if !errs.is_empty() {
-        let mut staff = StaffControl::get_staff_by_id(&conn, id).await.unwrap();
-        staff.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-        let ctx = CustomContext::<_, String> {
-            values: vec![staff],
-            table: "Працівники",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("staff_edit", ctx))
+        Err(render_staff(&conn, Some(id), errs).await)
     } else {
-        Ok(Redirect::to(uri!(staff(None::<i32>))))
+        Ok(Redirect::to(uri!(staff(Some(id)))))
     }
 }
 
-#[post("/staff/delete?<id>")]
-pub async fn staff_delete_post<'r>(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
-    StaffControl::delete_staff(&conn, id).await.unwrap();
+#[get("/staff/org")]
+pub async fn staff_org(conn: DBConnection) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: StaffControl::get_org_chart(&conn).await.unwrap(),
+        table: "Структура студії",
+        errors: vec![],
+        content: vec![],
+    };
 
-    Ok(Redirect::to(uri!(staff(None::<i32>))))
+    Template::render("staff_org", ctx)
 }
 
-#[get("/users?<id>")]
-pub async fn users(conn: DBConnection, id: Option<i32>) -> Template {
+async fn render_users(conn: &DBConnection, id: Option<i32>, errors: Vec<String>) -> Template {
     let mut content = Vec::new();
-    if id.is_some() {
-        let stat = UsersControl::get_statistic(&conn, id.unwrap()).await;
+    if let Some(id) = id {
+        let stat = UsersControl::get_statistic(conn, id).await;
         content.push(stat);
     }
     let ctx = CustomContext {
-        values: UsersControl::get_users(&conn).await.unwrap(),
+        values: UsersControl::get_users(conn).await.unwrap(),
         table: "Користувачі",
-        errors: vec![],
+        errors,
         content: vec![content],
     };
 
     Template::render("users", ctx)
 }
 
-#[get("/users/add")]
-pub async fn users_add() -> Template {
-    let ctx = CustomContext::<String, String> {
-        values: vec![],
-        table: "Користувачі",
-        errors: vec![],
-        content: vec![],
-    };
-
-    Template::render("users_add", ctx)
+#[get("/users?<id>")]
+pub async fn users(conn: DBConnection, id: Option<i32>) -> Template {
+    render_users(&conn, id, vec![]).await
 }
 
-#[post("/users/add", data = "<form>")]
-pub async fn users_add_post<'r>(
+resource_routes!(
+    UsersControl,
+    UsersForm,
+    users_routes,
+    users_add, users_add_post => "/users/add",
+    users_edit => "/users/edit?<id>",
+    users_edit_post => "/users/edit?<id>&<version>",
+    users_delete_post => "/users/delete?<id>",
+);
+
+#[post("/users/delete_selected", data = "<form>")]
+pub async fn users_delete_selected_post<'r>(
     conn: DBConnection,
-    mut form: Form<Contextual<'r, UsersForm<'r>>>,
+    mut form: Form<Contextual<'r, SelectedRowsForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let users = std::mem::replace(&mut form.value, None).unwrap().add;
-    let mut errs = Vec::new();
-
-    match users {
-        Err(errors) => {
-            let nicknames = errors
-                .iter()
-                .map(|err| {
-                    let nickname = err.name.as_ref().unwrap().to_string();
-                    nickname
-                        .rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(nicknames).to_string());
-        }
-        Ok(users) => {
-            let users = NewUser::from(users);
-            if let Err(err) = users {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = UsersControl::add_user(&conn, users.unwrap()).await.err() {
-                    errs.push(err.to_string());
-                }
-            }
-        }
-    }
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let errs = match rows {
+        Ok(rows) if !rows.ids.is_empty() => UsersControl::delete_selected(&conn, rows.ids)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+        _ => vec![ServerError::NothingSelected.to_string()],
+    };
 
     if !errs.is_empty() {
-        let ctx = CustomContext::<String, String> {
-            values: vec![],
-            table: "Користувачі",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("users_add", ctx))
+        Err(render_users(&conn, None, errs).await)
     } else {
         Ok(Redirect::to(uri!(users(None::<i32>))))
     }
 }
 
-#[get("/users/edit?<id>")]
-pub async fn users_edit<'r>(conn: DBConnection, id: i32) -> Template {
-    let mut users = UsersControl::get_user_by_id(&conn, id).await.unwrap();
-    users.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-
+async fn render_donations(conn: &DBConnection, errors: Vec<String>) -> Template {
     let ctx = CustomContext::<_, String> {
-        values: vec![users],
-        table: "Користувачі",
-        errors: vec![],
+        values: DonationsControl::get_donations(conn).await.unwrap(),
+        table: "Донати",
+        errors,
         content: vec![],
     };
 
-    Template::render("users_edit", ctx)
+    Template::render("donations", ctx)
+}
+
+#[get("/donations")]
+pub async fn donations(conn: DBConnection) -> Template {
+    render_donations(&conn, vec![]).await
+}
+
+async fn render_donations_refund(conn: &DBConnection, id: i32, errors: Vec<String>) -> Template {
+    let ctx = CustomContext {
+        values: vec![DonationsControl::get_donation_by_id(conn, id)
+            .await
+            .unwrap()],
+        table: "Повернення донату",
+        errors,
+        content: vec![DonationRefundsControl::get_refunds(conn, id).await.unwrap()],
+    };
+
+    Template::render("donations_refund", ctx)
+}
+
+#[get("/donations/refund?<id>")]
+pub async fn donations_refund(conn: DBConnection, id: i32) -> Template {
+    render_donations_refund(&conn, id, vec![]).await
 }
 
-#[post("/users/edit?<id>", data = "<form>")]
-pub async fn users_edit_post<'r>(
+#[post("/donations/refund?<id>", data = "<form>")]
+pub async fn donations_refund_post<'r>(
     conn: DBConnection,
     id: i32,
-    mut form: Form<Contextual<'r, UsersForm<'r>>>,
+    mut form: Form<Contextual<'r, RefundsForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let users = std::mem::replace(&mut form.value, None).unwrap().add;
+    let refund = std::mem::replace(&mut form.value, None).unwrap().add;
     let mut errs = Vec::new();
 
-    match users {
+    match refund {
         Err(errors) => {
-            let nicknames = errors
-                .iter()
-                .map(|err| {
-                    let nickname = err.name.as_ref().unwrap().to_string();
-                    nickname
-                        .rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(nicknames).to_string());
+            errs.push(form_errors(&errors));
         }
-        Ok(users) => {
-            let users = NewUser::from(users);
-            if let Err(err) = users {
-                errs.push(err.to_string());
+        Ok(refund) => {
+            let refund = NewRefund::from(id, refund);
+            if let Err(err) = refund {
+                errs.push(error_message(&conn, err));
             } else {
-                if let Some(err) = UsersControl::update_user(&conn, id, users.unwrap())
+                if let Some(err) = DonationRefundsControl::add_refund(&conn, refund.unwrap())
                     .await
                     .err()
                 {
-                    errs.push(err.to_string());
+                    errs.push(error_message(&conn, err));
                 }
             }
         }
     }
 
     if !errs.is_empty() {
-        let mut users = UsersControl::get_user_by_id(&conn, id).await.unwrap();
-        users.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-        let ctx = CustomContext::<_, String> {
-            values: vec![users],
-            table: "Користувачі",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("users_edit", ctx))
+        Err(render_donations_refund(&conn, id, errs).await)
     } else {
-        Ok(Redirect::to(uri!(users(None::<i32>))))
+        Ok(Redirect::to(format!("/donations/refund?id={}", id)))
     }
 }
 
-#[post("/users/delete?<id>")]
-pub async fn users_delete_post<'r>(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
-    UsersControl::delete_users(&conn, id).await.unwrap();
+async fn render_donations_review(conn: &DBConnection, errors: Vec<String>) -> Template {
+    let (pending, reviewed) = DonationFlagsControl::get_flags(conn).await.unwrap();
+
+    let ctx = CustomContext {
+        values: pending,
+        table: "Перевірка донатів",
+        errors,
+        content: vec![reviewed],
+    };
 
-    Ok(Redirect::to(uri!(users(None::<i32>))))
+    Template::render("donations_review", ctx)
 }
 
-#[get("/donations")]
-pub async fn donations(conn: DBConnection) -> Template {
-    let ctx = CustomContext::<_, String> {
-        values: DonationsControl::get_donations(&conn).await.unwrap(),
-        table: "Донати",
-        errors: vec![],
-        content: vec![],
-    };
+#[get("/donations/review")]
+pub async fn donations_review(conn: DBConnection) -> Template {
+    render_donations_review(&conn, vec![]).await
+}
 
-    Template::render("donations", ctx)
+#[post("/donations/review/approve?<id>")]
+pub async fn donations_review_approve_post(
+    conn: DBConnection,
+    id: i32,
+) -> Result<Redirect, Template> {
+    if let Some(err) = DonationFlagsControl::approve(&conn, id).await.err() {
+        return Err(render_donations_review(&conn, vec![error_message(&conn, err)]).await);
+    }
+
+    Ok(Redirect::to(uri!(donations_review)))
 }
 
-#[get("/donations/add")]
-pub async fn donations_add(conn: DBConnection) -> Template {
-    let users = UsersControl::get_users(&conn).await.unwrap();
-    let users_id = users.iter().map(|user| user.id.to_string()).collect();
+#[post("/donations/review/reverse?<id>")]
+pub async fn donations_review_reverse_post(
+    conn: DBConnection,
+    id: i32,
+) -> Result<Redirect, Template> {
+    if let Some(err) = DonationFlagsControl::reverse(&conn, id).await.err() {
+        return Err(render_donations_review(&conn, vec![error_message(&conn, err)]).await);
+    }
 
-    let users_name = users.into_iter().map(|user| user.nickname).collect();
+    Ok(Redirect::to(uri!(donations_review)))
+}
 
-    let games = GamesControl::get_games(&conn).await.unwrap();
-    let games_id = games.iter().map(|game| game.id.to_string()).collect();
+resource_routes!(
+    DonationsControl,
+    DonationsForm,
+    donations_routes,
+    donations_add, donations_add_post => "/donations/add",
+    donations_edit => "/donations/edit?<id>",
+    donations_edit_post => "/donations/edit?<id>&<version>",
+    donations_delete_post => "/donations/delete?<id>",
+);
 
-    let games_name = games.into_iter().map(|game| game.name).collect();
+#[post("/donations/delete_selected", data = "<form>")]
+pub async fn donations_delete_selected_post<'r>(
+    conn: DBConnection,
+    mut form: Form<Contextual<'r, SelectedRowsForm<'r>>>,
+) -> Result<Redirect, Template> {
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let errs = match rows {
+        Ok(rows) if !rows.ids.is_empty() => DonationsControl::delete_selected(&conn, rows.ids)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+        _ => vec![ServerError::NothingSelected.to_string()],
+    };
 
-    let ctx = CustomContext::<String, String> {
-        values: vec![],
-        table: "Донати",
-        errors: vec![],
-        content: vec![users_id, users_name, games_id, games_name],
+    if !errs.is_empty() {
+        Err(render_donations(&conn, errs).await)
+    } else {
+        Ok(Redirect::to(uri!(donations)))
+    }
+}
+
+async fn render_jobs(conn: &DBConnection, errors: Vec<String>) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: JobsControl::get_jobs(conn).await.unwrap(),
+        table: "Позиції працівників",
+        errors,
+        content: vec![],
     };
 
-    Template::render("donations_add", ctx)
+    Template::render("jobs", ctx)
+}
+
+#[get("/jobs")]
+pub async fn jobs(conn: DBConnection) -> Template {
+    render_jobs(&conn, vec![]).await
 }
 
-#[post("/donations/add", data = "<form>")]
-pub async fn donations_add_post<'r>(
+resource_routes!(
+    JobsControl,
+    JobsForm,
+    jobs_routes,
+    jobs_add, jobs_add_post => "/jobs/add",
+    jobs_edit => "/jobs/edit?<id>",
+    jobs_edit_post => "/jobs/edit?<id>&<version>",
+    jobs_delete_post => "/jobs/delete?<id>",
+);
+
+#[post("/jobs/raise?<id>", data = "<form>")]
+pub async fn jobs_raise_post<'r>(
     conn: DBConnection,
-    mut form: Form<Contextual<'r, DonationsForm<'r>>>,
+    id: i32,
+    mut form: Form<Contextual<'r, RaisesForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let donation = std::mem::replace(&mut form.value, None).unwrap().add;
+    let raise = std::mem::replace(&mut form.value, None).unwrap().add;
     let mut errs = Vec::new();
 
-    match donation {
+    match raise {
         Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
+            errs.push(form_errors(&errors));
         }
-        Ok(donation) => {
-            let donation = NewDonation::from(donation);
-            if let Err(err) = donation {
-                errs.push(err.to_string());
+        Ok(raise) => {
+            let change = NewSalaryChange::from(id, raise);
+            if let Err(err) = change {
+                errs.push(error_message(&conn, err));
             } else {
-                if let Some(err) = DonationsControl::add_donation(&conn, donation.unwrap())
+                if let Some(err) = JobsControl::raise_salary(&conn, change.unwrap())
                     .await
                     .err()
                 {
-                    errs.push(err.to_string());
+                    errs.push(error_message(&conn, err));
                 }
             }
         }
     }
 
     if !errs.is_empty() {
-        let ctx = CustomContext::<String, String> {
-            values: vec![],
-            table: "Донати",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("donations_add", ctx))
+        Err(render_resource_edit::<JobsControl>(&conn, id, errs).await)
     } else {
-        Ok(Redirect::to(uri!(donations)))
+        Ok(Redirect::to(format!("/jobs/edit?id={}", id)))
     }
 }
 
-#[get("/donations/edit?<id>")]
-pub async fn donations_edit<'r>(conn: DBConnection, id: i32) -> Template {
-    let mut donation = DonationsControl::get_donation_by_id(&conn, id)
-        .await
-        .unwrap();
-    donation
-        .change_date_format("%d-%m-%Y, %H:%M", "%Y-%m-%dT%H:%M")
-        .unwrap();
-    let users = UsersControl::get_users(&conn).await.unwrap();
-    let users_id = users.iter().map(|user| user.id.to_string()).collect();
+#[get("/payroll?<month>")]
+pub async fn payroll(conn: DBConnection, month: Option<String>) -> Template {
+    let mut errors = vec![];
+    let today = Local::today().naive_local();
+    let mut from = NaiveDate::from_ymd(today.year(), today.month(), 1);
+    if let Some(month) = month.filter(|month| !month.is_empty()) {
+        match NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d") {
+            Ok(first_day) => from = first_day,
+            Err(_) => errors.push(ServerError::InvalidDate.to_string()),
+        }
+    }
+    let next_month = if from.month() == 12 {
+        NaiveDate::from_ymd(from.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd(from.year(), from.month() + 1, 1)
+    };
+    let to = next_month.pred();
 
-    let users_name = users.into_iter().map(|user| user.nickname).collect();
+    let (payroll, total) = JobsControl::get_payroll(&conn, from, to).await.unwrap();
+    let ctx = CustomContext {
+        values: payroll,
+        table: "Зарплатна відомість",
+        errors,
+        content: vec![vec![
+            from.format("%Y-%m").to_string(),
+            format!("{:.2}", total),
+        ]],
+    };
 
-    let games = GamesControl::get_games(&conn).await.unwrap();
-    let games_id = games.iter().map(|game| game.id.to_string()).collect();
+    Template::render("payroll", ctx)
+}
+
+#[post("/jobs/delete_selected", data = "<form>")]
+pub async fn jobs_delete_selected_post<'r>(
+    conn: DBConnection,
+    mut form: Form<Contextual<'r, SelectedRowsForm<'r>>>,
+) -> Result<Redirect, Template> {
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let errs = match rows {
+        Ok(rows) if !rows.ids.is_empty() => JobsControl::delete_selected(&conn, rows.ids)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+        _ => vec![ServerError::NothingSelected.to_string()],
+    };
 
-    let games_name = games.into_iter().map(|game| game.name).collect();
+    if !errs.is_empty() {
+        Err(render_jobs(&conn, errs).await)
+    } else {
+        Ok(Redirect::to(uri!(jobs)))
+    }
+}
 
+async fn render_jobs_bulk(conn: &DBConnection, ids: Vec<i32>, errors: Vec<String>) -> Template {
+    let jobs = JobsControl::get_jobs(conn).await.unwrap();
     let ctx = CustomContext::<_, String> {
-        values: vec![donation],
-        table: "Донати",
-        errors: vec![],
-        content: vec![users_id, users_name, games_id, games_name],
+        values: jobs
+            .into_iter()
+            .filter(|job| ids.contains(&job.id))
+            .collect(),
+        table: "Позиції працівників",
+        errors,
+        content: vec![],
     };
 
-    Template::render("donations_edit", ctx)
+    Template::render("jobs_bulk", ctx)
 }
 
-#[post("/donations/edit?<id>", data = "<form>")]
-pub async fn donations_edit_post<'r>(
+#[get("/jobs/bulk?<rows>")]
+pub async fn jobs_bulk(conn: DBConnection, rows: Option<SelectedRows>) -> Template {
+    match rows {
+        Some(rows) if !rows.ids.is_empty() => render_jobs_bulk(&conn, rows.ids, vec![]).await,
+        _ => render_jobs(&conn, vec![ServerError::NothingSelected.to_string()]).await,
+    }
+}
+
+#[post("/jobs/bulk", data = "<form>")]
+pub async fn jobs_bulk_post<'r>(
     conn: DBConnection,
-    id: i32,
-    mut form: Form<Contextual<'r, DonationsForm<'r>>>,
+    mut form: Form<Contextual<'r, JobsBulkForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let donation = std::mem::replace(&mut form.value, None).unwrap().add;
-    let mut errs = Vec::new();
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let rows = match rows {
+        Ok(rows) if !rows.ids.is_empty() => rows,
+        _ => return Err(render_jobs(&conn, vec![ServerError::NothingSelected.to_string()]).await),
+    };
 
-    match donation {
-        Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
-        }
-        Ok(donation) => {
-            let donation = NewDonation::from(donation);
-            if let Err(err) = donation {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = DonationsControl::update_donation(&conn, id, donation.unwrap())
-                    .await
-                    .err()
-                {
-                    errs.push(err.to_string());
-                }
-            }
-        }
-    }
+    let errs = match NaiveDate::parse_from_str(&rows.last_work_day, "%Y-%m-%d") {
+        Err(_) => vec![ServerError::InvalidDate.to_string()],
+        Ok(last_day) => JobsControl::close_selected(&conn, rows.ids.clone(), last_day)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+    };
 
     if !errs.is_empty() {
-        let mut donation = DonationsControl::get_donation_by_id(&conn, id)
-            .await
-            .unwrap();
-        donation.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-        let ctx = CustomContext::<_, String> {
-            values: vec![donation],
-            table: "Донати",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("donations_edit", ctx))
+        Err(render_jobs_bulk(&conn, rows.ids, errs).await)
     } else {
-        Ok(Redirect::to(uri!(donations)))
+        Ok(Redirect::to(uri!(jobs)))
     }
 }
 
-#[post("/donations/delete?<id>")]
-pub async fn donations_delete_post<'r>(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
-    DonationsControl::delete_donation(&conn, id).await.unwrap();
+async fn render_investments(conn: &DBConnection, errors: Vec<String>) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: InvestmentsControl::get_investments(conn).await.unwrap(),
+        table: "Інвестиції",
+        errors,
+        content: vec![],
+    };
 
-    Ok(Redirect::to(uri!(donations)))
+    Template::render("investments", ctx)
 }
 
-#[get("/jobs")]
-pub async fn jobs(conn: DBConnection) -> Template {
+#[get("/investments")]
+pub async fn investments(conn: DBConnection) -> Template {
+    render_investments(&conn, vec![]).await
+}
+
+resource_routes!(
+    InvestmentsControl,
+    InvestmentsForm,
+    investments_routes,
+    investments_add, investments_add_post => "/investments/add",
+    investments_edit => "/investments/edit?<id>",
+    investments_edit_post => "/investments/edit?<id>&<version>",
+    investments_delete_post => "/investments/delete?<id>",
+);
+
+#[post("/investments/delete_selected", data = "<form>")]
+pub async fn investments_delete_selected_post<'r>(
+    conn: DBConnection,
+    mut form: Form<Contextual<'r, SelectedRowsForm<'r>>>,
+) -> Result<Redirect, Template> {
+    let rows = std::mem::replace(&mut form.value, None).unwrap().rows;
+    let errs = match rows {
+        Ok(rows) if !rows.ids.is_empty() => InvestmentsControl::delete_selected(&conn, rows.ids)
+            .await
+            .unwrap_or_else(|err| vec![error_message(&conn, err)]),
+        _ => vec![ServerError::NothingSelected.to_string()],
+    };
+
+    if !errs.is_empty() {
+        Err(render_investments(&conn, errs).await)
+    } else {
+        Ok(Redirect::to(uri!(investments)))
+    }
+}
+
+#[get("/contracts")]
+pub async fn contracts(conn: DBConnection) -> Template {
     let ctx = CustomContext::<_, String> {
-        values: JobsControl::get_jobs(&conn).await.unwrap(),
-        table: "Позиції працівників",
+        values: ContractsControl::get_contracts(&conn).await.unwrap(),
+        table: "Контракти",
         errors: vec![],
         content: vec![],
     };
 
-    Template::render("jobs", ctx)
+    Template::render("contracts", ctx)
 }
 
-#[get("/jobs/add")]
-pub async fn jobs_add(conn: DBConnection) -> Template {
-    let staff = StaffControl::get_staff(&conn).await.unwrap();
-    let staff_id = staff.iter().map(|user| user.id.to_string()).collect();
+resource_routes!(
+    ContractsControl,
+    ContractsForm,
+    contracts_routes,
+    contracts_add, contracts_add_post => "/contracts/add",
+    contracts_edit => "/contracts/edit?<id>",
+    contracts_edit_post => "/contracts/edit?<id>&<version>",
+    contracts_delete_post => "/contracts/delete?<id>",
+);
 
-    let staff_name = staff.into_iter().map(|user| user.name).collect();
+#[get("/sales")]
+pub async fn sales(conn: DBConnection) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: SalesControl::get_sales(&conn).await.unwrap(),
+        table: "Продажі",
+        errors: vec![],
+        content: vec![],
+    };
 
-    let games = GamesControl::get_games(&conn).await.unwrap();
-    let games_id = games.iter().map(|game| game.id.to_string()).collect();
+    Template::render("sales", ctx)
+}
 
-    let games_name = games.into_iter().map(|game| game.name).collect();
+resource_routes!(
+    SalesControl,
+    SalesForm,
+    sales_routes,
+    sales_add, sales_add_post => "/sales/add",
+    sales_edit => "/sales/edit?<id>",
+    sales_edit_post => "/sales/edit?<id>&<version>",
+    sales_delete_post => "/sales/delete?<id>",
+);
 
-    let ctx = CustomContext::<String, String> {
-        values: vec![],
-        table: "Позиції працівників",
+#[get("/revenue")]
+pub async fn revenue(conn: DBConnection) -> Template {
+    let (revenue, total) = RevenueControl::get_revenue(&conn, None).await.unwrap();
+    let ctx = CustomContext {
+        values: revenue,
+        table: "Доходи",
         errors: vec![],
-        content: vec![games_id, games_name, staff_id, staff_name],
+        content: vec![vec![total]],
     };
 
-    Template::render("jobs_add", ctx)
+    Template::render("revenue", ctx)
 }
 
-#[post("/jobs/add", data = "<form>")]
-pub async fn jobs_add_post<'r>(
-    conn: DBConnection,
-    mut form: Form<Contextual<'r, JobsForm<'r>>>,
-) -> Result<Redirect, Template> {
-    let job = std::mem::replace(&mut form.value, None).unwrap().add;
-    let mut errs = Vec::new();
+/// A generated report, shown in the browser and saved under its own name.
+#[derive(Responder)]
+pub struct ReportFile {
+    body: Vec<u8>,
+    content_type: ContentType,
+    disposition: Header<'static>,
+}
 
-    match job {
-        Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
-        }
-        Ok(job) => {
-            let job = NewJob::from(job);
-            if let Err(err) = job {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = JobsControl::add_job(&conn, job.unwrap()).await.err() {
-                    errs.push(err.to_string());
-                }
-            }
-        }
-    }
+async fn render_reports(conn: &DBConnection, errors: Vec<String>) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: GamesControl::get_games(conn).await.unwrap(),
+        table: "Звіти для друку",
+        errors,
+        content: vec![],
+    };
 
-    if !errs.is_empty() {
-        let ctx = CustomContext::<String, String> {
-            values: vec![],
-            table: "Позиції працівників",
-            errors: errs,
-            content: vec![],
+    Template::render("reports", ctx)
+}
+
+async fn report_file(
+    conn: &DBConnection,
+    config: &AppConfig,
+    id: Option<i32>,
+    year: Option<i32>,
+    quarter: Option<u32>,
+    format: Option<String>,
+) -> Result<ReportFile, Template> {
+    let file = async {
+        if !config.features.reports {
+            return Err(ServerError::FeatureDisabled("reports".to_string()).into());
+        }
+        let format = ReportFormat::parse(format.as_deref().unwrap_or("html"))?;
+        let quarter = Quarter::parse(year, quarter)?;
+        let report = match id {
+            Some(id) => Report::game(conn, id, quarter).await?,
+            None => Report::studio(conn, quarter).await?,
         };
-        Err(Template::render("jobs_add", ctx))
-    } else {
-        Ok(Redirect::to(uri!(jobs)))
+        let content_type = match format {
+            ReportFormat::Html => ContentType::HTML,
+            ReportFormat::Pdf => ContentType::PDF,
+        };
+        let disposition = Header::new(
+            "Content-Disposition",
+            format!("inline; filename=\"{}\"", report.file_name(&format)),
+        );
+        Ok::<_, anyhow::Error>(ReportFile {
+            body: report.render(&format)?,
+            content_type,
+            disposition,
+        })
+    };
+
+    match file.await {
+        Ok(file) => Ok(file),
+        Err(err) => Err(render_reports(conn, vec![error_message(conn, err)]).await),
     }
 }
 
-#[get("/jobs/edit?<id>")]
-pub async fn jobs_edit<'r>(conn: DBConnection, id: i32) -> Template {
-    let mut job = JobsControl::get_job_by_id(&conn, id).await.unwrap();
-    job.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-    let staff = StaffControl::get_staff(&conn).await.unwrap();
-    let staff_id = staff.iter().map(|user| user.id.to_string()).collect();
-
-    let staff_name = staff.into_iter().map(|user| user.name).collect();
+#[get("/reports")]
+pub async fn reports(conn: DBConnection) -> Template {
+    render_reports(&conn, vec![]).await
+}
 
-    let games = GamesControl::get_games(&conn).await.unwrap();
-    let games_id = games.iter().map(|game| game.id.to_string()).collect();
+#[get("/reports/studio?<year>&<quarter>&<format>")]
+pub async fn reports_studio(
+    conn: DBConnection,
+    config: &State<AppConfig>,
+    year: Option<i32>,
+    quarter: Option<u32>,
+    format: Option<String>,
+) -> Result<ReportFile, Template> {
+    report_file(&conn, config, None, year, quarter, format).await
+}
 
-    let games_name = games.into_iter().map(|game| game.name).collect();
+#[get("/reports/games?<id>&<year>&<quarter>&<format>")]
+pub async fn reports_games(
+    conn: DBConnection,
+    config: &State<AppConfig>,
+    id: i32,
+    year: Option<i32>,
+    quarter: Option<u32>,
+    format: Option<String>,
+) -> Result<ReportFile, Template> {
+    report_file(&conn, config, Some(id), year, quarter, format).await
+}
 
-    let ctx = CustomContext::<_, String> {
-        values: vec![job],
-        table: "Позиції працівників",
-        errors: vec![],
-        content: vec![games_id, games_name, staff_id, staff_name],
+async fn render_schedule(
+    conn: &DBConnection,
+    schedule: &Schedule,
+    errors: Vec<String>,
+) -> Template {
+    let ctx = CustomContext {
+        values: schedule.statuses(conn).await.unwrap(),
+        table: "Розклад фонових завдань",
+        errors,
+        content: vec![ScheduledRunsControl::get_history(conn).await.unwrap()],
     };
 
-    Template::render("jobs_edit", ctx)
+    Template::render("schedule", ctx)
 }
 
-#[post("/jobs/edit?<id>", data = "<form>")]
-pub async fn jobs_edit_post<'r>(
+#[get("/schedule")]
+pub async fn schedule(conn: DBConnection, schedule: &State<Schedule>) -> Template {
+    render_schedule(&conn, schedule, vec![]).await
+}
+
+#[post("/schedule/run?<task>")]
+pub async fn schedule_run_post(
     conn: DBConnection,
-    id: i32,
-    mut form: Form<Contextual<'r, JobsForm<'r>>>,
+    pool: DBPool,
+    schedule: &State<Schedule>,
+    task: &str,
 ) -> Result<Redirect, Template> {
-    let job = std::mem::replace(&mut form.value, None).unwrap().add;
-    let mut errs = Vec::new();
+    let outcome = match Task::parse(task) {
+        Ok(task) => schedule.run(&pool, task).await,
+        Err(err) => Err(err.into()),
+    };
 
-    match job {
-        Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
-        }
-        Ok(job) => {
-            let job = NewJob::from(job);
-            if let Err(err) = job {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) = JobsControl::update_job(&conn, id, job.unwrap()).await.err() {
-                    errs.push(err.to_string());
-                }
-            }
-        }
+    match outcome {
+        Ok(_) => Ok(Redirect::to("/schedule")),
+        Err(err) => Err(render_schedule(&conn, schedule, vec![error_message(&conn, err)]).await),
     }
+}
 
-    if !errs.is_empty() {
-        let mut job = JobsControl::get_job_by_id(&conn, id).await.unwrap();
-        job.change_date_format("%d-%m-%Y", "%Y-%m-%d").unwrap();
-        let ctx = CustomContext::<_, String> {
-            values: vec![job],
-            table: "Позиції працівників",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("jobs_edit", ctx))
+/// Error pages name the request, so the log lines of a reported error can
+/// be found.
+#[catch(default)]
+pub fn error_page(status: Status, request: &Request) -> Template {
+    let message = match status.code {
+        404 => "Сторінку не знайдено".to_string(),
+        500 => "Внутрішня помилка сервера".to_string(),
+        503 => "База даних недоступна, спробуйте пізніше".to_string(),
+        _ => status.reason().unwrap_or_default().to_string(),
+    };
+    let ctx = CustomContext::<String, String> {
+        values: vec![],
+        table: &format!("Помилка {}", status.code),
+        errors: vec![message, format!("Код запиту: {}", RequestId::of(request))],
+        content: vec![],
+    };
+
+    Template::render("error", ctx)
+}
+
+/// Liveness probe: the process is up.
+#[get("/healthz")]
+pub fn healthz() -> (ContentType, String) {
+    (ContentType::JSON, json!(health::health()).to_string())
+}
+
+/// Readiness probe: the database answers and its schema is up to date.
+#[get("/readyz")]
+pub async fn readyz(conn: Option<DBConnection>) -> (Status, (ContentType, String)) {
+    let readiness = health::readiness(conn).await;
+    let status = if readiness.is_ready() {
+        Status::Ok
     } else {
-        Ok(Redirect::to(uri!(jobs)))
-    }
+        Status::ServiceUnavailable
+    };
+
+    (status, (ContentType::JSON, json!(readiness).to_string()))
 }
 
-#[post("/jobs/delete?<id>")]
-pub async fn jobs_delete_post<'r>(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
-    JobsControl::delete_job(&conn, id).await.unwrap();
+#[get("/metrics")]
+pub async fn metrics_page(
+    _access: MetricsAccess,
+    conn: Option<DBConnection>,
+) -> (ContentType, String) {
+    let totals = match conn {
+        Some(conn) => StatisticsControl::get_totals(&conn, Local::today().naive_local())
+            .await
+            .ok(),
+        None => None,
+    };
 
-    Ok(Redirect::to(uri!(jobs)))
+    (ContentType::Plain, metrics::render(totals.as_ref()))
 }
 
-#[get("/investments")]
-pub async fn investments(conn: DBConnection) -> Template {
-    let ctx = CustomContext::<_, String> {
-        values: InvestmentsControl::get_investments(&conn).await.unwrap(),
-        table: "Інвестиції",
+#[get("/cache")]
+pub async fn cache_page() -> Template {
+    let ctx = CustomContext {
+        values: vec![cache::stats()],
+        table: "Кеш сторінок",
         errors: vec![],
+        content: vec![cache::entries()],
+    };
+
+    Template::render("cache", ctx)
+}
+
+#[post("/cache/clear")]
+pub async fn cache_clear_post() -> Redirect {
+    cache::clear();
+    Redirect::to("/cache")
+}
+
+async fn render_subscriptions(conn: &DBConnection, errors: Vec<String>) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: SubscriptionsControl::get_subscriptions(conn).await.unwrap(),
+        table: "Підписки",
+        errors,
         content: vec![],
     };
 
-    Template::render("investments", ctx)
+    Template::render("subscriptions", ctx)
 }
 
-#[get("/investments/add")]
-pub async fn investments_add(conn: DBConnection) -> Template {
-    let investors = InvestorsControl::get_investors(&conn).await.unwrap();
-    let investors_id = investors.iter().map(|user| user.id.to_string()).collect();
+#[get("/subscriptions")]
+pub async fn subscriptions(conn: DBConnection) -> Template {
+    render_subscriptions(&conn, vec![]).await
+}
 
-    let investors_name = investors.into_iter().map(|user| user.name).collect();
+resource_routes!(
+    SubscriptionsControl,
+    SubscriptionsForm,
+    subscriptions_routes,
+    subscriptions_add, subscriptions_add_post => "/subscriptions/add",
+    subscriptions_edit => "/subscriptions/edit?<id>",
+    subscriptions_edit_post => "/subscriptions/edit?<id>&<version>",
+    subscriptions_delete_post => "/subscriptions/delete?<id>",
+);
 
-    let games = GamesControl::get_games(&conn).await.unwrap();
-    let games_id = games.iter().map(|game| game.id.to_string()).collect();
+#[post("/subscriptions/renew?<id>")]
+pub async fn subscriptions_renew_post(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
+    if let Err(err) = SubscriptionsControl::renew_subscription(&conn, id).await {
+        return Err(render_subscriptions(&conn, vec![error_message(&conn, err)]).await);
+    }
 
-    let games_name = games.into_iter().map(|game| game.name).collect();
+    Ok(Redirect::to(uri!(subscriptions)))
+}
 
-    let ctx = CustomContext::<String, String> {
-        values: vec![],
-        table: "Інвестиції",
-        errors: vec![],
-        content: vec![games_id, games_name, investors_id, investors_name],
+#[post("/subscriptions/cancel?<id>")]
+pub async fn subscriptions_cancel_post(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
+    if let Err(err) = SubscriptionsControl::cancel_subscription(&conn, id).await {
+        return Err(render_subscriptions(&conn, vec![error_message(&conn, err)]).await);
+    }
+
+    Ok(Redirect::to(uri!(subscriptions)))
+}
+
+async fn render_payouts(conn: &DBConnection, errors: Vec<String>) -> Template {
+    let returns = InvestorsControl::get_returns(conn).await.unwrap();
+    let breakdown = InvestorReturnsControl::breakdown(&returns);
+
+    let ctx = CustomContext {
+        values: PayoutsControl::get_payouts(conn).await.unwrap(),
+        table: "Виплати інвесторам",
+        errors,
+        content: vec![breakdown, returns],
     };
 
-    Template::render("investments_add", ctx)
+    Template::render("payouts", ctx)
 }
 
-#[post("/investments/add", data = "<form>")]
-pub async fn investments_add_post<'r>(
+#[get("/payouts")]
+pub async fn payouts(conn: DBConnection) -> Template {
+    render_payouts(&conn, vec![]).await
+}
+
+#[post("/payouts/distribute?<game_id>", data = "<form>")]
+pub async fn payouts_distribute_post<'r>(
     conn: DBConnection,
-    mut form: Form<Contextual<'r, InvestmentsForm<'r>>>,
+    game_id: i32,
+    mut form: Form<Contextual<'r, PayoutsForm<'r>>>,
 ) -> Result<Redirect, Template> {
-    let investment = std::mem::replace(&mut form.value, None).unwrap().add;
+    let period = std::mem::replace(&mut form.value, None).unwrap().add;
     let mut errs = Vec::new();
 
-    match investment {
+    match period {
         Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
+            errs.push(form_errors(&errors));
         }
-        Ok(investment) => {
-            let investment = NewInvestment::from(investment);
-            if let Err(err) = investment {
-                errs.push(err.to_string());
+        Ok(period) => {
+            let period = PayoutPeriod::from(period);
+            if let Err(err) = period {
+                errs.push(error_message(&conn, err));
             } else {
-                if let Some(err) = InvestmentsControl::add_investment(&conn, investment.unwrap())
+                if let Some(err) = PayoutsControl::distribute(&conn, game_id, period.unwrap())
                     .await
                     .err()
                 {
-                    errs.push(err.to_string());
+                    errs.push(error_message(&conn, err));
                 }
             }
         }
     }
 
     if !errs.is_empty() {
-        let ctx = CustomContext::<String, String> {
-            values: vec![],
-            table: "Інвестиції",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("investments_add", ctx))
+        Err(render_games(&conn, Some(game_id), None, errs).await)
     } else {
-        Ok(Redirect::to(uri!(investments)))
+        Ok(Redirect::to(uri!(payouts)))
+    }
+}
+
+#[post("/payouts/delete?<id>")]
+pub async fn payouts_delete_post(conn: DBConnection, id: i32) -> Result<Redirect, Template> {
+    match PayoutsControl::delete_payout(&conn, id).await {
+        Ok(()) => Ok(Redirect::to(uri!(payouts))),
+        Err(err) => Err(render_payouts(&conn, vec![error_message(&conn, err)]).await),
     }
 }
 
-#[get("/investments/edit?<id>")]
-pub async fn investments_edit<'r>(conn: DBConnection, id: i32) -> Template {
-    let investment = InvestmentsControl::get_investment_by_id(&conn, id)
-        .await
-        .unwrap();
-    let investors = InvestorsControl::get_investors(&conn).await.unwrap();
-    let investors_id = investors.iter().map(|user| user.id.to_string()).collect();
+#[get("/leaderboards?<game_id>")]
+pub async fn leaderboards(conn: DBConnection, game_id: Option<i32>) -> Template {
+    let ctx = CustomContext {
+        values: GamesControl::get_games(&conn).await.unwrap(),
+        table: "Рейтинг донатерів",
+        errors: vec![],
+        content: LeaderboardsControl::get_leaderboards(&conn, game_id)
+            .await
+            .unwrap(),
+    };
 
-    let investors_name = investors.into_iter().map(|user| user.name).collect();
+    Template::render("leaderboards", ctx)
+}
 
-    let games = GamesControl::get_games(&conn).await.unwrap();
-    let games_id = games.iter().map(|game| game.id.to_string()).collect();
+#[get("/tiers")]
+pub async fn tiers(conn: DBConnection) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: LoyaltyTiersControl::get_tiers(&conn).await.unwrap(),
+        table: "Рівні лояльності",
+        errors: vec![],
+        content: vec![],
+    };
+
+    Template::render("tiers", ctx)
+}
 
-    let games_name = games.into_iter().map(|game| game.name).collect();
+resource_routes!(
+    LoyaltyTiersControl,
+    TiersForm,
+    tiers_routes,
+    tiers_add, tiers_add_post => "/tiers/add",
+    tiers_edit => "/tiers/edit?<id>",
+    tiers_edit_post => "/tiers/edit?<id>&<version>",
+    tiers_delete_post => "/tiers/delete?<id>",
+);
 
+#[get("/departments")]
+pub async fn departments(conn: DBConnection) -> Template {
     let ctx = CustomContext::<_, String> {
-        values: vec![investment],
-        table: "Інвестиції",
+        values: DepartmentsControl::get_departments(&conn).await.unwrap(),
+        table: "Відділи",
         errors: vec![],
-        content: vec![games_id, games_name, investors_id, investors_name],
+        content: vec![],
     };
 
-    Template::render("investments_edit", ctx)
+    Template::render("departments", ctx)
 }
 
-#[post("/investments/edit?<id>", data = "<form>")]
-pub async fn investments_edit_post<'r>(
-    conn: DBConnection,
-    id: i32,
-    mut form: Form<Contextual<'r, InvestmentsForm<'r>>>,
-) -> Result<Redirect, Template> {
-    let investment = std::mem::replace(&mut form.value, None).unwrap().add;
-    let mut errs = Vec::new();
+resource_routes!(
+    DepartmentsControl,
+    DepartmentsForm,
+    departments_routes,
+    departments_add, departments_add_post => "/departments/add",
+    departments_edit => "/departments/edit?<id>",
+    departments_edit_post => "/departments/edit?<id>&<version>",
+    departments_delete_post => "/departments/delete?<id>",
+);
 
-    match investment {
-        Err(errors) => {
-            let names = errors
-                .iter()
-                .map(|err| {
-                    let name = err.name.as_ref().unwrap().to_string();
-                    name.rsplit_once('.')
-                        .unwrap()
-                        .1
-                        .replace("_", " ")
-                        .to_string()
-                })
-                .collect();
-            errs.push(ServerError::NullValues(names).to_string());
-        }
-        Ok(investment) => {
-            let investment = NewInvestment::from(investment);
-            if let Err(err) = investment {
-                errs.push(err.to_string());
-            } else {
-                if let Some(err) =
-                    InvestmentsControl::update_investment(&conn, id, investment.unwrap())
-                        .await
-                        .err()
-                {
-                    errs.push(err.to_string());
-                }
-            }
-        }
-    }
+#[get("/genres")]
+pub async fn genres(conn: DBConnection) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: GenresControl::get_genres(&conn).await.unwrap(),
+        table: "Жанри",
+        errors: vec![],
+        content: vec![],
+    };
 
-    if !errs.is_empty() {
-        let investment = InvestmentsControl::get_investment_by_id(&conn, id)
-            .await
-            .unwrap();
-        let ctx = CustomContext::<_, String> {
-            values: vec![investment],
-            table: "Інвестиції",
-            errors: errs,
-            content: vec![],
-        };
-        Err(Template::render("investments_edit", ctx))
-    } else {
-        Ok(Redirect::to(uri!(investments)))
-    }
+    Template::render("genres", ctx)
 }
 
-#[post("/investments/delete?<id>")]
-pub async fn investments_delete_post<'r>(
-    conn: DBConnection,
-    id: i32,
-) -> Result<Redirect, Template> {
-    InvestmentsControl::delete_investment(&conn, id)
-        .await
-        .unwrap();
+resource_routes!(
+    GenresControl,
+    GenresForm,
+    genres_routes,
+    genres_add, genres_add_post => "/genres/add",
+    genres_edit => "/genres/edit?<id>",
+    genres_edit_post => "/genres/edit?<id>&<version>",
+    genres_delete_post => "/genres/delete?<id>",
+);
 
-    Ok(Redirect::to(uri!(investments)))
+#[get("/platforms")]
+pub async fn platforms(conn: DBConnection) -> Template {
+    let ctx = CustomContext::<_, String> {
+        values: PlatformsControl::get_platforms(&conn).await.unwrap(),
+        table: "Платформи",
+        errors: vec![],
+        content: vec![],
+    };
+
+    Template::render("platforms", ctx)
 }
+
+resource_routes!(
+    PlatformsControl,
+    PlatformsForm,
+    platforms_routes,
+    platforms_add, platforms_add_post => "/platforms/add",
+    platforms_edit => "/platforms/edit?<id>",
+    platforms_edit_post => "/platforms/edit?<id>&<version>",
+    platforms_delete_post => "/platforms/delete?<id>",
+);
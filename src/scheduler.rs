@@ -0,0 +1,650 @@
+use crate::cache;
+use crate::config::{self, AppConfig};
+use crate::controllers::*;
+use crate::errors::ServerError;
+use crate::logging;
+use crate::report::{Report, ReportFormat};
+use crate::{DBConnection, DBPool};
+use anyhow::Result;
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, Timelike};
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::futures::FutureExt;
+use rocket::tokio::time::sleep;
+use rocket::{Build, Orbit, Rocket};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// How far ahead a matching minute is searched for, so that expressions such
+/// as "0 0 30 2 *" are reported as never running.
+const LOOKAHEAD_DAYS: i64 = 5 * 366;
+/// The loop wakes up at least this often, so a changed system clock is
+/// noticed within a minute.
+const MAX_SLEEP_SECONDS: u64 = 60;
+
+/// The `scheduler` table of `Rocket.toml`. `tasks` maps task names to cron
+/// expressions; tasks without an expression only run from the schedule page.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct SchedulerConfig {
+    reports_dir: String,
+    tasks: BTreeMap<String, String>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            reports_dir: "reports".to_string(),
+            tasks: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Task {
+    PopularitySnapshot,
+    ExpireSubscriptions,
+    ClosePastDueJobs,
+    StudioReport,
+    RefreshStatistics,
+}
+
+impl Task {
+    pub const ALL: [Task; 5] = [
+        Task::PopularitySnapshot,
+        Task::ExpireSubscriptions,
+        Task::ClosePastDueJobs,
+        Task::StudioReport,
+        Task::RefreshStatistics,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Task::PopularitySnapshot => "popularity_snapshot",
+            Task::ExpireSubscriptions => "expire_subscriptions",
+            Task::ClosePastDueJobs => "close_past_due_jobs",
+            Task::StudioReport => "studio_report",
+            Task::RefreshStatistics => "refresh_statistics",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Task::PopularitySnapshot => "Знімок популярності видавництв",
+            Task::ExpireSubscriptions => "Завершення прострочених підписок",
+            Task::ClosePastDueJobs => "Закриття завершених позицій",
+            Task::StudioReport => "Зведений звіт студії у PDF",
+            Task::RefreshStatistics => "Перерахунок статистики донатів та інвестицій",
+        }
+    }
+
+    pub fn parse(task: &str) -> Result<Self, ServerError> {
+        Task::ALL
+            .iter()
+            .find(|known| known.as_str() == task)
+            .copied()
+            .ok_or_else(|| ServerError::UnknownTask(task.to_string()))
+    }
+
+    /// Runs the task and returns a short summary of what it did.
+    async fn run(&self, conn: &DBConnection, reports_dir: &PathBuf) -> Result<String> {
+        let today = Local::today().naive_local();
+
+        match self {
+            Task::PopularitySnapshot => {
+                let saved = PublishersControl::snapshot_popularity(conn).await?;
+                Ok(format!("Збережено оцінок видавництв: {}", saved))
+            }
+            Task::ExpireSubscriptions => {
+                let expired = conn
+                    .run(move |sql_conn| SubscriptionsControl::expire_lapsed(sql_conn, today))
+                    .await?;
+                Ok(format!("Завершено підписок: {}", expired))
+            }
+            Task::ClosePastDueJobs => {
+                let closed = conn
+                    .run(move |sql_conn| JobsControl::close_past_due(sql_conn, today))
+                    .await?;
+                Ok(format!("Закрито позицій: {}", closed))
+            }
+            Task::StudioReport => {
+                let report = Report::studio(conn, None).await?;
+                let format = ReportFormat::Pdf;
+                std::fs::create_dir_all(reports_dir)?;
+                let path = reports_dir.join(format!(
+                    "{}-{}.{}",
+                    report.name,
+                    today.format("%Y-%m-%d"),
+                    format.extension()
+                ));
+                std::fs::write(&path, report.render(&format)?)?;
+                Ok(format!("Звіт збережено у {}", path.display()))
+            }
+            Task::RefreshStatistics => {
+                let mismatches = conn
+                    .run(|sql_conn| -> Result<usize> {
+                        let mismatches = StatisticsControl::check(sql_conn)?;
+                        StatisticsControl::refresh_all(sql_conn)?;
+                        Ok(mismatches.len())
+                    })
+                    .await?;
+                cache::invalidate(&[("donation_user_stats", None)]);
+                Ok(format!("Виправлено розбіжностей: {}", mismatches))
+            }
+        }
+    }
+}
+
+/// A cron expression of five fields: minute, hour, day of month, month and
+/// day of week (0 or 7 is Sunday). Every field takes `*`, numbers, ranges
+/// `a-b`, steps `*/n` or `a-b/n` and comma separated lists of those. The
+/// time is the server's local time.
+#[derive(Clone, Debug)]
+pub struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn has(field: u64, value: u32) -> bool {
+    field & (1 << value) != 0
+}
+
+fn parse_number(value: &str, min: u32, max: u32) -> Option<u32> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+}
+
+/// Bits `min..=max` of the values the field allows.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, parse_number(step, 1, max)?),
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (
+                    parse_number(first, min, max)?,
+                    parse_number(last, min, max)?,
+                ),
+                None if step > 1 => (parse_number(range, min, max)?, max),
+                None => {
+                    let value = parse_number(range, min, max)?;
+                    (value, value)
+                }
+            },
+        };
+        if first > last {
+            return None;
+        }
+        for value in (first..=last).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+impl CronExpression {
+    pub fn parse(expression: &str) -> Option<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return None;
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        if has(weekdays, 7) {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Some(CronExpression {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    /// As in cron, when both the day of month and the day of week are
+    /// restricted, a day matching either of them is enough.
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+
+    /// The first matching minute after `after`.
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.date().and_hms(after.hour(), after.minute(), 0) + Duration::minutes(1);
+        let limit = time + Duration::days(LOOKAHEAD_DAYS);
+
+        while time < limit {
+            let date = time.date();
+            if !has(self.months, date.month()) {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd(year, month, 1).and_hms(0, 0, 0);
+            } else if !self.day_matches(date) {
+                time = date.succ().and_hms(0, 0, 0);
+            } else if !has(self.hours, time.hour()) {
+                time = date.and_hms(time.hour(), 0, 0) + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScheduledTask {
+    pub task: Task,
+    pub expression: Option<(String, CronExpression)>,
+}
+
+impl ScheduledTask {
+    pub fn next_run(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.expression
+            .as_ref()
+            .and_then(|(_, cron)| cron.next_after(after))
+    }
+}
+
+/// A row of the schedule page.
+#[derive(Serialize, Debug)]
+pub struct TaskStatus {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub expression: Option<String>,
+    pub next_run: Option<String>,
+    pub last_run: Option<ScheduledRunsControl>,
+}
+
+/// Background tasks of the server, read from the `scheduler` table of
+/// `Rocket.toml` and managed as state so the schedule page can show them.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    pub tasks: Vec<ScheduledTask>,
+    reports_dir: PathBuf,
+    /// `features.reports`; without it the studio report is neither scheduled
+    /// nor run by hand.
+    reports: bool,
+}
+
+impl Schedule {
+    fn from_config(config: SchedulerConfig, reports: bool) -> Result<Self, ServerError> {
+        if let Some(unknown) = config.tasks.keys().find(|name| Task::parse(name).is_err()) {
+            return Err(ServerError::UnknownTask(unknown.clone()));
+        }
+
+        let tasks = Task::ALL
+            .iter()
+            .map(|task| {
+                let expression = match config.tasks.get(task.as_str()) {
+                    Some(_) if *task == Task::StudioReport && !reports => {
+                        info!("studio_report is not scheduled, features.reports is off");
+                        None
+                    }
+                    Some(expression) => Some((
+                        expression.clone(),
+                        CronExpression::parse(expression).ok_or_else(|| {
+                            ServerError::InvalidCron(task.as_str().to_string(), expression.clone())
+                        })?,
+                    )),
+                    None => None,
+                };
+                Ok(ScheduledTask {
+                    task: *task,
+                    expression,
+                })
+            })
+            .collect::<Result<_, ServerError>>()?;
+
+        Ok(Schedule {
+            tasks,
+            reports_dir: PathBuf::from(config.reports_dir),
+            reports,
+        })
+    }
+
+    pub async fn statuses(&self, conn: &DBConnection) -> Result<Vec<TaskStatus>> {
+        let mut last_runs = ScheduledRunsControl::get_last_runs(conn).await?;
+        let now = Local::now().naive_local();
+
+        Ok(self
+            .tasks
+            .iter()
+            .map(|task| {
+                let last_run = last_runs
+                    .iter()
+                    .position(|run| run.task == task.task.as_str())
+                    .map(|index| last_runs.swap_remove(index));
+                TaskStatus {
+                    name: task.task.as_str(),
+                    title: task.task.title(),
+                    expression: task.expression.as_ref().map(|(text, _)| text.clone()),
+                    next_run: task
+                        .next_run(now)
+                        .map(|time| time.format(&config::formats().date_time).to_string()),
+                    last_run,
+                }
+            })
+            .collect())
+    }
+
+    /// Runs the task now and saves the run into the history. The connection
+    /// is taken from the pool for the run only. A panic of the task is saved
+    /// as a failed run instead of taking the scheduler down.
+    pub async fn run(&self, pool: &DBPool, task: Task) -> Result<String> {
+        if task == Task::StudioReport && !self.reports {
+            return Err(ServerError::FeatureDisabled("reports".to_string()).into());
+        }
+
+        let started_at = Local::now().naive_local();
+        let timer = Instant::now();
+        let conn = match pool.get_one().await {
+            Some(conn) => conn,
+            None => {
+                error!(
+                    "Scheduled task {} has no database connection",
+                    task.as_str()
+                );
+                return Err(anyhow::anyhow!("Не вдалося підключитися до бази даних"));
+            }
+        };
+        let outcome = AssertUnwindSafe(task.run(&conn, &self.reports_dir))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|panic| {
+                let message = logging::panic_message(&*panic).unwrap_or("невідома причина");
+                Err(ServerError::TaskPanicked(message.to_string()).into())
+            });
+        let duration_ms = timer.elapsed().as_millis() as i64;
+
+        if let Err(err) = &outcome {
+            error!("Scheduled task {} failed: {}", task.as_str(), err);
+        }
+        if let Err(err) =
+            ScheduledRunsControl::record(&conn, task.as_str(), started_at, duration_ms, &outcome)
+                .await
+        {
+            error!("Failed to save the run of {}: {}", task.as_str(), err);
+        }
+        outcome
+    }
+
+    async fn run_forever(self, pool: DBPool) {
+        let now = Local::now().naive_local();
+        let mut next_runs: Vec<Option<NaiveDateTime>> =
+            self.tasks.iter().map(|task| task.next_run(now)).collect();
+
+        loop {
+            let now = Local::now().naive_local();
+            for (task, next_run) in self.tasks.iter().zip(next_runs.iter_mut()) {
+                if matches!(next_run, Some(time) if *time <= now) {
+                    let _ = self.run(&pool, task.task).await;
+                    *next_run = task.next_run(Local::now().naive_local());
+                }
+            }
+
+            let wait = next_runs
+                .iter()
+                .flatten()
+                .min()
+                .and_then(|next| (*next - Local::now().naive_local()).to_std().ok())
+                .map_or(MAX_SLEEP_SECONDS, |wait| wait.as_secs() + 1)
+                .min(MAX_SLEEP_SECONDS);
+            sleep(std::time::Duration::from_secs(wait)).await;
+        }
+    }
+
+    pub fn fairing() -> impl Fairing {
+        Scheduler
+    }
+}
+
+struct Scheduler;
+
+#[rocket::async_trait]
+impl Fairing for Scheduler {
+    fn info(&self) -> Info {
+        Info {
+            name: "Scheduler",
+            kind: Kind::Ignite | Kind::Liftoff,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket
+            .figment()
+            .extract_inner::<SchedulerConfig>("scheduler")
+        {
+            Ok(config) => config,
+            Err(err) if err.missing() => SchedulerConfig::default(),
+            Err(err) => {
+                error!("Invalid scheduler config: {}", err);
+                return Err(rocket);
+            }
+        };
+
+        let reports = rocket
+            .state::<AppConfig>()
+            .is_none_or(|config| config.features.reports);
+        match Schedule::from_config(config, reports) {
+            Ok(schedule) => Ok(rocket.manage(schedule)),
+            Err(err) => {
+                error!("{}", err);
+                Err(rocket)
+            }
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let schedule = match rocket.state::<Schedule>() {
+            Some(schedule) => schedule.clone(),
+            None => return,
+        };
+        if schedule.tasks.iter().all(|task| task.expression.is_none()) {
+            return;
+        }
+        // Turned off for the profile, the tasks can still be run by hand.
+        if let Some(config) = rocket.state::<AppConfig>() {
+            if !config.features.scheduler {
+                info!("Scheduler is disabled by features.scheduler");
+                return;
+            }
+        }
+
+        match DBPool::get(rocket) {
+            Some(pool) => {
+                rocket::tokio::spawn(schedule.run_forever(pool));
+            }
+            None => error!("Scheduler has no database pool, scheduled tasks will not run"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(year, month, day).and_hms(hour, minute, 0)
+    }
+
+    fn next(expression: &str, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        CronExpression::parse(expression).unwrap().next_after(after)
+    }
+
+    fn bits(values: &[u32]) -> u64 {
+        values.iter().fold(0, |bits, value| bits | 1 << value)
+    }
+
+    #[test]
+    fn parse_field_steps() {
+        assert_eq!(parse_field("*/15", 0, 59), Some(bits(&[0, 15, 30, 45])));
+        assert_eq!(parse_field("10-20/5", 0, 59), Some(bits(&[10, 15, 20])));
+        assert_eq!(parse_field("50/5", 0, 59), Some(bits(&[50, 55])));
+        assert_eq!(parse_field("*/0", 0, 59), None);
+    }
+
+    #[test]
+    fn parse_field_lists() {
+        assert_eq!(parse_field("1,3,5", 1, 12), Some(bits(&[1, 3, 5])));
+        assert_eq!(
+            parse_field("1-3,10-12", 1, 12),
+            Some(bits(&[1, 2, 3, 10, 11, 12]))
+        );
+        assert_eq!(parse_field("1,*/6", 0, 23), Some(bits(&[0, 1, 6, 12, 18])));
+    }
+
+    #[test]
+    fn parse_field_rejects_bad_values() {
+        assert_eq!(parse_field("60", 0, 59), None);
+        assert_eq!(parse_field("0", 1, 31), None);
+        assert_eq!(parse_field("5-1", 0, 59), None);
+        assert_eq!(parse_field("1,,2", 0, 59), None);
+        assert_eq!(parse_field("a", 0, 59), None);
+    }
+
+    #[test]
+    fn parse_needs_five_fields() {
+        assert!(CronExpression::parse("0 0 * *").is_none());
+        assert!(CronExpression::parse("0 0 * * * *").is_none());
+        assert!(CronExpression::parse("0 24 * * *").is_none());
+        assert!(CronExpression::parse("0 0 * * *").is_some());
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        let sunday = CronExpression::parse("0 0 * * 7").unwrap();
+        let zero = CronExpression::parse("0 0 * * 0").unwrap();
+        assert_eq!(sunday.weekdays, zero.weekdays);
+        // 2 January 2022 is a Sunday.
+        assert_eq!(
+            next("0 0 * * 7", at(2021, 12, 31, 12, 0)),
+            Some(at(2022, 1, 2, 0, 0))
+        );
+        assert_eq!(
+            next("0 0 * * 5-7", at(2021, 12, 31, 12, 0)),
+            Some(at(2022, 1, 1, 0, 0))
+        );
+    }
+
+    #[test]
+    fn next_after_steps_and_lists() {
+        assert_eq!(
+            next("*/15 * * * *", at(2022, 1, 1, 10, 7)),
+            Some(at(2022, 1, 1, 10, 15))
+        );
+        assert_eq!(
+            next("*/15 * * * *", at(2022, 1, 1, 10, 15)),
+            Some(at(2022, 1, 1, 10, 30))
+        );
+        assert_eq!(
+            next("0 9,18 * * *", at(2022, 1, 1, 10, 0)),
+            Some(at(2022, 1, 1, 18, 0))
+        );
+        assert_eq!(
+            next("30 2 1 */3 *", at(2022, 2, 10, 0, 0)),
+            Some(at(2022, 4, 1, 2, 30))
+        );
+        assert_eq!(
+            next("0 0 1 1 *", at(2022, 6, 1, 0, 0)),
+            Some(at(2023, 1, 1, 0, 0))
+        );
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // The 15th or a Monday, whichever comes first. 3 January 2022 is a
+        // Monday.
+        assert_eq!(
+            next("0 0 15 * 1", at(2022, 1, 1, 0, 0)),
+            Some(at(2022, 1, 3, 0, 0))
+        );
+        assert_eq!(
+            next("0 0 15 * 1", at(2022, 1, 13, 0, 0)),
+            Some(at(2022, 1, 15, 0, 0))
+        );
+        // With one of them unrestricted only the other one counts.
+        assert_eq!(
+            next("0 0 15 * *", at(2022, 1, 1, 0, 0)),
+            Some(at(2022, 1, 15, 0, 0))
+        );
+        assert_eq!(
+            next("0 0 * * 1", at(2022, 1, 4, 0, 0)),
+            Some(at(2022, 1, 10, 0, 0))
+        );
+    }
+
+    #[test]
+    fn never_matching() {
+        assert_eq!(next("0 0 30 2 *", at(2022, 1, 1, 0, 0)), None);
+        assert_eq!(next("0 0 31 4,6,9,11 *", at(2022, 1, 1, 0, 0)), None);
+        assert_eq!(
+            next("0 0 29 2 *", at(2022, 1, 1, 0, 0)),
+            Some(at(2024, 2, 29, 0, 0))
+        );
+    }
+
+    #[test]
+    fn studio_report_needs_reports() {
+        let config = || SchedulerConfig {
+            reports_dir: "reports".to_string(),
+            tasks: vec![
+                ("studio_report".to_string(), "0 3 1 * *".to_string()),
+                ("popularity_snapshot".to_string(), "0 * * * *".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let scheduled = |schedule: &Schedule, task| {
+            schedule
+                .tasks
+                .iter()
+                .any(|scheduled| scheduled.task == task && scheduled.expression.is_some())
+        };
+
+        let schedule = Schedule::from_config(config(), true).unwrap();
+        assert!(scheduled(&schedule, Task::StudioReport));
+
+        let schedule = Schedule::from_config(config(), false).unwrap();
+        assert!(!scheduled(&schedule, Task::StudioReport));
+        assert!(scheduled(&schedule, Task::PopularitySnapshot));
+    }
+
+    #[test]
+    fn panic_messages() {
+        let message = |panic: fn()| {
+            let payload = std::panic::catch_unwind(panic).unwrap_err();
+            logging::panic_message(&*payload).map(str::to_string)
+        };
+        assert_eq!(message(|| panic!("static")).as_deref(), Some("static"));
+        assert_eq!(
+            message(|| panic!("formatted {}", 1)).as_deref(),
+            Some("formatted 1")
+        );
+        assert_eq!(message(|| std::panic::panic_any(1)), None);
+    }
+}
@@ -1,3 +1,68 @@
+table! {
+    contracts (id) {
+        id -> Int4,
+        game_id -> Int4,
+        publisher_id -> Int4,
+        advance_payment -> Money,
+        revenue_share -> Int2,
+        territory -> Varchar,
+        start_date -> Date,
+        end_date -> Nullable<Date>,
+        version -> Int4,
+    }
+}
+
+table! {
+    departments (id) {
+        id -> Int4,
+        name -> Varchar,
+        version -> Int4,
+    }
+}
+
+table! {
+    donation_daily_stats (game_id, day) {
+        game_id -> Int4,
+        day -> Date,
+        donations_count -> Int4,
+        amount -> Money,
+    }
+}
+
+table! {
+    donation_flags (id) {
+        id -> Int4,
+        donation_id -> Nullable<Int4>,
+        user_id -> Int4,
+        game_id -> Int4,
+        amount -> Money,
+        donation_time -> Timestamp,
+        reasons -> Varchar,
+        flagged_at -> Timestamp,
+        resolution -> Nullable<Varchar>,
+        reviewed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    donation_refunds (id) {
+        id -> Int4,
+        donation_id -> Int4,
+        amount -> Money,
+        reason -> Varchar,
+        refunded_at -> Timestamp,
+    }
+}
+
+table! {
+    donation_user_stats (game_id, user_id) {
+        game_id -> Int4,
+        user_id -> Int4,
+        donations_count -> Int4,
+        amount -> Money,
+    }
+}
+
 table! {
     donations (id) {
         id -> Int4,
@@ -5,6 +70,48 @@ table! {
         game_id -> Int4,
         amount -> Money,
         donation_time -> Timestamp,
+        version -> Int4,
+    }
+}
+
+table! {
+    game_genres (game_id, genre_id) {
+        game_id -> Int4,
+        genre_id -> Int4,
+    }
+}
+
+table! {
+    game_releases (id) {
+        id -> Int4,
+        game_id -> Int4,
+        platform_id -> Int4,
+        region -> Varchar,
+        release_date -> Date,
+        price -> Money,
+    }
+}
+
+table! {
+    game_roles (id) {
+        id -> Int4,
+        game_id -> Int4,
+        position -> Varchar,
+        headcount -> Int4,
+        start_date -> Date,
+        end_date -> Nullable<Date>,
+        salary -> Money,
+    }
+}
+
+table! {
+    game_status_history (id) {
+        id -> Int4,
+        game_id -> Int4,
+        from_status -> Varchar,
+        to_status -> Varchar,
+        changed_at -> Timestamp,
+        changed_by -> Varchar,
     }
 }
 
@@ -12,12 +119,30 @@ table! {
     games (id) {
         id -> Int4,
         name -> Varchar,
-        genre -> Varchar,
         release_date -> Date,
         prime_cost -> Money,
         publisher_id -> Int4,
         cost -> Money,
         is_subscribable -> Bool,
+        status -> Varchar,
+        version -> Int4,
+    }
+}
+
+table! {
+    genres (id) {
+        id -> Int4,
+        name -> Varchar,
+        parent_id -> Nullable<Int4>,
+        version -> Int4,
+    }
+}
+
+table! {
+    investment_stats (game_id) {
+        game_id -> Int4,
+        investments_count -> Int4,
+        invested -> Money,
     }
 }
 
@@ -28,6 +153,7 @@ table! {
         game_id -> Int4,
         share -> Int2,
         invested -> Money,
+        version -> Int4,
     }
 }
 
@@ -36,6 +162,7 @@ table! {
         id -> Int4,
         name -> Varchar,
         is_company -> Bool,
+        version -> Int4,
     }
 }
 
@@ -48,6 +175,48 @@ table! {
         first_work_day -> Date,
         last_work_day -> Nullable<Date>,
         salary -> Money,
+        version -> Int4,
+    }
+}
+
+table! {
+    loyalty_tiers (id) {
+        id -> Int4,
+        name -> Varchar,
+        min_amount -> Money,
+        badge -> Varchar,
+        version -> Int4,
+    }
+}
+
+table! {
+    payouts (id) {
+        id -> Int4,
+        investment_id -> Int4,
+        period_start -> Date,
+        period_end -> Date,
+        net_income -> Money,
+        amount -> Money,
+        paid_at -> Timestamp,
+    }
+}
+
+table! {
+    platforms (id) {
+        id -> Int4,
+        name -> Varchar,
+        version -> Int4,
+    }
+}
+
+table! {
+    publisher_popularity (id) {
+        id -> Int4,
+        publisher_id -> Int4,
+        score -> Int2,
+        computed_score -> Int2,
+        income -> Money,
+        taken_on -> Date,
     }
 }
 
@@ -56,7 +225,41 @@ table! {
         id -> Int4,
         name -> Varchar,
         price -> Money,
-        popularity -> Int2,
+        popularity -> Nullable<Int2>,
+        version -> Int4,
+    }
+}
+
+table! {
+    salary_changes (id) {
+        id -> Int4,
+        job_id -> Int4,
+        salary -> Money,
+        effective_date -> Date,
+    }
+}
+
+table! {
+    sales (id) {
+        id -> Int4,
+        game_id -> Int4,
+        platform -> Varchar,
+        sale_date -> Date,
+        units -> Int4,
+        gross -> Money,
+        refunds -> Money,
+        version -> Int4,
+    }
+}
+
+table! {
+    scheduled_runs (id) {
+        id -> Int4,
+        task -> Varchar,
+        started_at -> Timestamp,
+        duration_ms -> Int8,
+        message -> Text,
+        error -> Nullable<Text>,
     }
 }
 
@@ -65,6 +268,40 @@ table! {
         id -> Int4,
         name -> Varchar,
         birth -> Date,
+        department_id -> Nullable<Int4>,
+        job_title -> Varchar,
+        manager_id -> Nullable<Int4>,
+        employment_start -> Nullable<Date>,
+        employment_end -> Nullable<Date>,
+        email -> Varchar,
+        phone -> Varchar,
+        version -> Int4,
+    }
+}
+
+table! {
+    subscriptions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        game_id -> Int4,
+        plan -> Varchar,
+        price -> Money,
+        start_date -> Date,
+        end_date -> Date,
+        periods_paid -> Int4,
+        cancelled_at -> Nullable<Timestamp>,
+        version -> Int4,
+    }
+}
+
+table! {
+    user_tier_history (id) {
+        id -> Int4,
+        user_id -> Int4,
+        tier_id -> Nullable<Int4>,
+        tier_name -> Varchar,
+        total_donated -> Money,
+        reached_at -> Timestamp,
     }
 }
 
@@ -73,24 +310,71 @@ table! {
         id -> Int4,
         nickname -> Varchar,
         registration_date -> Date,
+        version -> Int4,
     }
 }
 
+joinable!(contracts -> games (game_id));
+joinable!(contracts -> publishers (publisher_id));
+joinable!(donation_daily_stats -> games (game_id));
+joinable!(donation_flags -> donations (donation_id));
+joinable!(donation_flags -> games (game_id));
+joinable!(donation_flags -> users (user_id));
+joinable!(donation_refunds -> donations (donation_id));
+joinable!(donation_user_stats -> games (game_id));
+joinable!(donation_user_stats -> users (user_id));
 joinable!(donations -> games (game_id));
 joinable!(donations -> users (user_id));
+joinable!(game_genres -> games (game_id));
+joinable!(game_genres -> genres (genre_id));
+joinable!(game_releases -> games (game_id));
+joinable!(game_releases -> platforms (platform_id));
+joinable!(game_roles -> games (game_id));
+joinable!(game_status_history -> games (game_id));
 joinable!(games -> publishers (publisher_id));
+joinable!(investment_stats -> games (game_id));
 joinable!(investments -> games (game_id));
 joinable!(investments -> investors (investor_id));
 joinable!(jobs -> games (game_id));
 joinable!(jobs -> staff (staff_id));
+joinable!(payouts -> investments (investment_id));
+joinable!(publisher_popularity -> publishers (publisher_id));
+joinable!(salary_changes -> jobs (job_id));
+joinable!(sales -> games (game_id));
+joinable!(staff -> departments (department_id));
+joinable!(subscriptions -> games (game_id));
+joinable!(subscriptions -> users (user_id));
+joinable!(user_tier_history -> loyalty_tiers (tier_id));
+joinable!(user_tier_history -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    contracts,
+    departments,
+    donation_daily_stats,
+    donation_flags,
+    donation_refunds,
+    donation_user_stats,
     donations,
+    game_genres,
+    game_releases,
+    game_roles,
+    game_status_history,
     games,
+    genres,
+    investment_stats,
     investments,
     investors,
     jobs,
+    loyalty_tiers,
+    payouts,
+    platforms,
+    publisher_popularity,
     publishers,
+    salary_changes,
+    sales,
+    scheduled_runs,
     staff,
+    subscriptions,
+    user_tier_history,
     users,
 );
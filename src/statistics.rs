@@ -0,0 +1,38 @@
+use crate::controllers::StatisticsControl;
+use anyhow::Result;
+
+/// `web_app stats check` lists the rows where the materialized statistics
+/// differ from the raw tables and fails if there are any. `web_app stats
+/// refresh` rebuilds them.
+pub async fn run_cli(args: &[String]) -> Result<()> {
+    let usage = "Використання: web_app stats check|refresh";
+
+    let refresh = match args.first().map(String::as_str) {
+        Some("check") => false,
+        Some("refresh") => true,
+        _ => return Err(anyhow::anyhow!(usage)),
+    };
+    let conn = crate::cli_connection().await?;
+
+    if refresh {
+        conn.run(|sql_conn| StatisticsControl::refresh_all(sql_conn))
+            .await?;
+        println!("Статистику перераховано");
+        return Ok(());
+    }
+
+    let mismatches = conn
+        .run(|sql_conn| StatisticsControl::check(sql_conn))
+        .await?;
+    if mismatches.is_empty() {
+        println!("Статистика узгоджена з даними");
+        return Ok(());
+    }
+    for mismatch in mismatches.iter() {
+        println!("{}", mismatch);
+    }
+    Err(anyhow::anyhow!(
+        "Знайдено розбіжностей: {}. Виправити: web_app stats refresh",
+        mismatches.len()
+    ))
+}